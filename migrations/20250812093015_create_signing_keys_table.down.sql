@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS signing_keys;
+DROP TYPE IF EXISTS key_state;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TYPE key_state AS ENUM ('active', 'verify_only', 'retired');
+
+CREATE TABLE signing_keys (
+  kid TEXT PRIMARY KEY,
+  algorithm VARCHAR(16) NOT NULL,
+  key_material TEXT NOT NULL,
+  state key_state NOT NULL DEFAULT 'active',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  rotated_at TIMESTAMPTZ,
+  retired_at TIMESTAMPTZ
+);
+
+-- Only one key may sign new tokens at any time
+CREATE UNIQUE INDEX signing_keys_single_active ON signing_keys (state) WHERE state = 'active';
@@ -0,0 +1,15 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS security_events;
+
+DROP INDEX IF EXISTS refresh_tokens_token_idx;
+DROP INDEX IF EXISTS refresh_tokens_family_id_idx;
+DROP INDEX IF EXISTS refresh_tokens_user_id_idx;
+
+ALTER TABLE refresh_tokens DROP COLUMN revoked_at;
+ALTER TABLE refresh_tokens DROP COLUMN consumed_at;
+ALTER TABLE refresh_tokens DROP COLUMN family_id;
+
+-- Keep only the newest token of each user before restoring the constraint
+DELETE FROM refresh_tokens a USING refresh_tokens b
+WHERE a.user_id = b.user_id AND a.created_at < b.created_at;
+ALTER TABLE refresh_tokens ADD CONSTRAINT refresh_tokens_user_id_key UNIQUE (user_id);
@@ -0,0 +1,24 @@
+-- Add up migration script here
+ALTER TABLE refresh_tokens DROP CONSTRAINT refresh_tokens_user_id_key;
+
+-- Every rotation inserts a new row in the same family; existing rows start their own family
+ALTER TABLE refresh_tokens ADD COLUMN family_id UUID;
+UPDATE refresh_tokens SET family_id = id;
+ALTER TABLE refresh_tokens ALTER COLUMN family_id SET NOT NULL;
+
+ALTER TABLE refresh_tokens ADD COLUMN consumed_at TIMESTAMPTZ;
+ALTER TABLE refresh_tokens ADD COLUMN revoked_at TIMESTAMPTZ;
+
+CREATE INDEX refresh_tokens_user_id_idx ON refresh_tokens (user_id);
+CREATE INDEX refresh_tokens_family_id_idx ON refresh_tokens (family_id);
+CREATE UNIQUE INDEX refresh_tokens_token_idx ON refresh_tokens (token);
+
+CREATE TABLE security_events (
+  id UUID PRIMARY KEY,
+  user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+  event_type VARCHAR(64) NOT NULL,
+  details JSONB NOT NULL DEFAULT '{}',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX security_events_user_id_idx ON security_events (user_id);
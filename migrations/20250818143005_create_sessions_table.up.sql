@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE sessions (
+  id UUID PRIMARY KEY,
+  user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  device_name VARCHAR(255),
+  user_agent TEXT,
+  ip_address VARCHAR(64),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_used_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
+
+-- A refresh token family is the chain of tokens of one session
+INSERT INTO sessions (id, user_id, created_at, last_used_at, revoked_at)
+SELECT DISTINCT ON (family_id) family_id, user_id, created_at, created_at, revoked_at
+FROM refresh_tokens
+ORDER BY family_id, created_at;
+
+ALTER TABLE refresh_tokens
+  ADD CONSTRAINT refresh_tokens_family_id_fkey
+  FOREIGN KEY (family_id) REFERENCES sessions(id) ON DELETE CASCADE;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+-- Hashes cannot be turned back into tokens
+DELETE FROM refresh_tokens;
+
+DROP INDEX IF EXISTS refresh_tokens_token_hash_idx;
+ALTER TABLE refresh_tokens RENAME COLUMN token_hash TO token;
+CREATE UNIQUE INDEX refresh_tokens_token_idx ON refresh_tokens (token);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Plaintext tokens cannot be rehashed without the application secret, so they are
+-- invalidated: every existing session has to log in again.
+UPDATE sessions SET revoked_at = NOW() WHERE revoked_at IS NULL;
+DELETE FROM refresh_tokens;
+
+DROP INDEX IF EXISTS refresh_tokens_token_idx;
+ALTER TABLE refresh_tokens RENAME COLUMN token TO token_hash;
+CREATE UNIQUE INDEX refresh_tokens_token_hash_idx ON refresh_tokens (token_hash);
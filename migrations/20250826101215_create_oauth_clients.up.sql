@@ -0,0 +1,25 @@
+-- Add up migration script here
+CREATE TABLE oauth_clients (
+  client_id VARCHAR(64) PRIMARY KEY,
+  name VARCHAR(255) NOT NULL,
+  redirect_uris TEXT[] NOT NULL DEFAULT '{}',
+  grant_types TEXT[] NOT NULL DEFAULT '{}',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Codes are single use; the session they opened is kept so a replayed code can end it
+CREATE TABLE oauth_authorization_codes (
+  code_hash VARCHAR(64) PRIMARY KEY,
+  client_id VARCHAR(64) NOT NULL REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+  user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  redirect_uri TEXT NOT NULL,
+  code_challenge VARCHAR(128) NOT NULL,
+  expires_at TIMESTAMPTZ NOT NULL,
+  consumed_at TIMESTAMPTZ,
+  session_id UUID REFERENCES sessions(id) ON DELETE SET NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Sessions opened through an OAuth client can only be refreshed by that client
+ALTER TABLE sessions
+  ADD COLUMN client_id VARCHAR(64) REFERENCES oauth_clients(client_id) ON DELETE CASCADE;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE oauth_clients
+  DROP COLUMN IF EXISTS scopes,
+  DROP COLUMN IF EXISTS client_secret_hash;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Confidential clients hold a secret (stored as a keyed hash); public clients have none
+ALTER TABLE oauth_clients
+  ADD COLUMN client_secret_hash VARCHAR(64),
+  ADD COLUMN scopes TEXT[] NOT NULL DEFAULT '{}';
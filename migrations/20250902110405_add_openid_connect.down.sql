@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE sessions DROP COLUMN IF EXISTS scope;
+
+ALTER TABLE oauth_authorization_codes
+  DROP COLUMN IF EXISTS nonce,
+  DROP COLUMN IF EXISTS scope;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE oauth_authorization_codes
+  ADD COLUMN scope TEXT,
+  ADD COLUMN nonce TEXT;
+
+-- Scope granted to the client, carried over to every token of the session
+ALTER TABLE sessions ADD COLUMN scope TEXT;
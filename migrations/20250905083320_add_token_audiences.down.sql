@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE sessions DROP COLUMN IF EXISTS audience;
+ALTER TABLE oauth_authorization_codes DROP COLUMN IF EXISTS audience;
+ALTER TABLE oauth_clients DROP COLUMN IF EXISTS audiences;
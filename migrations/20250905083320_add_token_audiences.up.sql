@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- Resource servers a client may request tokens for, besides this service
+ALTER TABLE oauth_clients ADD COLUMN audiences TEXT[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE oauth_authorization_codes ADD COLUMN audience TEXT;
+
+-- Audience of the access tokens of the session, kept across refreshes
+ALTER TABLE sessions ADD COLUMN audience TEXT;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Embedded in every token issued to the user; bumping it invalidates them all at once
+ALTER TABLE users ADD COLUMN token_epoch BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP WITH TIME ZONE;
+
+-- Accounts created so far were active from the start
+UPDATE users SET email_verified_at = created_at;
+
+-- Single-use secrets mailed to users, stored hashed like refresh tokens
+CREATE TABLE one_time_tokens (
+    token_hash TEXT PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    purpose TEXT NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    consumed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX one_time_tokens_user_id_idx ON one_time_tokens (user_id, purpose);
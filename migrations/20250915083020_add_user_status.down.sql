@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_users_status;
+
+ALTER TABLE users
+  DROP COLUMN IF EXISTS status_changed_at,
+  DROP COLUMN IF EXISTS status_reason,
+  DROP COLUMN IF EXISTS status;
+
+DROP TYPE IF EXISTS user_status;
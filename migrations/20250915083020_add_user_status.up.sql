@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TYPE user_status AS ENUM ('pending', 'active', 'suspended', 'locked', 'deleted');
+
+-- Existing accounts are in use; new ones wait for their email to be verified
+ALTER TABLE users
+  ADD COLUMN status user_status NOT NULL DEFAULT 'active',
+  ADD COLUMN status_reason TEXT,
+  ADD COLUMN status_changed_at TIMESTAMPTZ;
+
+ALTER TABLE users ALTER COLUMN status SET DEFAULT 'pending';
+
+UPDATE users SET status = 'pending' WHERE email_verified_at IS NULL;
+
+CREATE INDEX idx_users_status ON users (status);
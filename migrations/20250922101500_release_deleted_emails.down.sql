@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX users_email_not_deleted;
+
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- A deleted account no longer holds on to its email address, so it can sign up again
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+
+CREATE UNIQUE INDEX users_email_not_deleted ON users (email) WHERE status <> 'deleted';
@@ -11,6 +11,8 @@ POSTGRES_PASSWORD=postgres
 POSTGRES_HOST=localhost
 DATABASE_URL=postgresql://${POSTGRES_USER}:${POSTGRES_PASSWORD}@${POSTGRES_HOST}:5432/${POSTGRES_DB}
 JWT_SECRET=1234
+TOKEN_HASH_SECRET=5678
+SIGNING_KEY_ENCRYPTION_KEY=$(openssl rand -base64 32)
 REDIS_URL=redis://localhost:6379
 RUST_LOG=rust_auth_service=debug,tower_http=debug,sqlx=debug
 EOF
@@ -41,6 +43,11 @@ echo "🌱 Creating initial data..."
 set -a
 source .env
 set +a
+if [ -z "$SEED_ADMIN_PASSWORD" ]; then
+    read -rsp "Password for admin@example.com: " SEED_ADMIN_PASSWORD
+    echo
+    export SEED_ADMIN_PASSWORD
+fi
 cargo run --bin seed -- --nocapture
 
 echo "✅ Setup complete!"
@@ -0,0 +1,75 @@
+use redis::{AsyncCommands, aio::ConnectionManager};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::epoch::{EPOCH_CACHE_TTL, sign_out_everywhere},
+    db::user::{get_user_status, set_user_status},
+    errors::my_error::MyError,
+    models::user::UserStatus,
+};
+
+fn cache_key(user_id: uuid::Uuid) -> String {
+    format!("user_status:{}", user_id)
+}
+
+// The user's status, none when the user no longer exists. Checked on every authenticated
+// request, so it is cached in Redis like the token epoch.
+pub async fn current_status(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    user_id: uuid::Uuid,
+) -> Result<Option<UserStatus>, MyError> {
+    let key = cache_key(user_id);
+
+    let cached: Option<String> = redis.get(&key).await.map_err(|_| MyError::Internal)?;
+
+    if let Some(status) = cached.as_deref().and_then(UserStatus::parse) {
+        return Ok(Some(status));
+    }
+
+    let status = get_user_status(pool, user_id).await?;
+
+    if let Some(status) = status {
+        let _: () = redis
+            .set_ex(&key, status.as_str(), EPOCH_CACHE_TTL)
+            .await
+            .map_err(|_| MyError::Internal)?;
+    }
+
+    Ok(status)
+}
+
+// Moves the user to `next` if the lifecycle allows it and returns the previous status.
+// Leaving a status that allows signing in also signs the user out everywhere.
+pub async fn change_status(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    user_id: uuid::Uuid,
+    next: UserStatus,
+    reason: Option<&str>,
+) -> Result<UserStatus, MyError> {
+    let previous = get_user_status(pool, user_id).await?.ok_or(MyError::NotFound)?;
+
+    if !previous.can_transition_to(next) {
+        return Err(MyError::Validation(format!(
+            "Cannot change account status from {} to {}",
+            previous.as_str(),
+            next.as_str()
+        )));
+    }
+
+    if !set_user_status(pool, user_id, previous, next, reason).await? {
+        return Err(MyError::Validation("The account status changed concurrently".to_string()));
+    }
+
+    let _: () = redis
+        .set_ex(cache_key(user_id), next.as_str(), EPOCH_CACHE_TTL)
+        .await
+        .map_err(|_| MyError::Internal)?;
+
+    if previous.allows_sign_in() && !next.allows_sign_in() {
+        sign_out_everywhere(pool, redis, user_id).await?;
+    }
+
+    Ok(previous)
+}
@@ -1,76 +1,293 @@
-use jsonwebtoken::{ Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode };
-use redis::{ AsyncCommands, aio::ConnectionManager };
+use jsonwebtoken::{ Header, Validation, decode, decode_header, encode };
+use redis::aio::ConnectionManager;
+use serde::Serialize;
 use sqlx::{Pool, Postgres};
 
-use crate::{ db::role::get_user_roles, errors::my_error::MyError, models::{ auth::{ Claims, TokenType }, user::User } };
+use crate::{ auth::{ epoch::current_epoch, keys::{ SigningKey, key_ring }, oidc::{ issuer, resource_audience }, revocation::{ is_session_revoked, is_token_revoked } }, db::{ role::get_user_roles, user::get_token_epoch }, errors::my_error::MyError, models::{ auth::{ ACR_ELEVATED, ACR_SESSION, AMR_PASSWORD, Actor, Claims, TokenType }, oauth_client::OAuthClient, user::User } };
+
+pub const ACCESS_TOKEN_TTL: i64 = 60 * 15;
+pub const REFRESH_TOKEN_TTL: i64 = 60 * 60 * 24 * 7;
+pub const IMPERSONATION_TOKEN_TTL: i64 = 60 * 5;
+pub const ELEVATED_TOKEN_TTL: i64 = 60 * 5;
+// How recent a login sensitive operations demand, see middleware::auth::require_recent_auth
+pub const STEP_UP_MAX_AGE: i64 = 60 * 5;
+
+// Optional context embedded in the issued tokens
+#[derive(Debug, Clone, Default)]
+pub struct TokenOptions {
+    pub session_id: Option<uuid::Uuid>,
+    pub client_id: Option<String>,
+    pub scope: Option<String>,
+    pub audience: Option<String>, // defaults to this resource server
+    pub auth_time: Option<usize>, // when the user entered their password
+}
 
 pub async fn generate_tokens(pool: &Pool<Postgres>, user: &User) -> Result<(String, String), MyError> {
+    issue_tokens(pool, user, &TokenOptions::default()).await
+}
+
+pub async fn issue_tokens(
+    pool: &Pool<Postgres>,
+    user: &User,
+    options: &TokenOptions
+) -> Result<(String, String), MyError> {
 
     let roles = get_user_roles(&pool, user.id).await?;
+    let epoch = get_token_epoch(pool, user.id).await?.unwrap_or_default();
 
     let now = chrono::Utc::now().timestamp() as usize;
+    let audience = options.audience.clone().unwrap_or_else(|| resource_audience().to_string());
+    let amr: Vec<String> = options.auth_time.iter().map(|_| AMR_PASSWORD.to_string()).collect();
+    let acr = options.auth_time.map(|_| ACR_SESSION.to_string());
 
     let access_claim = Claims {
+        iss: issuer().to_string(),
+        aud: audience,
         sub: user.id,
         email: user.email.clone(),
         roles: roles.clone(),
         jti: uuid::Uuid::new_v4().to_string(),
         iat: now,
-        exp: now + 60 * 15,
+        exp: now + ACCESS_TOKEN_TTL as usize,
         token_type: TokenType::Access,
+        sid: options.session_id,
+        client_id: options.client_id.clone(),
+        scope: options.scope.clone(),
+        machine: false,
+        epoch,
+        act: None,
+        auth_time: options.auth_time,
+        amr: amr.clone(),
+        acr: acr.clone(),
     };
 
+    // Refresh tokens are only ever redeemed here
     let refresh_claim = Claims {
+        iss: issuer().to_string(),
+        aud: resource_audience().to_string(),
         sub: user.id,
         email: user.email.clone(),
         roles: roles.clone(),
         jti: uuid::Uuid::new_v4().to_string(),
         iat: now,
-        exp: now + 60 * 60 * 24 * 7,
+        exp: now + REFRESH_TOKEN_TTL as usize,
         token_type: TokenType::Refresh,
+        sid: options.session_id,
+        client_id: options.client_id.clone(),
+        scope: options.scope.clone(),
+        machine: false,
+        epoch,
+        act: None,
+        auth_time: options.auth_time,
+        amr: amr.clone(),
+        acr: acr.clone(),
     };
 
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set in .env file");
+    let access_token = sign_claims(&access_claim)?;
+    let refresh_token = sign_claims(&refresh_claim)?;
 
-    let access_token = encode(
-        &Header::new(Algorithm::HS256),
-        &access_claim,
-        &EncodingKey::from_secret(secret.as_bytes())
-    ).map_err(|err| MyError::Validation(err.to_string()))?;
+    Ok((access_token, refresh_token))
+}
 
-    let refresh_token = encode(
-        &Header::new(Algorithm::HS256),
-        &refresh_claim,
-        &EncodingKey::from_secret(secret.as_bytes())
-    ).map_err(|err| MyError::Validation(err.to_string()))?;
+// Access token for a client acting on its own behalf. There is no refresh token,
+// the client authenticates again once it expires (RFC 6749 §4.4.3).
+pub fn issue_client_token(client: &OAuthClient, scope: &str, audience: &str) -> Result<String, MyError> {
+    // Client ids are generated as UUIDs, so the client fits the subject claim
+    let subject = uuid::Uuid::parse_str(&client.client_id)
+        .map_err(|_| MyError::Validation("Invalid client id".to_string()))?;
 
-    Ok((access_token, refresh_token))
+    let now = chrono::Utc::now().timestamp() as usize;
+
+    let claims = Claims {
+        iss: issuer().to_string(),
+        aud: audience.to_string(),
+        sub: subject,
+        email: String::new(),
+        roles: vec![],
+        jti: uuid::Uuid::new_v4().to_string(),
+        iat: now,
+        exp: now + ACCESS_TOKEN_TTL as usize,
+        token_type: TokenType::Access,
+        sid: None,
+        client_id: Some(client.client_id.clone()),
+        scope: Some(scope.to_string()),
+        machine: true,
+        epoch: 0,
+        act: None,
+        auth_time: None,
+        amr: vec![],
+        acr: None,
+    };
+
+    sign_claims(&claims)
 }
 
-pub fn decode_access_token(token: &str) -> Result<Claims, MyError> {
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set in .env file");
+// Short-lived access token for `user` on behalf of `actor` (RFC 8693 impersonation).
+// It belongs to no session and comes without a refresh token.
+pub async fn issue_impersonation_token(
+    pool: &Pool<Postgres>,
+    user: &User,
+    actor: uuid::Uuid
+) -> Result<(String, Claims), MyError> {
+    let roles = get_user_roles(pool, user.id).await?;
+    let epoch = get_token_epoch(pool, user.id).await?.unwrap_or_default();
+
+    let now = chrono::Utc::now().timestamp() as usize;
+
+    let claims = Claims {
+        iss: issuer().to_string(),
+        aud: resource_audience().to_string(),
+        sub: user.id,
+        email: user.email.clone(),
+        roles,
+        jti: uuid::Uuid::new_v4().to_string(),
+        iat: now,
+        exp: now + IMPERSONATION_TOKEN_TTL as usize,
+        token_type: TokenType::Access,
+        sid: None,
+        client_id: None,
+        scope: None,
+        machine: false,
+        epoch,
+        act: Some(Actor { sub: actor }),
+        auth_time: None,
+        amr: vec![],
+        acr: None,
+    };
+
+    let token = sign_claims(&claims)?;
+
+    Ok((token, claims))
+}
+
+// Short-lived access token proving the user just entered their password again. It keeps
+// the session, client, scope and audience of the token it steps up from.
+pub async fn issue_elevated_token(
+    pool: &Pool<Postgres>,
+    user: &User,
+    current: &Claims
+) -> Result<String, MyError> {
+    let roles = get_user_roles(pool, user.id).await?;
+    let epoch = get_token_epoch(pool, user.id).await?.unwrap_or_default();
+
+    let now = chrono::Utc::now().timestamp() as usize;
+
+    let claims = Claims {
+        iss: issuer().to_string(),
+        aud: current.aud.clone(),
+        sub: user.id,
+        email: user.email.clone(),
+        roles,
+        jti: uuid::Uuid::new_v4().to_string(),
+        iat: now,
+        exp: now + ELEVATED_TOKEN_TTL as usize,
+        token_type: TokenType::Access,
+        sid: current.sid,
+        client_id: current.client_id.clone(),
+        scope: current.scope.clone(),
+        machine: false,
+        epoch,
+        act: None,
+        auth_time: Some(now),
+        amr: vec![AMR_PASSWORD.to_string()],
+        acr: Some(ACR_ELEVATED.to_string()),
+    };
+
+    sign_claims(&claims)
+}
+
+pub(crate) fn sign_claims<T: Serialize>(claims: &T) -> Result<String, MyError> {
+    sign_claims_with(key_ring().active(), claims)
+}
+
+pub(crate) fn sign_claims_with<T: Serialize>(key: &SigningKey, claims: &T) -> Result<String, MyError> {
+    let header = Header { kid: Some(key.kid.clone()), ..Header::new(key.algorithm) };
+
+    encode(
+        &header,
+        claims,
+        key.encoding_key()
+    ).map_err(|err| MyError::Validation(err.to_string()))
+}
+
+// Verifies signature, expiry and issuer. With an `audience` the token must also be
+// addressed to it; without one any audience is accepted (introspection, revocation).
+pub fn decode_token(token: &str, audience: Option<&str>) -> Result<Claims, MyError> {
+    let header = decode_header(token)
+        .map_err(|_| MyError::Validation("The token has expired or is invalid".to_string()))?;
+
+    let key_ring = key_ring();
+    let key = key_ring
+        .verification_key(header.kid.as_deref())
+        .ok_or(MyError::Validation("The token has expired or is invalid".to_string()))?;
+
+    let mut validation = Validation::new(key.algorithm);
+    validation.set_issuer(&[issuer()]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+    match audience {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+
     let data = decode(
         token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::new(Algorithm::HS256)
+        key.decoding_key(),
+        &validation
     ).map_err(|_| MyError::Validation("The token has expired or is invalid".to_string()))?;
 
     Ok(data.claims)
 }
 
+// A token this resource server accepts: ours and addressed to us
+pub fn decode_access_token(token: &str) -> Result<Claims, MyError> {
+    decode_token(token, Some(resource_audience()))
+}
+
 pub async fn validate_jwt(
+    pool: &Pool<Postgres>,
     redis: &mut ConnectionManager,
     access_token: &str
 ) -> Result<Claims, MyError> {
-    let claims = decode_access_token(access_token)?;
+    validate_token(pool, redis, access_token, Some(resource_audience())).await
+}
 
-    let key_jti = format!("jti_revoked:{}", claims.jti);
+pub async fn validate_token(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    token: &str,
+    audience: Option<&str>
+) -> Result<Claims, MyError> {
+    let claims = decode_token(token, audience)?;
+
+    check_not_revoked(pool, redis, &claims).await?;
 
-    let is_revoked: Option<bool> = redis.get(key_jti).await.unwrap();
+    Ok(claims)
+}
 
-    if is_revoked.is_some() {
+// Rejects a well-formed token that was revoked since it was issued, on its own or
+// along with every token of the user
+pub async fn check_not_revoked(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    claims: &Claims
+) -> Result<(), MyError> {
+    if is_token_revoked(redis, &claims.jti).await? {
         return Err(MyError::Validation("The token expired or is invalid".to_string()));
     }
 
-    Ok(claims)
+    // Issued before the user's last logout everywhere, password or role change.
+    // Machine tokens belong to a client, not a user, and have no epoch.
+    if !claims.machine && current_epoch(pool, redis, claims.sub).await? != Some(claims.epoch) {
+        return Err(MyError::Validation("The token expired or is invalid".to_string()));
+    }
+
+    // Revoking a session ends its access tokens too, not only its refresh tokens
+    if let Some(session_id) = claims.sid
+        && is_session_revoked(pool, redis, session_id).await?
+    {
+        return Err(MyError::Validation("The token expired or is invalid".to_string()));
+    }
+
+    Ok(())
 }
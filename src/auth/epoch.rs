@@ -0,0 +1,75 @@
+use redis::{AsyncCommands, aio::ConnectionManager};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    db::{
+        auth::revoke_user_sessions,
+        user::{get_token_epoch, increment_token_epoch},
+    },
+    errors::my_error::MyError,
+};
+
+// Bounds how long a bump made without going through `bump_epoch` (e.g. a role change
+// in the database layer) can go unnoticed
+pub const EPOCH_CACHE_TTL: u64 = 60;
+
+fn cache_key(user_id: uuid::Uuid) -> String {
+    format!("token_epoch:{}", user_id)
+}
+
+// The user's current token epoch, none when the user no longer exists. Checked on every
+// authenticated request, so it is served from Redis and only read from Postgres on a miss.
+pub async fn current_epoch(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    user_id: uuid::Uuid,
+) -> Result<Option<i64>, MyError> {
+    let key = cache_key(user_id);
+
+    let cached: Option<i64> = redis.get(&key).await.map_err(|_| MyError::Internal)?;
+
+    if cached.is_some() {
+        return Ok(cached);
+    }
+
+    let epoch = get_token_epoch(pool, user_id).await?;
+
+    if let Some(epoch) = epoch {
+        let _: () = redis
+            .set_ex(&key, epoch, EPOCH_CACHE_TTL)
+            .await
+            .map_err(|_| MyError::Internal)?;
+    }
+
+    Ok(epoch)
+}
+
+// Invalidates every token issued to the user so far
+pub async fn bump_epoch(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    user_id: uuid::Uuid,
+) -> Result<i64, MyError> {
+    let epoch = increment_token_epoch(pool, user_id).await?;
+
+    let _: () = redis
+        .set_ex(cache_key(user_id), epoch, EPOCH_CACHE_TTL)
+        .await
+        .map_err(|_| MyError::Internal)?;
+
+    Ok(epoch)
+}
+
+// Kills all outstanding tokens and ends every session, so the session list matches.
+// Returns the number of sessions revoked.
+pub async fn sign_out_everywhere(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    user_id: uuid::Uuid,
+) -> Result<u64, MyError> {
+    bump_epoch(pool, redis, user_id).await?;
+
+    let revoked = revoke_user_sessions(pool, user_id, None, None).await?;
+
+    Ok(revoked.len() as u64)
+}
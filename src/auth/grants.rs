@@ -0,0 +1,137 @@
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::{
+        auth::{TokenOptions, check_not_revoked, decode_access_token, issue_tokens},
+        revocation::revoke_session_tokens,
+    },
+    db::{
+        auth::{
+            consume_refresh_token, create_refresh_token, create_session, get_refresh_token,
+            get_session, revoke_token_family, touch_session,
+        },
+        security_event::record_security_event,
+        user::get_user_account_by_id,
+    },
+    errors::my_error::MyError,
+    models::{
+        app::AppState,
+        auth::TokenType,
+        security_event::{SecurityEvent, SecurityEventType},
+        session::Session,
+        user::User,
+    },
+};
+
+// Opens the session and issues its first token pair, the refresh token starting the family
+pub async fn start_session(
+    pool: &Pool<Postgres>,
+    user: &User,
+    session: &Session,
+) -> Result<(String, String), MyError> {
+    create_session(pool, session).await?;
+
+    let options = TokenOptions {
+        session_id: Some(session.id),
+        client_id: session.client_id.clone(),
+        scope: session.scope.clone(),
+        audience: session.audience.clone(),
+        auth_time: Some(session.created_at.timestamp() as usize),
+    };
+    let (access_token, refresh_token) = issue_tokens(pool, user, &options).await?;
+
+    create_refresh_token(pool, user.id, session.id, &refresh_token).await?;
+
+    Ok((access_token, refresh_token))
+}
+
+// Exchanges a refresh token for a new pair within the same family. `client_id` is the
+// OAuth client redeeming it, none for direct logins; a session only refreshes for its own client.
+pub async fn rotate_refresh_token(
+    app_state: &AppState,
+    refresh_token: &str,
+    client_id: Option<&str>,
+) -> Result<(String, String), MyError> {
+    // Only signature, expiry and audience for now: a replayed token must reach the reuse
+    // check below even if it has been revoked since
+    let claims = decode_access_token(refresh_token)?;
+
+    if claims.token_type != TokenType::Refresh {
+        return Err(MyError::Validation("Invalid token".to_string()));
+    }
+
+    let stored_token = get_refresh_token(&app_state.pool, refresh_token)
+        .await?
+        .ok_or(MyError::Validation("Invalid token".to_string()))?;
+
+    if stored_token.revoked_at.is_some() {
+        return Err(MyError::Validation("Invalid token".to_string()));
+    }
+
+    let session = get_session(&app_state.pool, stored_token.family_id)
+        .await?
+        .ok_or(MyError::Validation("Invalid token".to_string()))?;
+
+    if session.client_id.as_deref() != client_id {
+        return Err(MyError::Validation("Invalid token".to_string()));
+    }
+
+    // A refresh token is single use: presenting it twice means it leaked, so the whole
+    // family is revoked and both the attacker and the legitimate client must log in again
+    if !consume_refresh_token(&app_state.pool, stored_token.id).await? {
+        revoke_token_family(&app_state.pool, stored_token.family_id).await?;
+        revoke_session_tokens(&mut app_state.redis.clone(), &[stored_token.family_id]).await?;
+
+        tracing::warn!(
+            "Refresh token reuse detected for user {}, family {} revoked",
+            stored_token.user_id,
+            stored_token.family_id
+        );
+
+        record_security_event(
+            &app_state.pool,
+            SecurityEvent::new(
+                Some(stored_token.user_id),
+                SecurityEventType::RefreshTokenReuse,
+                serde_json::json!({
+                    "family_id": stored_token.family_id,
+                    "jti": claims.jti,
+                }),
+            ),
+        )
+        .await?;
+
+        return Err(MyError::Validation("Invalid token".to_string()));
+    }
+
+    let mut redis_conn = app_state.redis.clone();
+    check_not_revoked(&app_state.pool, &mut redis_conn, &claims).await?;
+
+    let user = get_user_account_by_id(&app_state.pool, claims.sub).await?;
+
+    if user.is_none() {
+        return Err(MyError::Validation("User not found".to_string()));
+    }
+
+    let user = user.unwrap();
+
+    // Suspended or locked accounts cannot keep their sessions alive
+    user.status.check_sign_in()?;
+
+    // The consumed row keeps the old token from being redeemed again, no denylist entry needed
+
+    let options = TokenOptions {
+        session_id: Some(stored_token.family_id),
+        client_id: session.client_id,
+        scope: session.scope,
+        audience: session.audience,
+        // Refreshing does not re-authenticate the user
+        auth_time: Some(session.created_at.timestamp() as usize),
+    };
+    let (access_token, refresh_token) = issue_tokens(&app_state.pool, &user, &options).await?;
+
+    create_refresh_token(&app_state.pool, user.id, stored_token.family_id, &refresh_token).await?;
+    touch_session(&app_state.pool, stored_token.family_id).await?;
+
+    Ok((access_token, refresh_token))
+}
@@ -0,0 +1,403 @@
+use std::{
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters,
+        OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use ring::{
+    rand::{SecureRandom, SystemRandom},
+    rsa::PublicKeyComponents,
+    signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair},
+};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::auth::REFRESH_TOKEN_TTL,
+    db::signing_key::{
+        get_trusted_signing_keys, insert_initial_signing_key, retire_signing_keys,
+        rotate_signing_key, update_signing_key_material,
+    },
+    errors::my_error::MyError,
+    models::signing_key::{KeyState, RotateKeyInput, SigningKeyRecord},
+    services::key_encryption::{decrypt_key_material, encrypt_key_material, is_encrypted},
+};
+
+// Until `load_key_ring` runs (or when there is no database, e.g. in tests) the ring holds
+// the single key configured through the environment.
+static KEY_RING: LazyLock<RwLock<Arc<KeyRing>>> = LazyLock::new(|| {
+    let key = SigningKey::from_env().expect("Failed to load the JWT signing key");
+    RwLock::new(Arc::new(KeyRing { keys: vec![key] }))
+});
+
+pub fn key_ring() -> Arc<KeyRing> {
+    KEY_RING.read().unwrap().clone()
+}
+
+fn install_key_ring(ring: KeyRing) {
+    *KEY_RING.write().unwrap() = Arc::new(ring);
+}
+
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub state: KeyState,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    public_jwk: Option<Jwk>,
+}
+
+impl SigningKey {
+    pub fn from_secret(kid: &str, secret: &[u8]) -> Self {
+        SigningKey {
+            kid: kid.to_string(),
+            algorithm: Algorithm::HS256,
+            state: KeyState::Active,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            public_jwk: None,
+        }
+    }
+
+    // Loads a PKCS#8 (or PKCS#1 for RSA) private key and derives the public half from it
+    pub fn from_pem(kid: &str, algorithm: Algorithm, pem: &[u8]) -> Result<Self, MyError> {
+        let der = pem::parse(pem)
+            .map_err(|err| MyError::Key(err.to_string()))?
+            .into_contents();
+
+        let (encoding_key, decoding_key, params) = match algorithm {
+            Algorithm::RS256 => {
+                let key_pair = RsaKeyPair::from_pkcs8(&der)
+                    .or_else(|_| RsaKeyPair::from_der(&der))
+                    .map_err(|err| MyError::Key(err.to_string()))?;
+                let components: PublicKeyComponents<Vec<u8>> = key_pair.public().into();
+
+                (
+                    EncodingKey::from_rsa_pem(pem).map_err(|err| MyError::Key(err.to_string()))?,
+                    DecodingKey::from_rsa_raw_components(&components.n, &components.e),
+                    AlgorithmParameters::RSA(RSAKeyParameters {
+                        key_type: RSAKeyType::RSA,
+                        n: URL_SAFE_NO_PAD.encode(&components.n),
+                        e: URL_SAFE_NO_PAD.encode(&components.e),
+                    }),
+                )
+            }
+            Algorithm::ES256 => {
+                let key_pair = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    &der,
+                    &SystemRandom::new(),
+                )
+                .map_err(|err| MyError::Key(err.to_string()))?;
+                // Uncompressed SEC1 point: 0x04 || x || y
+                let point = key_pair.public_key().as_ref();
+                let x = URL_SAFE_NO_PAD.encode(&point[1..33]);
+                let y = URL_SAFE_NO_PAD.encode(&point[33..65]);
+
+                (
+                    EncodingKey::from_ec_der(&der),
+                    DecodingKey::from_ec_components(&x, &y)
+                        .map_err(|err| MyError::Key(err.to_string()))?,
+                    AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x,
+                        y,
+                    }),
+                )
+            }
+            Algorithm::EdDSA => {
+                let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+                    .map_err(|err| MyError::Key(err.to_string()))?;
+                let x = URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref());
+
+                (
+                    EncodingKey::from_ed_der(&der),
+                    DecodingKey::from_ed_components(&x)
+                        .map_err(|err| MyError::Key(err.to_string()))?,
+                    AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x,
+                    }),
+                )
+            }
+            other => {
+                return Err(MyError::Key(format!(
+                    "Unsupported asymmetric algorithm: {:?}",
+                    other
+                )));
+            }
+        };
+
+        let public_jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm(algorithm)),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+
+        Ok(SigningKey {
+            kid: kid.to_string(),
+            algorithm,
+            state: KeyState::Active,
+            encoding_key,
+            decoding_key,
+            public_jwk: Some(public_jwk),
+        })
+    }
+
+    pub fn from_record(record: &SigningKeyRecord) -> Result<Self, MyError> {
+        let algorithm = parse_algorithm(&record.algorithm)?;
+
+        let mut key = if algorithm == Algorithm::HS256 {
+            let secret = STANDARD
+                .decode(&record.key_material)
+                .map_err(|err| MyError::Key(err.to_string()))?;
+            SigningKey::from_secret(&record.kid, &secret)
+        } else {
+            SigningKey::from_pem(&record.kid, algorithm, record.key_material.as_bytes())?
+        };
+        key.state = record.state;
+
+        Ok(key)
+    }
+
+    // JWT_ALGORITHM selects the algorithm (HS256 by default). HS256 uses JWT_SECRET,
+    // the asymmetric ones read the private key from JWT_PRIVATE_KEY_PATH.
+    pub fn from_env() -> Result<Self, MyError> {
+        SigningKey::from_record(&env_key_record()?)
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    // None for HS256: a shared secret must never be published
+    pub fn public_jwk(&self) -> Option<&Jwk> {
+        self.public_jwk.as_ref()
+    }
+}
+
+pub struct KeyRing {
+    keys: Vec<SigningKey>,
+}
+
+impl KeyRing {
+    pub fn new(keys: Vec<SigningKey>) -> Result<Self, MyError> {
+        let active = keys.iter().filter(|key| key.state == KeyState::Active).count();
+
+        if active != 1 {
+            return Err(MyError::Key(format!(
+                "Expected exactly one active signing key, found {}",
+                active
+            )));
+        }
+
+        Ok(KeyRing {
+            keys: keys
+                .into_iter()
+                .filter(|key| key.state != KeyState::Retired)
+                .collect(),
+        })
+    }
+
+    // The key that signs new tokens
+    pub fn active(&self) -> &SigningKey {
+        self.keys
+            .iter()
+            .find(|key| key.state == KeyState::Active)
+            .expect("Key ring always holds an active key")
+    }
+
+    // Tokens without a `kid` predate key rotation and can only match the active key
+    pub fn verification_key(&self, kid: Option<&str>) -> Option<&SigningKey> {
+        match kid {
+            Some(kid) => self.keys.iter().find(|key| key.kid == kid),
+            None => Some(self.active()),
+        }
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .filter_map(|key| key.public_jwk().cloned())
+                .collect(),
+        }
+    }
+}
+
+pub fn parse_algorithm(name: &str) -> Result<Algorithm, MyError> {
+    match name {
+        "HS256" => Ok(Algorithm::HS256),
+        "RS256" => Ok(Algorithm::RS256),
+        "ES256" => Ok(Algorithm::ES256),
+        "EdDSA" => Ok(Algorithm::EdDSA),
+        other => Err(MyError::Key(format!("Unsupported JWT algorithm: {}", other))),
+    }
+}
+
+pub fn algorithm_name(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::RS256 => "RS256",
+        Algorithm::ES256 => "ES256",
+        Algorithm::EdDSA => "EdDSA",
+        _ => "HS256",
+    }
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+        _ => KeyAlgorithm::HS256,
+    }
+}
+
+fn env_key_record() -> Result<SigningKeyRecord, MyError> {
+    dotenvy::dotenv().ok();
+
+    let algorithm = std::env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string());
+
+    let key_material = if parse_algorithm(&algorithm)? == Algorithm::HS256 {
+        let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set in .env file");
+        STANDARD.encode(secret.as_bytes())
+    } else {
+        let path = std::env::var("JWT_PRIVATE_KEY_PATH")
+            .expect("JWT_PRIVATE_KEY_PATH must be set when using an asymmetric JWT_ALGORITHM");
+        std::fs::read_to_string(&path)
+            .map_err(|err| MyError::Key(format!("Failed to read {}: {}", path, err)))?
+    };
+
+    let mut record = SigningKeyRecord::new(algorithm, key_material);
+    record.kid = std::env::var("JWT_KEY_ID").unwrap_or_else(|_| "primary".to_string());
+
+    Ok(record)
+}
+
+// Creates key material for `algorithm`. RSA keys cannot be generated here and must be supplied.
+pub fn generate_key_material(algorithm: Algorithm) -> Result<String, MyError> {
+    let rng = SystemRandom::new();
+
+    let pkcs8 = match algorithm {
+        Algorithm::HS256 => {
+            let mut secret = [0u8; 64];
+            rng.fill(&mut secret).map_err(|_| MyError::Internal)?;
+            return Ok(STANDARD.encode(secret));
+        }
+        Algorithm::ES256 => EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng),
+        Algorithm::EdDSA => Ed25519KeyPair::generate_pkcs8(&rng),
+        _ => {
+            return Err(MyError::Validation(
+                "RS256 keys must be provided as a PEM encoded private key".to_string(),
+            ));
+        }
+    }
+    .map_err(|_| MyError::Internal)?;
+
+    Ok(pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref())))
+}
+
+// Loads the trusted keys from the database, bootstrapping it with the environment key
+pub async fn load_key_ring(pool: &Pool<Postgres>) -> Result<(), MyError> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::seconds(REFRESH_TOKEN_TTL);
+    retire_signing_keys(pool, cutoff).await?;
+
+    let mut records = get_trusted_signing_keys(pool).await?;
+
+    if records.is_empty() {
+        insert_initial_signing_key(pool, &sealed(&env_key_record()?)?).await?;
+        records = get_trusted_signing_keys(pool).await?;
+    }
+
+    for record in records.iter_mut() {
+        if is_encrypted(&record.key_material) {
+            record.key_material = decrypt_key_material(&record.kid, &record.key_material)?;
+        } else {
+            // Stored before key material was encrypted
+            let encrypted = encrypt_key_material(&record.kid, &record.key_material)?;
+            update_signing_key_material(pool, &record.kid, &encrypted).await?;
+        }
+    }
+
+    let keys = records
+        .iter()
+        .map(SigningKey::from_record)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    install_key_ring(KeyRing::new(keys)?);
+
+    Ok(())
+}
+
+// The record as stored: private material never reaches the database in plaintext
+fn sealed(record: &SigningKeyRecord) -> Result<SigningKeyRecord, MyError> {
+    Ok(SigningKeyRecord {
+        key_material: encrypt_key_material(&record.kid, &record.key_material)?,
+        ..record.clone()
+    })
+}
+
+// Other instances pick up rotations on their next refresh
+pub fn spawn_key_ring_refresh(pool: Pool<Postgres>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = load_key_ring(&pool).await {
+                tracing::error!("Failed to refresh signing keys: {}", err);
+            }
+        }
+    });
+}
+
+// The previous key keeps verifying until every token it signed has expired
+pub async fn rotate_keys(
+    pool: &Pool<Postgres>,
+    input: RotateKeyInput,
+) -> Result<SigningKeyRecord, MyError> {
+    let algorithm = match input.algorithm {
+        Some(name) => parse_algorithm(&name).map_err(|_| MyError::BadRequest)?,
+        None => key_ring().active().algorithm,
+    };
+
+    let key_material = match input.private_key_pem {
+        Some(pem) => pem,
+        None => generate_key_material(algorithm)?,
+    };
+
+    let record = SigningKeyRecord::new(algorithm_name(algorithm).to_string(), key_material);
+
+    // Refuse to store material that cannot be loaded back
+    SigningKey::from_record(&record).map_err(|_| MyError::BadRequest)?;
+
+    rotate_signing_key(pool, &sealed(&record)?).await?;
+    load_key_ring(pool).await?;
+
+    tracing::info!("Rotated signing key, new kid: {}", record.kid);
+
+    Ok(record)
+}
@@ -0,0 +1,263 @@
+use std::sync::LazyLock;
+
+use redis::{AsyncCommands, aio::ConnectionManager};
+
+use crate::{
+    db::{security_event::record_security_event, user::get_user_by_email},
+    errors::my_error::MyError,
+    models::{
+        app::AppState,
+        security_event::{SecurityEvent, SecurityEventType},
+        user::User,
+    },
+    services::{mailer::Email, password::verify_password},
+};
+
+// Failed password checks tolerated before logins are refused for a while
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub max_account_failures: u64, // per email address, whether or not it is registered
+    pub max_ip_failures: u64,      // per client IP, across all accounts
+    pub window: u64,               // seconds over which failures are counted
+    pub duration: u64,             // seconds a lockout lasts
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        LockoutPolicy {
+            max_account_failures: 5,
+            max_ip_failures: 20,
+            window: 60 * 15,
+            duration: 60 * 15,
+        }
+    }
+}
+
+impl LockoutPolicy {
+    // LOGIN_MAX_FAILURES, LOGIN_MAX_IP_FAILURES, LOGIN_FAILURE_WINDOW and
+    // LOGIN_LOCKOUT_DURATION override the defaults
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    // Same as from_env, reading the variables through `lookup`
+    pub fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = LockoutPolicy::default();
+        let var = |name: &str, default: u64| {
+            lookup(name)
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+        };
+
+        LockoutPolicy {
+            max_account_failures: var("LOGIN_MAX_FAILURES", defaults.max_account_failures),
+            max_ip_failures: var("LOGIN_MAX_IP_FAILURES", defaults.max_ip_failures),
+            window: var("LOGIN_FAILURE_WINDOW", defaults.window),
+            duration: var("LOGIN_LOCKOUT_DURATION", defaults.duration),
+        }
+    }
+
+    pub fn locks_account(&self, failures: u64) -> bool {
+        failures >= self.max_account_failures
+    }
+
+    pub fn locks_ip(&self, failures: u64) -> bool {
+        failures >= self.max_ip_failures
+    }
+}
+
+static POLICY: LazyLock<LockoutPolicy> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+    LockoutPolicy::from_env()
+});
+
+pub fn lockout_policy() -> &'static LockoutPolicy {
+    &POLICY
+}
+
+// Accounts are keyed by email so unknown addresses are throttled like real ones
+fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn failures_key(kind: &str, id: &str) -> String {
+    format!("login_failures:{}:{}", kind, id)
+}
+
+fn lock_key(kind: &str, id: &str) -> String {
+    format!("login_lock:{}:{}", kind, id)
+}
+
+// Refuses the attempt while the account or the client IP is locked out
+pub async fn check_login_allowed(
+    redis: &mut ConnectionManager,
+    email: &str,
+    ip: Option<&str>,
+) -> Result<(), MyError> {
+    let mut keys = vec![lock_key("account", &account_key(email))];
+    keys.extend(ip.map(|ip| lock_key("ip", ip)));
+
+    let locked: u64 = redis.exists(&keys).await.map_err(|_| MyError::Internal)?;
+
+    if locked > 0 {
+        return Err(MyError::TooManyRequests);
+    }
+
+    Ok(())
+}
+
+// Counts one failure within the window and returns the count so far
+async fn count_failure(
+    redis: &mut ConnectionManager,
+    policy: &LockoutPolicy,
+    kind: &str,
+    id: &str,
+) -> Result<u64, MyError> {
+    let key = failures_key(kind, id);
+
+    let failures: u64 = redis.incr(&key, 1).await.map_err(|_| MyError::Internal)?;
+
+    if failures == 1 {
+        let _: () = redis
+            .expire(&key, policy.window as i64)
+            .await
+            .map_err(|_| MyError::Internal)?;
+    }
+
+    Ok(failures)
+}
+
+async fn lock(
+    redis: &mut ConnectionManager,
+    policy: &LockoutPolicy,
+    kind: &str,
+    id: &str,
+) -> Result<(), MyError> {
+    let _: () = redis
+        .set_ex(lock_key(kind, id), 1, policy.duration)
+        .await
+        .map_err(|_| MyError::Internal)?;
+    let _: () = redis.del(failures_key(kind, id)).await.map_err(|_| MyError::Internal)?;
+
+    Ok(())
+}
+
+// Records a failed login for `email` from `ip` and runs the lockout hook when the
+// account gets locked
+pub async fn record_login_failure(
+    app_state: &AppState,
+    email: &str,
+    ip: Option<&str>,
+) -> Result<(), MyError> {
+    let policy = lockout_policy();
+    let mut redis_conn = app_state.redis.clone();
+    let account = account_key(email);
+
+    let failures = count_failure(&mut redis_conn, policy, "account", &account).await?;
+
+    if policy.locks_account(failures) {
+        lock(&mut redis_conn, policy, "account", &account).await?;
+        on_account_locked(app_state, &account, failures, ip).await?;
+    }
+
+    if let Some(ip) = ip {
+        let failures = count_failure(&mut redis_conn, policy, "ip", ip).await?;
+
+        if policy.locks_ip(failures) {
+            lock(&mut redis_conn, policy, "ip", ip).await?;
+            tracing::warn!("Logins from {} locked out after {} failed attempts", ip, failures);
+        }
+    }
+
+    Ok(())
+}
+
+// Checks the password of a signed-in user before something sensitive. A wrong password
+// counts as a failed login, so a stolen token cannot be used to guess it without limit.
+pub async fn verify_current_password(
+    app_state: &AppState,
+    user: &User,
+    password: &str,
+    ip: Option<&str>,
+) -> Result<(), MyError> {
+    let mut redis_conn = app_state.redis.clone();
+    check_login_allowed(&mut redis_conn, &user.email, ip).await?;
+
+    if !verify_password(&user.password, password).unwrap_or(false) {
+        record_login_failure(app_state, &user.email, ip).await?;
+        return Err(MyError::LoginError("Invalid password".to_string()));
+    }
+
+    clear_login_failures(&mut redis_conn, &user.email).await
+}
+
+// A successful login starts the account's count over; the IP's keeps running
+pub async fn clear_login_failures(redis: &mut ConnectionManager, email: &str) -> Result<(), MyError> {
+    let _: () = redis
+        .del(failures_key("account", &account_key(email)))
+        .await
+        .map_err(|_| MyError::Internal)?;
+
+    Ok(())
+}
+
+// Lifts an account lockout early and forgets its failures
+pub async fn unlock_account(redis: &mut ConnectionManager, email: &str) -> Result<(), MyError> {
+    let account = account_key(email);
+
+    let _: () = redis
+        .del(&[lock_key("account", &account), failures_key("account", &account)])
+        .await
+        .map_err(|_| MyError::Internal)?;
+
+    Ok(())
+}
+
+// Hook run when an account gets locked: logs it, audits it and tells the owner, who
+// can reset their password if the attempts were not theirs. The lockout stays in Redis
+// and leaves the account status alone: locking the account would end its sessions, and
+// anyone who knows the address could sign its owner out.
+async fn on_account_locked(
+    app_state: &AppState,
+    email: &str,
+    failures: u64,
+    ip: Option<&str>,
+) -> Result<(), MyError> {
+    tracing::warn!("Account {} locked out after {} failed logins", email, failures);
+
+    let Some(user) = get_user_by_email(&app_state.pool, email.to_string()).await? else {
+        return Ok(());
+    };
+
+    record_security_event(
+        &app_state.pool,
+        SecurityEvent::new(
+            Some(user.id),
+            SecurityEventType::AccountLocked,
+            serde_json::json!({
+                "failures": failures,
+                "ip_address": ip,
+                "duration": lockout_policy().duration,
+            }),
+        ),
+    )
+    .await?;
+
+    let email = Email {
+        to: user.email,
+        subject: "Your account was locked".to_string(),
+        body: format!(
+            "After {} failed sign-in attempts your account is locked for {} minutes.\n\nIf this was not you, someone may be guessing your password; consider resetting it.",
+            failures,
+            lockout_policy().duration / 60
+        ),
+    };
+
+    // Failing to notify must not turn the failed login into a server error
+    if let Err(err) = app_state.mailer.send(email).await {
+        tracing::warn!("Could not send the lockout notice to user {}: {}", user.id, err);
+    }
+
+    Ok(())
+}
@@ -1 +1,8 @@
+pub mod account_status;
 pub mod auth;
+pub mod epoch;
+pub mod grants;
+pub mod keys;
+pub mod lockout;
+pub mod oidc;
+pub mod revocation;
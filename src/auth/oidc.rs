@@ -0,0 +1,120 @@
+use std::sync::LazyLock;
+
+use crate::{
+    auth::{
+        auth::{ACCESS_TOKEN_TTL, sign_claims_with},
+        keys::{KeyRing, SigningKey, algorithm_name},
+    },
+    errors::my_error::MyError,
+    models::{
+        oauth_client::SUPPORTED_GRANT_TYPES,
+        oidc::{EMAIL_SCOPE, IdTokenClaims, OIDC_SCOPES, OpenIdConfiguration, PROFILE_SCOPE, has_scope},
+        scope::API_SCOPES,
+        user::User,
+    },
+};
+
+static ISSUER: LazyLock<String> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+    let issuer = std::env::var("OIDC_ISSUER").unwrap_or_else(|_| "http://localhost:4000".to_string());
+
+    issuer.trim_end_matches('/').to_string()
+});
+
+// This resource server's own identifier, the default `aud` of issued tokens
+static AUDIENCE: LazyLock<String> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+    std::env::var("JWT_AUDIENCE").unwrap_or_else(|_| issuer().to_string())
+});
+
+// Other resource servers that trust tokens from this issuer and may be requested as audience
+static EXTERNAL_AUDIENCES: LazyLock<Vec<String>> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+    std::env::var("JWT_EXTERNAL_AUDIENCES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|audience| audience.trim().to_string())
+        .filter(|audience| !audience.is_empty())
+        .collect()
+});
+
+// Public base URL of the service, the `iss` of every token
+pub fn issuer() -> &'static str {
+    &ISSUER
+}
+
+pub fn resource_audience() -> &'static str {
+    &AUDIENCE
+}
+
+pub fn is_known_audience(audience: &str) -> bool {
+    audience == resource_audience() || EXTERNAL_AUDIENCES.iter().any(|known| known == audience)
+}
+
+// Context of the authentication an ID token asserts
+pub struct IdTokenContext<'a> {
+    pub client_id: &'a str,
+    pub scope: &'a str,
+    pub auth_time: usize,
+    pub nonce: Option<&'a str>,
+    pub session_id: Option<uuid::Uuid>,
+}
+
+// Relying parties verify ID tokens against the JWKS and never hold the server's secret,
+// so OpenID Connect is only offered while the active key is an asymmetric one
+pub fn supports_oidc(key: &SigningKey) -> bool {
+    key.public_jwk().is_some()
+}
+
+pub fn issue_id_token(key: &SigningKey, user: &User, context: &IdTokenContext) -> Result<String, MyError> {
+    if !supports_oidc(key) {
+        return Err(MyError::Key("ID tokens need an asymmetric signing key".to_string()));
+    }
+
+    let now = chrono::Utc::now().timestamp() as usize;
+
+    let claims = IdTokenClaims {
+        iss: issuer().to_string(),
+        sub: user.id,
+        aud: context.client_id.to_string(),
+        exp: now + ACCESS_TOKEN_TTL as usize,
+        iat: now,
+        auth_time: context.auth_time,
+        nonce: context.nonce.map(str::to_string),
+        sid: context.session_id,
+        name: has_scope(context.scope, PROFILE_SCOPE).then(|| user.name.clone()),
+        email: has_scope(context.scope, EMAIL_SCOPE).then(|| user.email.clone()),
+    };
+
+    sign_claims_with(key, &claims)
+}
+
+// None while OpenID Connect is off, see supports_oidc
+pub fn openid_configuration(key_ring: &KeyRing) -> Option<OpenIdConfiguration> {
+    let key = key_ring.active();
+
+    if !supports_oidc(key) {
+        return None;
+    }
+
+    let issuer = issuer();
+    let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+
+    Some(OpenIdConfiguration {
+        issuer: issuer.to_string(),
+        authorization_endpoint: format!("{}/api/oauth/authorize", issuer),
+        token_endpoint: format!("{}/api/oauth/token", issuer),
+        userinfo_endpoint: format!("{}/api/userinfo", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        revocation_endpoint: format!("{}/api/oauth/revoke", issuer),
+        introspection_endpoint: format!("{}/api/oauth/introspect", issuer),
+        response_types_supported: strings(&["code"]),
+        grant_types_supported: strings(&SUPPORTED_GRANT_TYPES),
+        subject_types_supported: strings(&["public"]),
+        id_token_signing_alg_values_supported: vec![algorithm_name(key.algorithm).to_string()],
+        scopes_supported: strings(&[OIDC_SCOPES.as_slice(), API_SCOPES.as_slice()].concat()),
+        claims_supported: strings(&["sub", "iss", "aud", "exp", "iat", "auth_time", "nonce", "sid", "name", "email"]),
+        token_endpoint_auth_methods_supported: strings(&["none", "client_secret_basic", "client_secret_post"]),
+        code_challenge_methods_supported: strings(&["S256"]),
+    })
+}
@@ -0,0 +1,95 @@
+use redis::{AsyncCommands, aio::ConnectionManager};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::{auth::ACCESS_TOKEN_TTL, epoch::EPOCH_CACHE_TTL},
+    db::auth::get_session,
+    errors::my_error::MyError,
+    models::auth::Claims,
+};
+
+fn revocation_key(jti: &str) -> String {
+    format!("jti_revoked:{}", jti)
+}
+
+fn session_key(session_id: uuid::Uuid) -> String {
+    format!("session_revoked:{}", session_id)
+}
+
+// Seconds until the token expires on its own, none when it already has. A revocation
+// entry must live exactly this long: shorter would bring the token back to life,
+// longer only wastes memory since expiry rejects it from then on.
+pub fn revocation_ttl(exp: usize, now: i64) -> Option<u64> {
+    let ttl = exp as i64 - now;
+
+    (ttl > 0).then_some(ttl as u64)
+}
+
+// Denylists the token's jti until the token expires
+pub async fn revoke_token(redis: &mut ConnectionManager, claims: &Claims) -> Result<(), MyError> {
+    let Some(ttl) = revocation_ttl(claims.exp, chrono::Utc::now().timestamp()) else {
+        return Ok(());
+    };
+
+    let _: () = redis
+        .set_ex(revocation_key(&claims.jti), true, ttl)
+        .await
+        .map_err(|_| MyError::Internal)?;
+
+    Ok(())
+}
+
+pub async fn is_token_revoked(redis: &mut ConnectionManager, jti: &str) -> Result<bool, MyError> {
+    let revoked: bool = redis
+        .exists(revocation_key(jti))
+        .await
+        .map_err(|_| MyError::Internal)?;
+
+    Ok(revoked)
+}
+
+// Whether the session was revoked or no longer exists. Checked on every request made
+// with a session's token, so cached in Redis like the token epoch.
+pub async fn is_session_revoked(
+    pool: &Pool<Postgres>,
+    redis: &mut ConnectionManager,
+    session_id: uuid::Uuid,
+) -> Result<bool, MyError> {
+    let key = session_key(session_id);
+
+    let cached: Option<bool> = redis.get(&key).await.map_err(|_| MyError::Internal)?;
+
+    if let Some(revoked) = cached {
+        return Ok(revoked);
+    }
+
+    let revoked = get_session(pool, session_id)
+        .await?
+        .is_none_or(|session| session.revoked_at.is_some());
+
+    // A revoked session stays revoked: remember it until its last access token expires
+    let ttl = if revoked { ACCESS_TOKEN_TTL as u64 } else { EPOCH_CACHE_TTL };
+
+    let _: () = redis
+        .set_ex(&key, revoked, ttl)
+        .await
+        .map_err(|_| MyError::Internal)?;
+
+    Ok(revoked)
+}
+
+// Ends the access tokens of sessions just revoked in the database right away, rather
+// than once their cached state expires
+pub async fn revoke_session_tokens(
+    redis: &mut ConnectionManager,
+    session_ids: &[uuid::Uuid],
+) -> Result<(), MyError> {
+    for session_id in session_ids {
+        let _: () = redis
+            .set_ex(session_key(*session_id), true, ACCESS_TOKEN_TTL as u64)
+            .await
+            .map_err(|_| MyError::Internal)?;
+    }
+
+    Ok(())
+}
@@ -1,21 +1,42 @@
 use rust_auth_service::db::role::{get_role_by_name, set_user_role};
 use sqlx::PgPool;
-use rust_auth_service::db::user::{create_user, get_user_by_email};
+use rust_auth_service::db::user::{create_user, get_user_by_email, mark_email_verified};
+use rust_auth_service::errors::my_error::MyError;
 use rust_auth_service::models::user::UserRegister;
+use rust_auth_service::services::password_policy::password_policy;
+
+const ADMIN_NAME: &str = "Admin";
+const ADMIN_EMAIL: &str = "admin@example.com";
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenvy::dotenv().ok();
+
+    // No built-in password: the admin is held to the same policy as every other account
+    let password = std::env::var("SEED_ADMIN_PASSWORD")
+        .map_err(|_| "Set SEED_ADMIN_PASSWORD to the password of the seeded admin")?;
+
+    if let Err(MyError::PasswordPolicy(violations)) =
+        password_policy().check(&password, &[ADMIN_NAME, ADMIN_EMAIL])
+    {
+        return Err(format!(
+            "SEED_ADMIN_PASSWORD breaks the password policy: {}",
+            serde_json::to_string(&violations)?
+        )
+        .into());
+    }
+
     let pool = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
     
     println!("Connected to database successfully");
     
     let admin_user = UserRegister {
-        name: Some("Admin".to_string()),
-        email: Some("admin@example.com".to_string()),
-        password: Some("admin123".to_string()),
+        name: Some(ADMIN_NAME.to_string()),
+        email: Some(ADMIN_EMAIL.to_string()),
+        password: Some(password),
     };
     
-    let exist_admin_user = get_user_by_email(&pool, "admin@example.com".to_string()).await?;
+    let exist_admin_user = get_user_by_email(&pool, ADMIN_EMAIL.to_string()).await?;
 
     if exist_admin_user.is_some() {
         println!("Admin user already exists");
@@ -25,6 +46,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let admin_user = create_user(&pool, admin_user).await?;
     println!("Admin user created: {}", admin_user.name);
 
+    // Nobody can receive mail for the seeded address
+    mark_email_verified(&pool, admin_user.id).await?;
+
     let admin_role = get_role_by_name(&pool, "Admin".to_string()).await?;
     println!("Admin role: {}", admin_role.name);
 
@@ -32,7 +56,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("User role assigned successfully");
 
     println!("Seed data created successfully!");
-    println!("Admin user: admin@example.com / admin123");
+    println!("Admin user: {} (password from SEED_ADMIN_PASSWORD)", ADMIN_EMAIL);
     
     Ok(())
 }
@@ -1,38 +1,215 @@
 use sqlx::{Pool, Postgres};
 
-use crate::{errors::my_error::MyError, models::auth::RefreshToken};
+use crate::{
+    errors::my_error::MyError,
+    models::{auth::RefreshToken, session::Session},
+    services::token_hash::hash_token,
+};
 
-pub async fn upsert_refresh_token(
+pub async fn create_refresh_token(
     pool: &Pool<Postgres>,
     user_id: uuid::Uuid,
-    refresh_token: &String,
+    family_id: uuid::Uuid,
+    refresh_token: &str,
 ) -> Result<(), MyError> {
-    let refresh_token = RefreshToken::new(refresh_token.to_string(), user_id);
+    let refresh_token = RefreshToken::new(refresh_token, user_id, family_id);
 
     let result = sqlx::query(
         r#"
-        INSERT INTO refresh_tokens (id, token, user_id, expires_at)
-        VALUES ($1, $2, $3, $4)
-        ON CONFLICT (user_id)
-        DO UPDATE SET
-            token = EXCLUDED.token,
-            expires_at = EXCLUDED.expires_at
+        INSERT INTO refresh_tokens (id, token_hash, user_id, family_id, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
     "#,
     )
     .bind(refresh_token.id)
-    .bind(refresh_token.token)
+    .bind(refresh_token.token_hash)
     .bind(refresh_token.user_id)
+    .bind(refresh_token.family_id)
     .bind(refresh_token.expires_at)
     .execute(pool)
     .await;
 
-    if result.is_err() {
-        return Err(MyError::DatabaseError(result.unwrap_err()));
+    if let Err(err) = result {
+        return Err(MyError::DatabaseError(err));
     }
 
     Ok(())
 }
 
+pub async fn get_refresh_token(
+    pool: &Pool<Postgres>,
+    refresh_token: &str,
+) -> Result<Option<RefreshToken>, MyError> {
+    let token = sqlx::query_as::<_, RefreshToken>(
+        r#"
+        SELECT id, token_hash, user_id, family_id, expires_at, consumed_at, revoked_at
+        FROM refresh_tokens
+        WHERE token_hash = $1
+        "#,
+    )
+    .bind(hash_token(refresh_token))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(token)
+}
+
+// Returns false when the token was already consumed or revoked, including by a concurrent request
+pub async fn consume_refresh_token(pool: &Pool<Postgres>, id: uuid::Uuid) -> Result<bool, MyError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE refresh_tokens SET consumed_at = NOW()
+        WHERE id = $1 AND consumed_at IS NULL AND revoked_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// Ends the session the family belongs to along with all of its refresh tokens
+pub async fn revoke_token_family(
+    pool: &Pool<Postgres>,
+    family_id: uuid::Uuid,
+) -> Result<(), MyError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL")
+        .bind(family_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(family_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn create_session(pool: &Pool<Postgres>, session: &Session) -> Result<(), MyError> {
+    sqlx::query(
+        r#"
+        INSERT INTO sessions (id, user_id, device_name, user_agent, ip_address, client_id, scope, audience, created_at, last_used_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+    )
+    .bind(session.id)
+    .bind(session.user_id)
+    .bind(&session.device_name)
+    .bind(&session.user_agent)
+    .bind(&session.ip_address)
+    .bind(&session.client_id)
+    .bind(&session.scope)
+    .bind(&session.audience)
+    .bind(session.created_at)
+    .bind(session.last_used_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_user_sessions(
+    pool: &Pool<Postgres>,
+    user_id: uuid::Uuid,
+) -> Result<Vec<Session>, MyError> {
+    let sessions = sqlx::query_as::<_, Session>(
+        r#"
+        SELECT id, user_id, device_name, user_agent, ip_address, client_id, scope, audience, created_at, last_used_at, revoked_at
+        FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL
+        ORDER BY last_used_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(sessions)
+}
+
+pub async fn get_session(
+    pool: &Pool<Postgres>,
+    session_id: uuid::Uuid,
+) -> Result<Option<Session>, MyError> {
+    let session = sqlx::query_as::<_, Session>(
+        r#"
+        SELECT id, user_id, device_name, user_agent, ip_address, client_id, scope, audience, created_at, last_used_at, revoked_at
+        FROM sessions
+        WHERE id = $1
+        "#,
+    )
+    .bind(session_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(session)
+}
+
+pub async fn touch_session(pool: &Pool<Postgres>, session_id: uuid::Uuid) -> Result<(), MyError> {
+    sqlx::query("UPDATE sessions SET last_used_at = NOW() WHERE id = $1")
+        .bind(session_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Returns false when the session does not exist, belongs to someone else or is already revoked
+pub async fn revoke_session(
+    pool: &Pool<Postgres>,
+    user_id: uuid::Uuid,
+    session_id: uuid::Uuid,
+) -> Result<bool, MyError> {
+    let revoked = revoke_user_sessions(pool, user_id, Some(session_id), None).await?;
+
+    Ok(revoked.len() == 1)
+}
+
+// Revokes the user's sessions (only `only` when given), sparing `except`, with their refresh
+// tokens. Returns the ids of the sessions revoked.
+pub async fn revoke_user_sessions(
+    pool: &Pool<Postgres>,
+    user_id: uuid::Uuid,
+    only: Option<uuid::Uuid>,
+    except: Option<uuid::Uuid>,
+) -> Result<Vec<uuid::Uuid>, MyError> {
+    let mut tx = pool.begin().await?;
+
+    let revoked: Vec<uuid::Uuid> = sqlx::query_scalar(
+        r#"
+        UPDATE sessions SET revoked_at = NOW()
+        WHERE user_id = $1
+            AND revoked_at IS NULL
+            AND ($2::uuid IS NULL OR id = $2)
+            AND ($3::uuid IS NULL OR id <> $3)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(only)
+    .bind(except)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = ANY($1) AND revoked_at IS NULL",
+    )
+    .bind(&revoked)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(revoked)
+}
+
 pub async fn revoke_refresh_token(
     pool: &Pool<Postgres>,
     user_id: uuid::Uuid,
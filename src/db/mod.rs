@@ -1,3 +1,7 @@
 pub mod auth;
 pub mod user;
 pub mod role;
+pub mod oauth;
+pub mod one_time_token;
+pub mod security_event;
+pub mod signing_key;
@@ -0,0 +1,149 @@
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    errors::my_error::MyError,
+    models::oauth_client::{AuthorizationCode, OAuthClient},
+    services::token_hash::hash_token,
+};
+
+pub async fn create_oauth_client(pool: &Pool<Postgres>, client: &OAuthClient) -> Result<(), MyError> {
+    sqlx::query(
+        r#"
+        INSERT INTO oauth_clients
+            (client_id, name, redirect_uris, grant_types, scopes, audiences, client_secret_hash, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(&client.client_id)
+    .bind(&client.name)
+    .bind(&client.redirect_uris)
+    .bind(&client.grant_types)
+    .bind(&client.scopes)
+    .bind(&client.audiences)
+    .bind(&client.client_secret_hash)
+    .bind(client.created_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_oauth_client(
+    pool: &Pool<Postgres>,
+    client_id: &str,
+) -> Result<Option<OAuthClient>, MyError> {
+    let client = sqlx::query_as::<_, OAuthClient>(
+        r#"
+        SELECT client_id, name, redirect_uris, grant_types, scopes, audiences, client_secret_hash, created_at
+        FROM oauth_clients
+        WHERE client_id = $1
+        "#,
+    )
+    .bind(client_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(client)
+}
+
+pub async fn list_oauth_clients(pool: &Pool<Postgres>) -> Result<Vec<OAuthClient>, MyError> {
+    let clients = sqlx::query_as::<_, OAuthClient>(
+        r#"
+        SELECT client_id, name, redirect_uris, grant_types, scopes, audiences, client_secret_hash, created_at
+        FROM oauth_clients
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(clients)
+}
+
+pub async fn delete_oauth_client(pool: &Pool<Postgres>, client_id: &str) -> Result<bool, MyError> {
+    let result = sqlx::query("DELETE FROM oauth_clients WHERE client_id = $1")
+        .bind(client_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn create_authorization_code(
+    pool: &Pool<Postgres>,
+    code: &AuthorizationCode,
+) -> Result<(), MyError> {
+    sqlx::query(
+        r#"
+        INSERT INTO oauth_authorization_codes
+            (code_hash, client_id, user_id, redirect_uri, code_challenge, scope, nonce, audience,
+            expires_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+    )
+    .bind(&code.code_hash)
+    .bind(&code.client_id)
+    .bind(code.user_id)
+    .bind(&code.redirect_uri)
+    .bind(&code.code_challenge)
+    .bind(&code.scope)
+    .bind(&code.nonce)
+    .bind(&code.audience)
+    .bind(code.expires_at)
+    .bind(code.created_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_authorization_code(
+    pool: &Pool<Postgres>,
+    code: &str,
+) -> Result<Option<AuthorizationCode>, MyError> {
+    let code = sqlx::query_as::<_, AuthorizationCode>(
+        r#"
+        SELECT code_hash, client_id, user_id, redirect_uri, code_challenge, scope, nonce, audience,
+            expires_at, consumed_at, session_id, created_at
+        FROM oauth_authorization_codes
+        WHERE code_hash = $1
+        "#,
+    )
+    .bind(hash_token(code))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(code)
+}
+
+// Returns false when the code was already exchanged, including by a concurrent request
+pub async fn consume_authorization_code(
+    pool: &Pool<Postgres>,
+    code_hash: &str,
+) -> Result<bool, MyError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE oauth_authorization_codes SET consumed_at = NOW()
+        WHERE code_hash = $1 AND consumed_at IS NULL
+        "#,
+    )
+    .bind(code_hash)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn set_authorization_code_session(
+    pool: &Pool<Postgres>,
+    code_hash: &str,
+    session_id: uuid::Uuid,
+) -> Result<(), MyError> {
+    sqlx::query("UPDATE oauth_authorization_codes SET session_id = $2 WHERE code_hash = $1")
+        .bind(code_hash)
+        .bind(session_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    errors::my_error::MyError,
+    models::one_time_token::{OneTimeToken, TokenPurpose},
+    services::token_hash::hash_token,
+};
+
+pub async fn create_one_time_token(
+    pool: &Pool<Postgres>,
+    token: &OneTimeToken,
+) -> Result<(), MyError> {
+    sqlx::query(
+        r#"
+        INSERT INTO one_time_tokens (token_hash, user_id, purpose, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(&token.token_hash)
+    .bind(token.user_id)
+    .bind(&token.purpose)
+    .bind(token.expires_at)
+    .bind(token.created_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// The user an unused, unexpired token belongs to, without redeeming it
+pub async fn find_one_time_token_user(
+    pool: &Pool<Postgres>,
+    token: &str,
+    purpose: TokenPurpose,
+) -> Result<Option<uuid::Uuid>, MyError> {
+    let user_id = sqlx::query_scalar::<_, uuid::Uuid>(
+        r#"
+        SELECT user_id FROM one_time_tokens
+        WHERE token_hash = $1
+            AND purpose = $2
+            AND consumed_at IS NULL
+            AND expires_at > NOW()
+        "#,
+    )
+    .bind(hash_token(token))
+    .bind(purpose.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user_id)
+}
+
+// Redeems the token and returns its user, none when it is unknown, meant for something
+// else, expired or already used, including by a concurrent request
+pub async fn consume_one_time_token(
+    pool: &Pool<Postgres>,
+    token: &str,
+    purpose: TokenPurpose,
+) -> Result<Option<uuid::Uuid>, MyError> {
+    let user_id = sqlx::query_scalar::<_, uuid::Uuid>(
+        r#"
+        UPDATE one_time_tokens SET consumed_at = NOW()
+        WHERE token_hash = $1
+            AND purpose = $2
+            AND consumed_at IS NULL
+            AND expires_at > NOW()
+        RETURNING user_id
+        "#,
+    )
+    .bind(hash_token(token))
+    .bind(purpose.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user_id)
+}
+
+// Voids the user's outstanding tokens for `purpose`, so only the latest one mailed works
+pub async fn revoke_one_time_tokens(
+    pool: &Pool<Postgres>,
+    user_id: uuid::Uuid,
+    purpose: TokenPurpose,
+) -> Result<(), MyError> {
+    sqlx::query(
+        r#"
+        UPDATE one_time_tokens SET consumed_at = NOW()
+        WHERE user_id = $1 AND purpose = $2 AND consumed_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(purpose.as_str())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
-use crate::{errors::my_error::MyError, models::role::Role};
+use crate::{db::user::increment_token_epoch, errors::my_error::MyError, models::role::Role};
 
 pub async fn get_user_roles(pool: &Pool<Postgres>, user_id: Uuid) -> Result<Vec<String>, MyError> {
 
@@ -33,6 +33,10 @@ pub async fn set_user_role(
         .await
         .map_err(|err| MyError::DatabaseError(err))?;
 
+        // Tokens carry the roles they were issued with, so outstanding ones must go.
+        // Servers holding a cached epoch notice within EPOCH_CACHE_TTL (see auth::epoch).
+        increment_token_epoch(pool, user_id).await?;
+
         Ok(())
     }
 
@@ -0,0 +1,24 @@
+use sqlx::{Pool, Postgres};
+
+use crate::{errors::my_error::MyError, models::security_event::SecurityEvent};
+
+pub async fn record_security_event(
+    pool: &Pool<Postgres>,
+    event: SecurityEvent,
+) -> Result<(), MyError> {
+    sqlx::query(
+        r#"
+        INSERT INTO security_events (id, user_id, event_type, details, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(event.id)
+    .bind(event.user_id)
+    .bind(event.event_type)
+    .bind(event.details)
+    .bind(event.created_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    errors::my_error::MyError,
+    models::signing_key::{SigningKeyOutput, SigningKeyRecord},
+};
+
+pub async fn get_trusted_signing_keys(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<SigningKeyRecord>, MyError> {
+    let keys = sqlx::query_as::<_, SigningKeyRecord>(
+        r#"
+        SELECT kid, algorithm, key_material, state, created_at, rotated_at, retired_at
+        FROM signing_keys
+        WHERE state <> 'retired'
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(keys)
+}
+
+pub async fn list_signing_keys(pool: &Pool<Postgres>) -> Result<Vec<SigningKeyOutput>, MyError> {
+    let keys = sqlx::query_as::<_, SigningKeyOutput>(
+        r#"
+        SELECT kid, algorithm, state, created_at, rotated_at, retired_at
+        FROM signing_keys
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(keys)
+}
+
+// Inserts the key only if no key is active yet, so concurrent instances bootstrap a single key
+pub async fn insert_initial_signing_key(
+    pool: &Pool<Postgres>,
+    key: &SigningKeyRecord,
+) -> Result<(), MyError> {
+    sqlx::query(
+        r#"
+        INSERT INTO signing_keys (kid, algorithm, key_material, state, created_at)
+        SELECT $1, $2, $3, 'active', $4
+        WHERE NOT EXISTS (SELECT 1 FROM signing_keys WHERE state = 'active')
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(&key.kid)
+    .bind(&key.algorithm)
+    .bind(&key.key_material)
+    .bind(key.created_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Demotes the active key to verify-only and activates the new one atomically
+pub async fn rotate_signing_key(
+    pool: &Pool<Postgres>,
+    key: &SigningKeyRecord,
+) -> Result<(), MyError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "UPDATE signing_keys SET state = 'verify_only', rotated_at = $1 WHERE state = 'active'",
+    )
+    .bind(key.created_at)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO signing_keys (kid, algorithm, key_material, state, created_at)
+        VALUES ($1, $2, $3, 'active', $4)
+        "#,
+    )
+    .bind(&key.kid)
+    .bind(&key.algorithm)
+    .bind(&key.key_material)
+    .bind(key.created_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn update_signing_key_material(
+    pool: &Pool<Postgres>,
+    kid: &str,
+    key_material: &str,
+) -> Result<(), MyError> {
+    sqlx::query("UPDATE signing_keys SET key_material = $1 WHERE kid = $2")
+        .bind(key_material)
+        .bind(kid)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Verify-only keys rotated out before `cutoff` can no longer have live tokens
+pub async fn retire_signing_keys(
+    pool: &Pool<Postgres>,
+    cutoff: DateTime<Utc>,
+) -> Result<u64, MyError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE signing_keys SET state = 'retired', retired_at = NOW()
+        WHERE state = 'verify_only' AND rotated_at < $1
+        "#,
+    )
+    .bind(cutoff)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -1,5 +1,5 @@
 use crate::{
-    db::role::{get_default_role, set_user_role}, errors::my_error::MyError, models::user::{User, UserOutput, UserRegister, UserWithRoles}
+    db::role::{get_default_role, set_user_role}, errors::my_error::MyError, models::user::{User, UserFilter, UserOutput, UserRegister, UserSort, UserStatus, UserWithRoles}
 };
 use chrono::Utc;
 use sqlx::{Pool, Postgres};
@@ -42,13 +42,14 @@ pub async fn get_user_by_id(pool: &Pool<Postgres>, id: uuid::Uuid) -> Result<Use
                 u.id,
                 u.name,
                 u.email,
+                u.status AS "status: UserStatus",
                 u.created_at,
                 u.updated_at,
                 COALESCE(ARRAY_AGG(r.name) FILTER (WHERE r.name IS NOT NULL), '{}') AS "roles!"
             FROM users u
             LEFT JOIN user_roles ur ON ur.user_id = u.id
             LEFT JOIN roles r ON r.id = ur.role_id
-            WHERE u.id = $1
+            WHERE u.id = $1 AND u.status <> 'deleted'
             GROUP BY u.id
             "#,
             id
@@ -61,14 +62,94 @@ pub async fn get_user_by_id(pool: &Pool<Postgres>, id: uuid::Uuid) -> Result<Use
 }
 
 pub async fn get_users(pool: &Pool<Postgres>) -> Result<Vec<UserOutput>, MyError> {
-    let users =
-        sqlx::query_as::<_, UserOutput>("SELECT id, name, email, created_at, updated_at FROM users")
-            .fetch_all(pool)
-            .await?;
+    let users = sqlx::query_as::<_, UserOutput>(
+        "SELECT id, name, email, created_at, updated_at FROM users WHERE status <> 'deleted'",
+    )
+    .fetch_all(pool)
+    .await?;
 
     Ok(users)
 }
 
+// Shared by the page and count queries of list_users, $1 to $5 are the filters. Deleted
+// accounts only show up when asked for by status.
+const USER_FILTERS: &str = r#"
+    ($1::text IS NULL OR u.email ILIKE '%' || $1 || '%')
+    AND ($2::text IS NULL OR EXISTS (
+        SELECT 1 FROM user_roles fur
+        INNER JOIN roles fr ON fr.id = fur.role_id
+        WHERE fur.user_id = u.id AND fr.name = $2
+    ))
+    AND ($3::timestamptz IS NULL OR u.created_at >= $3)
+    AND ($4::timestamptz IS NULL OR u.created_at <= $4)
+    AND (($5::user_status IS NULL AND u.status <> 'deleted') OR u.status = $5)
+"#;
+
+// Escapes LIKE wildcards so an email filter only ever matches a literal substring
+fn like_literal(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// One page of users matching the filter, with the number of matches across all pages
+pub async fn list_users(
+    pool: &Pool<Postgres>,
+    filter: &UserFilter,
+) -> Result<(Vec<UserWithRoles>, i64), MyError> {
+    let email = filter.email.as_deref().map(like_literal);
+
+    // Only whitelisted columns and directions ever reach the SQL text
+    let column = match filter.sort {
+        UserSort::CreatedAt => "u.created_at",
+        UserSort::Email => "u.email",
+        UserSort::Name => "u.name",
+    };
+    let direction = if filter.descending { "DESC" } else { "ASC" };
+
+    let users = sqlx::query_as::<_, UserWithRoles>(&format!(
+        r#"
+        SELECT
+            u.id,
+            u.name,
+            u.email,
+            u.status,
+            u.created_at,
+            u.updated_at,
+            COALESCE(ARRAY_AGG(r.name) FILTER (WHERE r.name IS NOT NULL), '{{}}') AS roles
+        FROM users u
+        LEFT JOIN user_roles ur ON ur.user_id = u.id
+        LEFT JOIN roles r ON r.id = ur.role_id
+        WHERE {USER_FILTERS}
+        GROUP BY u.id
+        ORDER BY {column} {direction}, u.id {direction}
+        LIMIT $6 OFFSET $7
+        "#
+    ))
+    .bind(&email)
+    .bind(&filter.role)
+    .bind(filter.created_from)
+    .bind(filter.created_to)
+    .bind(filter.status)
+    .bind(filter.per_page)
+    .bind(filter.offset())
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar::<_, i64>(&format!(
+        "SELECT COUNT(*) FROM users u WHERE {USER_FILTERS}"
+    ))
+    .bind(&email)
+    .bind(&filter.role)
+    .bind(filter.created_from)
+    .bind(filter.created_to)
+    .bind(filter.status)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((users, total))
+}
+
+// A new email address has not been verified, so changing it clears email_verified_at.
+// Deleted accounts are not found.
 pub async fn update_user(
     pool: &Pool<Postgres>,
     id: uuid::Uuid,
@@ -78,9 +159,11 @@ pub async fn update_user(
         r#"
             UPDATE users SET name = COALESCE($1, name),
             email = COALESCE($2, email),
+            email_verified_at = CASE WHEN $2 <> email THEN NULL ELSE email_verified_at END,
             password = COALESCE($3, password),
             updated_at = COALESCE($5, updated_at)
-            WHERE id = $4 RETURNING id, name, email, created_at, updated_at
+            WHERE id = $4 AND status <> 'deleted'
+            RETURNING id, name, email, created_at, updated_at
         "#,
     )
     .bind(user.name)
@@ -94,6 +177,9 @@ pub async fn update_user(
     let user = match user {
         Ok(u) => u,
         Err(sqlx::Error::RowNotFound) => return Err(MyError::NotFound),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            return Err(MyError::Conflict("Email address already in use".to_string()));
+        }
         Err(e) => return Err(MyError::DatabaseError(e)),
     };
 
@@ -109,6 +195,7 @@ pub async fn delete_user(pool: &Pool<Postgres>, id: uuid::Uuid) -> Result<(), My
     Ok(())
 }
 
+// Deleted accounts have released their address, which a new account may hold by now
 pub async fn get_user_by_email(
     pool: &Pool<Postgres>,
     email: String,
@@ -116,7 +203,7 @@ pub async fn get_user_by_email(
     let email = email.trim().to_lowercase();
 
     let user = sqlx::query_as::<_, User>(
-        "SELECT id, name, email, password, created_at, updated_at FROM users WHERE email = $1",
+        "SELECT id, name, email, password, email_verified_at, status, created_at, updated_at FROM users WHERE email = $1 AND status <> 'deleted'",
     )
     .bind(email)
     .fetch_optional(pool)
@@ -124,3 +211,115 @@ pub async fn get_user_by_email(
 
     Ok(user)
 }
+
+// Full user row, for flows that issue tokens to a user identified by id
+pub async fn get_user_account_by_id(
+    pool: &Pool<Postgres>,
+    id: uuid::Uuid,
+) -> Result<Option<User>, MyError> {
+    let user = sqlx::query_as::<_, User>(
+        "SELECT id, name, email, password, email_verified_at, status, created_at, updated_at FROM users WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user)
+}
+
+// None when the user no longer exists
+pub async fn get_token_epoch(
+    pool: &Pool<Postgres>,
+    id: uuid::Uuid,
+) -> Result<Option<i64>, MyError> {
+    let epoch = sqlx::query_scalar::<_, i64>("SELECT token_epoch FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(epoch)
+}
+
+pub async fn increment_token_epoch(pool: &Pool<Postgres>, id: uuid::Uuid) -> Result<i64, MyError> {
+    let epoch = sqlx::query_scalar::<_, i64>(
+        "UPDATE users SET token_epoch = token_epoch + 1 WHERE id = $1 RETURNING token_epoch",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(MyError::NotFound)?;
+
+    Ok(epoch)
+}
+
+// Activates a pending account. Returns false when the user does not exist
+pub async fn mark_email_verified(pool: &Pool<Postgres>, id: uuid::Uuid) -> Result<bool, MyError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW()),
+            status = CASE WHEN status = 'pending' THEN 'active' ELSE status END,
+            status_changed_at = CASE WHEN status = 'pending' THEN NOW() ELSE status_changed_at END
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// `password_hash` must already be hashed with services::password::hash_password
+pub async fn set_password(
+    pool: &Pool<Postgres>,
+    id: uuid::Uuid,
+    password_hash: &str,
+) -> Result<bool, MyError> {
+    let result = sqlx::query("UPDATE users SET password = $1, updated_at = $2 WHERE id = $3")
+        .bind(password_hash)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// None when the user no longer exists
+pub async fn get_user_status(
+    pool: &Pool<Postgres>,
+    id: uuid::Uuid,
+) -> Result<Option<UserStatus>, MyError> {
+    let status = sqlx::query_scalar::<_, UserStatus>("SELECT status FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(status)
+}
+
+// Moves the user from `from` to `to`; false when the status was no longer `from`,
+// e.g. because a concurrent request changed it first
+pub async fn set_user_status(
+    pool: &Pool<Postgres>,
+    id: uuid::Uuid,
+    from: UserStatus,
+    to: UserStatus,
+    reason: Option<&str>,
+) -> Result<bool, MyError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET status = $1, status_reason = $2, status_changed_at = $3, updated_at = $3
+        WHERE id = $4 AND status = $5
+        "#,
+    )
+    .bind(to)
+    .bind(reason)
+    .bind(Utc::now())
+    .bind(id)
+    .bind(from)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
@@ -1,9 +1,22 @@
 
 use crate::models::{
-    user::{UserOutput, UserRegister, UserWithRoles},
-    auth::{Login, TokenResponse, RefreshTokenInput, Claims},
+    user::{
+        ChangePasswordInput, ForgotPasswordInput, ResendVerificationInput, ResetPasswordInput,
+        StatusChangeInput, UserOutput, UserPage, UserRegister, UserStatus, UserWithRoles,
+        VerifyEmailInput,
+    },
+    auth::{Actor, ElevatedTokenResponse, Login, ReauthenticateInput, TokenResponse, RefreshTokenInput, Claims},
+    oauth::{
+        AuthorizationParams, AuthorizeRequest, IntrospectionRequest, IntrospectionResponse,
+        OAuthTokenResponse, RevocationRequest, TokenRequest,
+    },
+    oauth_client::{CreateOAuthClientInput, CreatedOAuthClient, OAuthClient},
+    oidc::{IdTokenClaims, OpenIdConfiguration, UserInfo},
     role::Role,
+    session::SessionOutput,
+    signing_key::{KeyState, RotateKeyInput, SigningKeyOutput},
 };
+use crate::services::password_policy::PasswordViolation;
 use utoipa::OpenApi;
 
 #[derive(OpenApi)]
@@ -14,10 +27,41 @@ use utoipa::OpenApi;
         crate::handlers::user::get_user_handler,
         crate::handlers::user::update_user_handler,
         crate::handlers::user::delete_user_handler,
+        crate::handlers::verification::verify_email_handler,
+        crate::handlers::verification::resend_verification_handler,
+        crate::handlers::password::forgot_password_handler,
+        crate::handlers::password::reset_password_handler,
+        crate::handlers::password::change_password_handler,
         // Auth endpoints
         crate::handlers::auth::login_handler,
         crate::handlers::auth::logout_handler,
+        crate::handlers::auth::logout_all_handler,
         crate::handlers::auth::refresh_token_handler,
+        crate::handlers::auth::reauthenticate_handler,
+        // Session endpoints
+        crate::handlers::session::list_sessions_handler,
+        crate::handlers::session::revoke_session_handler,
+        crate::handlers::session::revoke_other_sessions_handler,
+        crate::handlers::well_known::jwks_handler,
+        crate::handlers::well_known::openid_configuration_handler,
+        // OAuth endpoints
+        crate::handlers::oauth::authorize_page_handler,
+        crate::handlers::oauth::authorize_handler,
+        crate::handlers::oauth::token_handler,
+        crate::handlers::oauth::userinfo_handler,
+        crate::handlers::oauth::introspect_handler,
+        crate::handlers::oauth::revoke_handler,
+        // Admin endpoints
+        crate::handlers::admin::list_users_handler,
+        crate::handlers::admin::suspend_user_handler,
+        crate::handlers::admin::lock_user_handler,
+        crate::handlers::admin::reactivate_user_handler,
+        crate::handlers::admin::unlock_user_handler,
+        crate::handlers::admin::list_keys_handler,
+        crate::handlers::admin::rotate_keys_handler,
+        crate::handlers::admin::list_oauth_clients_handler,
+        crate::handlers::admin::create_oauth_client_handler,
+        crate::handlers::admin::delete_oauth_client_handler,
     ),
     components(
         schemas(
@@ -25,19 +69,52 @@ use utoipa::OpenApi;
             UserRegister,
             UserOutput,
             UserWithRoles,
+            UserPage,
+            UserStatus,
+            StatusChangeInput,
+            VerifyEmailInput,
+            ResendVerificationInput,
+            ForgotPasswordInput,
+            ResetPasswordInput,
+            ChangePasswordInput,
+            PasswordViolation,
             // Auth models
             Login,
             TokenResponse,
             RefreshTokenInput,
             Claims,
+            Actor,
+            ReauthenticateInput,
+            ElevatedTokenResponse,
+            // OAuth models
+            AuthorizationParams,
+            AuthorizeRequest,
+            TokenRequest,
+            OAuthTokenResponse,
+            OAuthClient,
+            CreateOAuthClientInput,
+            CreatedOAuthClient,
+            IdTokenClaims,
+            UserInfo,
+            OpenIdConfiguration,
+            IntrospectionRequest,
+            IntrospectionResponse,
+            RevocationRequest,
             // Role models
             Role,
+            // Session models
+            SessionOutput,
+            // Signing key models
+            KeyState,
+            SigningKeyOutput,
+            RotateKeyInput,
         )
     ),
     tags(
         (name = "rust-auth-service", description = "Rust Auth Service API - Complete authentication and user management system"),
         (name = "users", description = "User management operations"),
         (name = "auth", description = "Authentication operations"),
+        (name = "oauth", description = "OAuth 2.0 endpoints"),
         (name = "admin", description = "Admin-only operations")
     ),
     info(
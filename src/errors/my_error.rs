@@ -1,13 +1,15 @@
 use argon2::password_hash::Error as Argon2Error;
 use axum::{
     Json,
-    http::StatusCode,
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde_json::{Error as SerdeJsonError, json};
 use sqlx::Error as SqlxError;
 use thiserror::Error;
 
+use crate::services::password_policy::PasswordViolation;
+
 #[derive(Debug, Error)]
 pub enum MyError {
     #[error("Database: {0}")]
@@ -31,6 +33,10 @@ pub enum MyError {
     #[error("Unauthorized")]
     Unauthorized,
 
+    // Authenticated, but not allowed to touch this resource
+    #[error("Forbidden")]
+    Forbidden,
+
     #[error("Login error: {0}")]
     LoginError(String),
 
@@ -39,6 +45,25 @@ pub enum MyError {
 
     #[error("Too many requests")]
     TooManyRequests,
+
+    #[error("Key error: {0}")]
+    Key(String),
+
+    // RFC 6749 error code, e.g. "invalid_grant"
+    #[error("{0}")]
+    OAuth(&'static str),
+
+    // RFC 9470: the user must have authenticated at most this many seconds ago
+    #[error("insufficient_user_authentication")]
+    StepUpRequired(i64),
+
+    // Every rule of services::password_policy the new password breaks
+    #[error("Password does not meet the password policy")]
+    PasswordPolicy(Vec<PasswordViolation>),
+
+    // A unique value, such as an email address, another row already holds
+    #[error("{0}")]
+    Conflict(String),
 }
 
 impl IntoResponse for MyError {
@@ -51,15 +76,42 @@ impl IntoResponse for MyError {
             MyError::Validation(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
             MyError::NotFound => (StatusCode::NOT_FOUND, self.to_string()),
             MyError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
+            MyError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
             MyError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             MyError::LoginError(message) => (StatusCode::UNAUTHORIZED, message.to_string()),
             MyError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            MyError::Key(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            MyError::OAuth("invalid_client") => (StatusCode::UNAUTHORIZED, self.to_string()),
+            MyError::OAuth(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            MyError::StepUpRequired(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
+            MyError::PasswordPolicy(_) => (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()),
+            MyError::Conflict(message) => (StatusCode::CONFLICT, message.to_string()),
         };
 
+        // Listed so frontends can point out each rule the password breaks
+        if let MyError::PasswordPolicy(violations) = &self {
+            let body = Json(json!({
+              "error": error_message,
+              "violations": violations
+            }));
+
+            return (status, body).into_response();
+        }
+
         let body = Json(json!({
           "error": error_message
         }));
 
+        // Tells the client to send the user through POST /api/reauthenticate
+        if let MyError::StepUpRequired(max_age) = self {
+            let challenge = format!(
+                "Bearer error=\"insufficient_user_authentication\", max_age={}",
+                max_age
+            );
+
+            return (status, [(header::WWW_AUTHENTICATE, challenge)], body).into_response();
+        }
+
         (status, body).into_response()
     }
 }
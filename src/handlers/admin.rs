@@ -0,0 +1,363 @@
+use axum::{
+    Extension,
+    extract::{Json, Path, Query, State},
+};
+
+use crate::{
+    auth::{
+        account_status::change_status,
+        keys::rotate_keys,
+        lockout::unlock_account,
+    },
+    db::{
+        oauth::{create_oauth_client, delete_oauth_client, list_oauth_clients},
+        security_event::record_security_event,
+        signing_key::list_signing_keys,
+        user::{get_user_account_by_id, get_user_by_id, list_users},
+    },
+    errors::my_error::MyError,
+    models::{
+        app::AppState,
+        auth::Claims,
+        oauth_client::{CreateOAuthClientInput, CreatedOAuthClient, OAuthClient},
+        security_event::{SecurityEvent, SecurityEventType},
+        signing_key::{RotateKeyInput, SigningKeyOutput},
+        user::{StatusChangeInput, UserListQuery, UserPage, UserStatus, UserWithRoles},
+    },
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/keys",
+    responses(
+        (status = 200, description = "Signing keys and their rotation state", body = Vec<SigningKeyOutput>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn list_keys_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<SigningKeyOutput>>, MyError> {
+    let keys = list_signing_keys(&app_state.pool).await?;
+
+    Ok(Json(keys))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/keys/rotate",
+    request_body = RotateKeyInput,
+    responses(
+        (status = 200, description = "New active signing key", body = SigningKeyOutput),
+        (status = 400, description = "Invalid algorithm or key material"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn rotate_keys_handler(
+    State(app_state): State<AppState>,
+    payload: Option<Json<RotateKeyInput>>,
+) -> Result<Json<SigningKeyOutput>, MyError> {
+    let input = payload.map(|Json(input)| input).unwrap_or_default();
+
+    let key = rotate_keys(&app_state.pool, input).await?;
+
+    Ok(Json(SigningKeyOutput {
+        kid: key.kid,
+        algorithm: key.algorithm,
+        state: key.state,
+        created_at: key.created_at,
+        rotated_at: key.rotated_at,
+        retired_at: key.retired_at,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/oauth/clients",
+    responses(
+        (status = 200, description = "Registered OAuth clients", body = Vec<OAuthClient>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn list_oauth_clients_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<OAuthClient>>, MyError> {
+    let clients = list_oauth_clients(&app_state.pool).await?;
+
+    Ok(Json(clients))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/oauth/clients",
+    request_body = CreateOAuthClientInput,
+    responses(
+        (status = 200, description = "OAuth client registered, with its secret if confidential", body = CreatedOAuthClient),
+        (status = 400, description = "Invalid redirect URI, grant type or scope"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn create_oauth_client_handler(
+    State(app_state): State<AppState>,
+    Json(payload): Json<CreateOAuthClientInput>,
+) -> Result<Json<CreatedOAuthClient>, MyError> {
+    let created = OAuthClient::register(payload)?;
+
+    create_oauth_client(&app_state.pool, &created.client).await?;
+
+    Ok(Json(created))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/oauth/clients/{client_id}",
+    params(
+        ("client_id" = String, Path, description = "OAuth client ID")
+    ),
+    responses(
+        (status = 200, description = "OAuth client deleted along with its sessions"),
+        (status = 404, description = "OAuth client not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn delete_oauth_client_handler(
+    State(app_state): State<AppState>,
+    Path(client_id): Path<String>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    if !delete_oauth_client(&app_state.pool, &client_id).await? {
+        return Err(MyError::NotFound);
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "OAuth client deleted successfully",
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/users",
+    params(UserListQuery),
+    responses(
+        (status = 200, description = "One page of users matching the filters", body = UserPage),
+        (status = 400, description = "Invalid page, page size, date range or sort"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn list_users_handler(
+    State(app_state): State<AppState>,
+    Query(query): Query<UserListQuery>,
+) -> Result<Json<UserPage>, MyError> {
+    let filter = query.into_filter()?;
+
+    let (items, total) = list_users(&app_state.pool, &filter).await?;
+
+    Ok(Json(UserPage {
+        items,
+        page: filter.page,
+        per_page: filter.per_page,
+        total,
+        total_pages: (total + filter.per_page - 1) / filter.per_page,
+    }))
+}
+
+// Applies an admin's status change and records who made it and why
+async fn change_user_status(
+    app_state: &AppState,
+    actor: uuid::Uuid,
+    user_id: uuid::Uuid,
+    next: UserStatus,
+    reason: &str,
+) -> Result<UserWithRoles, MyError> {
+    let reason = reason.trim();
+
+    if reason.is_empty() {
+        return Err(MyError::Validation("A reason is required".to_string()));
+    }
+
+    // An admin locking themselves out would need another admin to get back in
+    if user_id == actor {
+        return Err(MyError::Validation("You cannot change your own account status".to_string()));
+    }
+
+    let mut redis_conn = app_state.redis.clone();
+    let previous = change_status(&app_state.pool, &mut redis_conn, user_id, next, Some(reason)).await?;
+
+    tracing::info!(
+        "Admin {} changed the status of user {} from {} to {}",
+        actor,
+        user_id,
+        previous.as_str(),
+        next.as_str()
+    );
+
+    record_security_event(
+        &app_state.pool,
+        SecurityEvent::new(
+            Some(user_id),
+            SecurityEventType::StatusChange,
+            serde_json::json!({
+                "actor": actor,
+                "from": previous,
+                "to": next,
+                "reason": reason,
+            }),
+        ),
+    )
+    .await?;
+
+    get_user_by_id(&app_state.pool, user_id).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/suspend",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    request_body = StatusChangeInput,
+    responses(
+        (status = 200, description = "User suspended and signed out everywhere", body = UserWithRoles),
+        (status = 401, description = "Unauthorized, missing reason, own account or not allowed from the current status"),
+        (status = 404, description = "User not found"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn suspend_user_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+    Path(user_id): Path<uuid::Uuid>,
+    Json(payload): Json<StatusChangeInput>,
+) -> Result<Json<UserWithRoles>, MyError> {
+    let user =
+        change_user_status(&app_state, claims.sub, user_id, UserStatus::Suspended, &payload.reason)
+            .await?;
+
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/lock",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    request_body = StatusChangeInput,
+    responses(
+        (status = 200, description = "User locked for the account's protection and signed out everywhere", body = UserWithRoles),
+        (status = 401, description = "Unauthorized, missing reason, own account or not allowed from the current status"),
+        (status = 404, description = "User not found"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn lock_user_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+    Path(user_id): Path<uuid::Uuid>,
+    Json(payload): Json<StatusChangeInput>,
+) -> Result<Json<UserWithRoles>, MyError> {
+    let user =
+        change_user_status(&app_state, claims.sub, user_id, UserStatus::Locked, &payload.reason)
+            .await?;
+
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/reactivate",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    request_body = StatusChangeInput,
+    responses(
+        (status = 200, description = "Suspended or locked user active again", body = UserWithRoles),
+        (status = 401, description = "Unauthorized, missing reason, own account or not allowed from the current status"),
+        (status = 404, description = "User not found"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn reactivate_user_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+    Path(user_id): Path<uuid::Uuid>,
+    Json(payload): Json<StatusChangeInput>,
+) -> Result<Json<UserWithRoles>, MyError> {
+    let user =
+        change_user_status(&app_state, claims.sub, user_id, UserStatus::Active, &payload.reason)
+            .await?;
+
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/unlock",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Login lockout lifted, failed attempts forgotten and a locked account made active"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "User not found"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn unlock_user_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+    Path(user_id): Path<uuid::Uuid>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    let user = get_user_account_by_id(&app_state.pool, user_id)
+        .await?
+        .ok_or(MyError::NotFound)?;
+
+    let mut redis_conn = app_state.redis.clone();
+    unlock_account(&mut redis_conn, &user.email).await?;
+
+    // An account an admin locked is made active again as well
+    if user.status == UserStatus::Locked {
+        change_user_status(&app_state, claims.sub, user.id, UserStatus::Active, "Unlocked by an admin")
+            .await?;
+    }
+
+    tracing::info!("Admin {} lifted the login lockout of user {}", claims.sub, user.id);
+
+    Ok(Json(serde_json::json!({
+        "message": "Account unlocked",
+    })))
+}
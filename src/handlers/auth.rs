@@ -1,21 +1,41 @@
 use axum::{
     Extension,
     extract::{Json, State},
+    http::HeaderMap,
 };
-use chrono::Utc;
-use redis::AsyncCommands;
 
 use crate::{
-    auth::auth::{generate_tokens, validate_jwt},
+    auth::{
+        auth::{ELEVATED_TOKEN_TTL, issue_elevated_token},
+        epoch::sign_out_everywhere,
+        grants::{rotate_refresh_token, start_session},
+        lockout::{
+            check_login_allowed, clear_login_failures, record_login_failure,
+            verify_current_password,
+        },
+        oidc::{is_known_audience, resource_audience},
+        revocation::{revoke_session_tokens, revoke_token},
+    },
     db::{
-        auth::{revoke_refresh_token, upsert_refresh_token}, user::get_user_by_email
+        auth::{revoke_refresh_token, revoke_session},
+        role::get_user_roles,
+        user::{get_user_account_by_id, get_user_by_email},
     },
     errors::my_error::MyError,
     models::{
         app::AppState,
-        auth::{Claims, Login, RefreshTokenInput, TokenResponse, TokenType},
+        auth::{
+            Claims, ElevatedTokenResponse, Login, ReauthenticateInput, RefreshTokenInput,
+            TokenResponse,
+        },
+        scope::user_scopes,
+        session::Session,
+    },
+    services::{
+        client_info::{ClientInfo, ClientIp},
+        email_verification::email_verification_required,
+        password::verify_password_or_dummy,
     },
-    services::password::verify_password,
 };
 
 #[utoipa::path(
@@ -24,13 +44,16 @@ use crate::{
     request_body = Login,
     responses(
         (status = 200, description = "Login successful", body = TokenResponse),
-        (status = 400, description = "Validation error"),
-        (status = 401, description = "Invalid credentials"),
+        (status = 400, description = "Unknown audience or no grantable scope"),
+        (status = 401, description = "Invalid credentials or unverified email address"),
+        (status = 429, description = "Too many failed attempts for this account or client"),
     ),
     tag = "auth"
 )]
 pub async fn login_handler(
     State(app_state): State<AppState>,
+    headers: HeaderMap,
+    client_ip: ClientIp,
     Json(payload): Json<Login>,
 ) -> Result<Json<TokenResponse>, MyError> {
     if payload.email.is_empty() || payload.password.is_empty() {
@@ -39,25 +62,57 @@ pub async fn login_handler(
         ));
     }
 
-    let user = get_user_by_email(&app_state.pool, payload.email.clone()).await?;
+    let mut redis_conn = app_state.redis.clone();
+    check_login_allowed(&mut redis_conn, &payload.email, client_ip.0.as_deref()).await?;
 
-    if user.is_none() {
-        return Err(MyError::LoginError("User not found".to_string()));
-    }
-
-    let user = user.unwrap();
+    let user = get_user_by_email(&app_state.pool, payload.email.clone()).await?;
 
-    let verify_password = verify_password(&user.password, &payload.password).unwrap();
+    // Unknown email and wrong password look alike, in the answer and in the time it takes
+    let password_matches =
+        verify_password_or_dummy(user.as_ref().map(|user| user.password.as_str()), &payload.password);
 
-    if !verify_password {
+    let Some(user) = user.filter(|_| password_matches) else {
+        record_login_failure(&app_state, &payload.email, client_ip.0.as_deref()).await?;
         return Err(MyError::LoginError(
             "Invalid username or password".to_string(),
         ));
+    };
+
+    clear_login_failures(&mut redis_conn, &payload.email).await?;
+
+    user.status.check_sign_in()?;
+
+    if email_verification_required() && user.email_verified_at.is_none() {
+        return Err(MyError::LoginError("Email address not verified".to_string()));
+    }
+
+    // Access tokens for another resource server must name one that trusts this issuer
+    if let Some(audience) = payload.audience.as_deref()
+        && !is_known_audience(audience)
+    {
+        return Err(MyError::BadRequest);
     }
 
-    let (access_token, refresh_token) = generate_tokens(&app_state.pool, &user).await?;
+    // A requested scope is cut down to what the user's roles allow; nothing left is an error
+    let scope = match payload.scope.as_deref() {
+        Some(requested) => {
+            let roles = get_user_roles(&app_state.pool, user.id).await?;
+            let scope = user_scopes(requested, &roles);
+
+            if scope.is_empty() {
+                return Err(MyError::BadRequest);
+            }
 
-    let _ = upsert_refresh_token(&app_state.pool, user.id, &refresh_token).await;
+            Some(scope)
+        }
+        None => None,
+    };
+
+    // Every login opens its own session, other devices stay signed in
+    let mut session = Session::new(user.id, ClientInfo::new(&headers, &client_ip));
+    session.audience = payload.audience.filter(|audience| audience != resource_audience());
+    session.scope = scope;
+    let (access_token, refresh_token) = start_session(&app_state.pool, &user, &session).await?;
 
     Ok(Json(TokenResponse {
         access_token,
@@ -97,20 +152,56 @@ pub async fn logout_handler(
     Extension(claims): Extension<Claims>,
     State(app_state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, MyError> {
-    let _ = revoke_refresh_token(&app_state.pool, claims.sub).await;
-
+    // Only the current session ends; tokens issued before sessions existed carry no sid.
+    // An impersonation token has no session, and must not sign the user out anywhere.
     let mut redis_conn = app_state.redis;
-    let key_jti = format!("jti_revoked:{}", claims.jti);
-    let _: bool = redis_conn
-        .set_ex(key_jti, true, 600)
-        .await
-        .map_err(|_| MyError::Internal)?;
+
+    if !claims.is_impersonated() {
+        match claims.sid {
+            Some(session_id) => {
+                if let Ok(true) = revoke_session(&app_state.pool, claims.sub, session_id).await {
+                    revoke_session_tokens(&mut redis_conn, &[session_id]).await?;
+                }
+            }
+            None => {
+                let _ = revoke_refresh_token(&app_state.pool, claims.sub).await;
+            }
+        }
+    }
+
+    revoke_token(&mut redis_conn, &claims).await?;
 
     Ok(Json(serde_json::json!({
         "message": "Logged out successfully",
     })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/logout/all",
+    responses(
+        (status = 200, description = "Every token and session of the user revoked"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "auth"
+)]
+pub async fn logout_all_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    // Unlike revoke-others this also kills access tokens still in flight, the caller's included
+    let mut redis_conn = app_state.redis;
+    let revoked = sign_out_everywhere(&app_state.pool, &mut redis_conn, claims.sub).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Logged out everywhere",
+        "revoked_sessions": revoked,
+    })))
+}
+
 #[utoipa::path(
     post,
     path = "/api/refresh",
@@ -126,48 +217,46 @@ pub async fn refresh_token_handler(
     State(app_state): State<AppState>,
     Json(payload): Json<RefreshTokenInput>,
 ) -> Result<Json<TokenResponse>, MyError> {
-    let mut redis_conn = app_state.redis;
-    let claims = validate_jwt(&mut redis_conn, payload.refresh_token.as_str()).await?;
-
-
-    if claims.token_type != TokenType::Refresh {
-        return Err(MyError::Validation("Invalid token".to_string()));
-    }
-
-    let key_jti = format!("jti_revoked:{}", claims.jti);
-
-    let is_revoked: Option<bool> = redis_conn
-        .get(&key_jti)
-        .await
-        .map_err(|_| MyError::Internal)?;
+    let (access_token, refresh_token) =
+        rotate_refresh_token(&app_state, &payload.refresh_token, None).await?;
 
-    if is_revoked.is_some() {
-        return Err(MyError::Validation("Invalid token".to_string()));
-    }
-
-    let user = get_user_by_email(&app_state.pool, claims.email).await?;
-
-    if user.is_none() {
-        return Err(MyError::Validation("User not found".to_string()));
-    }
-
-    let user = user.unwrap();
-
-    let ttl = claims.exp as i64 - Utc::now().timestamp();
+    Ok(Json(TokenResponse {
+        access_token,
+        refresh_token,
+    }))
+}
 
-    if ttl > 0 {
-        let _: bool = redis_conn
-            .set_ex(&key_jti, true, 600)
-            .await
-            .map_err(|_| MyError::Internal)?;
-    }
+#[utoipa::path(
+    post,
+    path = "/api/reauthenticate",
+    request_body = ReauthenticateInput,
+    responses(
+        (status = 200, description = "Short-lived token allowed to perform sensitive operations", body = ElevatedTokenResponse),
+        (status = 401, description = "Unauthorized or wrong password"),
+        (status = 429, description = "Too many failed attempts for this account or client"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "auth"
+)]
+pub async fn reauthenticate_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Json(payload): Json<ReauthenticateInput>,
+) -> Result<Json<ElevatedTokenResponse>, MyError> {
+    let user = get_user_account_by_id(&app_state.pool, claims.sub)
+        .await?
+        .ok_or(MyError::Unauthorized)?;
 
-    let (access_token, refresh_token) = generate_tokens(&app_state.pool, &user).await?;
+    verify_current_password(&app_state, &user, &payload.password, client_ip.as_deref())
+        .await?;
 
-    let _ = upsert_refresh_token(&app_state.pool, user.id, &refresh_token).await;
+    let access_token = issue_elevated_token(&app_state.pool, &user, &claims).await?;
 
-    Ok(Json(TokenResponse {
+    Ok(Json(ElevatedTokenResponse {
         access_token,
-        refresh_token,
+        expires_in: ELEVATED_TOKEN_TTL,
     }))
 }
@@ -1,2 +1,8 @@
+pub mod admin;
 pub mod auth;
+pub mod oauth;
+pub mod password;
+pub mod session;
 pub mod user;
+pub mod verification;
+pub mod well_known;
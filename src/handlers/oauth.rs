@@ -0,0 +1,822 @@
+use axum::{
+    Extension,
+    extract::{Form, Json, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use chrono::Utc;
+use headers::{Authorization, HeaderMapExt, authorization::Basic};
+
+use crate::{
+    auth::{
+        auth::{
+            IMPERSONATION_TOKEN_TTL, decode_token, issue_client_token, issue_impersonation_token,
+            validate_token,
+        },
+        grants::{rotate_refresh_token, start_session},
+        lockout::{
+            check_login_allowed, clear_login_failures, record_login_failure,
+        },
+        keys::key_ring,
+        oidc::{IdTokenContext, issue_id_token, resource_audience, supports_oidc},
+        revocation::{revoke_session_tokens, revoke_token},
+    },
+    db::{
+        auth::{get_refresh_token, revoke_token_family},
+        oauth::{
+            consume_authorization_code, create_authorization_code, get_authorization_code,
+            get_oauth_client, set_authorization_code_session,
+        },
+        role::get_user_roles,
+        security_event::record_security_event,
+        user::{get_user_account_by_id, get_user_by_email, get_user_by_id},
+    },
+    errors::my_error::MyError,
+    middleware::rate_limit::{IP_RATE_LIMIT, check_rate_limit, client_rate_limit},
+    models::{
+        app::AppState,
+        auth::{Claims, TokenType},
+        oauth::{
+            ACCESS_TOKEN_TYPE, AuthorizationParams, AuthorizeRequest, DENY_DECISION,
+            IntrospectionRequest, IntrospectionResponse, OAuthTokenResponse, RevocationRequest,
+            TokenRequest, USER_ID_TOKEN_TYPE,
+        },
+        oauth_client::{
+            AUTHORIZATION_CODE_GRANT, AuthorizationCode, CLIENT_CREDENTIALS_GRANT, OAuthClient,
+            REFRESH_TOKEN_GRANT, SUPPORTED_GRANT_TYPES, TOKEN_EXCHANGE_GRANT,
+        },
+        oidc::{EMAIL_SCOPE, OPENID_SCOPE, PROFILE_SCOPE, UserInfo, has_scope},
+        scope::{ADMIN_SCOPE, restrict_to_roles},
+        security_event::{SecurityEvent, SecurityEventType},
+        session::Session,
+        user::User,
+    },
+    services::{
+        client_info::{ClientInfo, ClientIp},
+        email_verification::email_verification_required,
+        password::verify_password_or_dummy,
+        pkce::{is_valid_code_challenge, verify_code_challenge},
+        token_hash::generate_opaque_token,
+    },
+};
+
+// What a valid authorization request asks for, once checked against the client
+struct AuthorizationGrant {
+    client: OAuthClient,
+    code_challenge: String,
+    scope: Option<String>,
+    audience: Option<String>,
+}
+
+// Checks the client's authorization request. Problems with the client or redirect URI are
+// errors shown to the user, never redirected (RFC 6749 §4.1.2.1); the others are sent back
+// to the client as the inner redirect.
+async fn check_authorization_request(
+    app_state: &AppState,
+    params: &AuthorizationParams,
+) -> Result<Result<AuthorizationGrant, Redirect>, MyError> {
+    let client = get_oauth_client(&app_state.pool, &params.client_id)
+        .await?
+        .ok_or(MyError::OAuth("invalid_client"))?;
+
+    if !client.allows_redirect_uri(&params.redirect_uri) {
+        return Err(MyError::OAuth("invalid_request"));
+    }
+
+    let redirect_uri = params.redirect_uri.as_str();
+    let state = params.state.as_deref();
+    let error = |code: &str| authorization_redirect(redirect_uri, &[("error", code)], state).map(Err);
+
+    if params.response_type != "code" {
+        return error("unsupported_response_type");
+    }
+
+    if !client.allows_grant(AUTHORIZATION_CODE_GRANT) {
+        return error("unauthorized_client");
+    }
+
+    // PKCE is mandatory for every client, and only with S256
+    let code_challenge = match (params.code_challenge.as_deref(), params.code_challenge_method.as_deref()) {
+        (Some(challenge), Some("S256")) if is_valid_code_challenge(challenge) => challenge.to_string(),
+        _ => return error("invalid_request"),
+    };
+
+    let scope = match params.scope.as_deref().map(|scope| client.authorize_scopes(scope)) {
+        Some(Ok(scope)) => Some(scope),
+        Some(Err(_)) => return error("invalid_scope"),
+        None => None,
+    };
+
+    if scope.as_deref().is_some_and(|scope| has_scope(scope, OPENID_SCOPE))
+        && !supports_oidc(key_ring().active())
+    {
+        return error("invalid_scope");
+    }
+
+    let audience = match client.token_audience(params.audience.as_deref()) {
+        Ok(audience) => audience,
+        Err(_) => return error("invalid_target"),
+    };
+
+    Ok(Ok(AuthorizationGrant {
+        client,
+        code_challenge,
+        scope,
+        audience,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/oauth/authorize",
+    params(AuthorizationParams),
+    responses(
+        (status = 200, description = "The login and consent page, which posts back to this endpoint", content_type = "text/html"),
+        (status = 303, description = "Redirect to the client with an `error`"),
+        (status = 400, description = "Unknown client or unregistered redirect URI"),
+    ),
+    tag = "oauth"
+)]
+pub async fn authorize_page_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<AuthorizationParams>,
+) -> Result<Response, MyError> {
+    match check_authorization_request(&app_state, &params).await? {
+        Ok(grant) => Ok(login_page(&grant, &params, None)),
+        Err(redirect) => Ok(redirect.into_response()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/oauth/authorize",
+    request_body(content = AuthorizeRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the client with `code` and `state`, or with an `error`"),
+        (status = 400, description = "Unknown client or unregistered redirect URI"),
+        (status = 401, description = "Invalid credentials; the login page is shown again", content_type = "text/html"),
+        (status = 429, description = "Too many failed attempts for this account or client", content_type = "text/html"),
+    ),
+    tag = "oauth"
+)]
+pub async fn authorize_handler(
+    State(app_state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Form(payload): Form<AuthorizeRequest>,
+) -> Result<Response, MyError> {
+    let params = &payload.params;
+    let redirect_uri = params.redirect_uri.as_str();
+    let state = params.state.as_deref();
+
+    let grant = match check_authorization_request(&app_state, params).await? {
+        Ok(grant) => grant,
+        Err(redirect) => return Ok(redirect.into_response()),
+    };
+
+    if payload.decision.as_deref() == Some(DENY_DECISION) {
+        return Ok(authorization_redirect(redirect_uri, &[("error", "access_denied")], state)?.into_response());
+    }
+
+    // A failed sign-in shows the page again, still holding the client's request
+    let user = match sign_in(&app_state, client_ip.as_deref(), &payload.email, &payload.password).await {
+        Ok(user) => user,
+        Err(err @ (MyError::LoginError(_) | MyError::TooManyRequests)) => {
+            return Ok(login_page(&grant, params, Some(&err)));
+        }
+        Err(err) => return Err(err),
+    };
+
+    // A client only gets the API scopes the user could use themselves
+    let scope = match grant.scope {
+        Some(scope) => {
+            let roles = get_user_roles(&app_state.pool, user.id).await?;
+            Some(restrict_to_roles(&scope, &roles))
+        }
+        None => None,
+    };
+
+    let code = generate_opaque_token();
+
+    let mut authorization_code = AuthorizationCode::new(
+        &code,
+        grant.client.client_id,
+        user.id,
+        params.redirect_uri.clone(),
+        grant.code_challenge,
+    );
+    authorization_code.scope = scope;
+    authorization_code.nonce = params.nonce.clone();
+    authorization_code.audience = grant.audience;
+
+    create_authorization_code(&app_state.pool, &authorization_code).await?;
+
+    Ok(authorization_redirect(redirect_uri, &[("code", &code)], state)?.into_response())
+}
+
+// The user behind the credentials posted to the login page, with the same lockout and
+// account checks as /api/login
+async fn sign_in(
+    app_state: &AppState,
+    client_ip: Option<&str>,
+    email: &str,
+    password: &str,
+) -> Result<User, MyError> {
+    let mut redis_conn = app_state.redis.clone();
+    check_login_allowed(&mut redis_conn, email, client_ip).await?;
+
+    let user = get_user_by_email(&app_state.pool, email.to_string()).await?;
+    let password_matches =
+        verify_password_or_dummy(user.as_ref().map(|user| user.password.as_str()), password);
+
+    let Some(user) = user.filter(|_| password_matches) else {
+        record_login_failure(app_state, email, client_ip).await?;
+        return Err(MyError::LoginError("Invalid username or password".to_string()));
+    };
+
+    clear_login_failures(&mut redis_conn, email).await?;
+
+    user.status.check_sign_in()?;
+
+    if email_verification_required() && user.email_verified_at.is_none() {
+        return Err(MyError::LoginError("Email address not verified".to_string()));
+    }
+
+    Ok(user)
+}
+
+// The identity provider's own login and consent page. It names the client and what it
+// asks for, and posts the credentials back with the client's request in hidden fields.
+fn login_page(grant: &AuthorizationGrant, params: &AuthorizationParams, error: Option<&MyError>) -> Response {
+    let status = match error {
+        Some(MyError::TooManyRequests) => StatusCode::TOO_MANY_REQUESTS,
+        Some(_) => StatusCode::UNAUTHORIZED,
+        None => StatusCode::OK,
+    };
+
+    let hidden = [
+        ("response_type", Some(params.response_type.as_str())),
+        ("client_id", Some(params.client_id.as_str())),
+        ("redirect_uri", Some(params.redirect_uri.as_str())),
+        ("state", params.state.as_deref()),
+        ("code_challenge", params.code_challenge.as_deref()),
+        ("code_challenge_method", params.code_challenge_method.as_deref()),
+        ("scope", params.scope.as_deref()),
+        ("nonce", params.nonce.as_deref()),
+        ("audience", params.audience.as_deref()),
+    ]
+    .iter()
+    .filter_map(|(name, value)| {
+        value.map(|value| format!(r#"<input type="hidden" name="{}" value="{}">"#, name, escape_html(value)))
+    })
+    .collect::<Vec<_>>()
+    .join("\n      ");
+
+    let scopes = grant
+        .scope
+        .as_deref()
+        .map(|scope| {
+            scope
+                .split_whitespace()
+                .map(|scope| format!("<li>{}</li>", escape_html(scope)))
+                .collect::<String>()
+        })
+        .unwrap_or_else(|| "<li>Your basic account information</li>".to_string());
+
+    let error = error
+        .map(|err| format!(r#"<p role="alert">{}</p>"#, escape_html(&err.to_string())))
+        .unwrap_or_default();
+
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>Sign in to {client}</title>
+  </head>
+  <body>
+    <h1>Sign in to continue to {client}</h1>
+    {error}
+    <form method="post" action="authorize">
+      {hidden}
+      <label>Email <input type="email" name="email" autocomplete="username" required></label>
+      <label>Password <input type="password" name="password" autocomplete="current-password" required></label>
+      <p>{client} will be able to access:</p>
+      <ul>{scopes}</ul>
+      <button type="submit" name="decision" value="allow">Sign in and allow</button>
+      <button type="submit" name="decision" value="{deny}" formnovalidate>Deny</button>
+    </form>
+  </body>
+</html>
+"#,
+        client = escape_html(&grant.client.name),
+        error = error,
+        hidden = hidden,
+        scopes = scopes,
+        deny = DENY_DECISION,
+    );
+
+    (
+        status,
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            // Nobody may frame the page and trick the user into approving a client
+            (header::X_FRAME_OPTIONS, "DENY"),
+            (header::CONTENT_SECURITY_POLICY, "frame-ancestors 'none'"),
+        ],
+        Html(page),
+    )
+        .into_response()
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/oauth/token",
+    request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Tokens issued", body = OAuthTokenResponse),
+        (status = 400, description = "OAuth error such as `invalid_grant` or `invalid_scope`"),
+        (status = 401, description = "Client authentication failed (`invalid_client`)"),
+        (status = 429, description = "Too many requests from this client, or failed authentications from this IP"),
+    ),
+    tag = "oauth"
+)]
+pub async fn token_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    client_ip: ClientIp,
+    Form(payload): Form<TokenRequest>,
+) -> Result<impl IntoResponse, MyError> {
+    let mut redis_conn = app_state.redis.clone();
+
+    // Authenticated clients are limited on their own, failed attempts per IP like the
+    // other public endpoints
+    let client = match authenticate_client(
+        &app_state,
+        &headers,
+        payload.client_id.as_deref(),
+        payload.client_secret.as_deref(),
+    )
+    .await
+    {
+        Ok(client) => client,
+        Err(err) => {
+            let ip = client_ip.0.as_deref().unwrap_or("unknown");
+            let key = format!("rate_limit:{}:oauth_token_failures", ip);
+            check_rate_limit(&mut redis_conn, &key, IP_RATE_LIMIT).await?;
+
+            return Err(err);
+        }
+    };
+
+    let key = format!("rate_limit:client:{}", client.client_id);
+    check_rate_limit(&mut redis_conn, &key, client_rate_limit()).await?;
+
+    if !SUPPORTED_GRANT_TYPES.contains(&payload.grant_type.as_str()) {
+        return Err(MyError::OAuth("unsupported_grant_type"));
+    }
+
+    if !client.allows_grant(&payload.grant_type) {
+        return Err(MyError::OAuth("unauthorized_client"));
+    }
+
+    let response = match payload.grant_type.as_str() {
+        AUTHORIZATION_CODE_GRANT => {
+            exchange_authorization_code(&app_state, &client, ClientInfo::new(&headers, &client_ip), &payload)
+                .await?
+        }
+        REFRESH_TOKEN_GRANT => {
+            let refresh_token = payload
+                .refresh_token
+                .as_deref()
+                .ok_or(MyError::OAuth("invalid_request"))?;
+
+            let (access_token, refresh_token) =
+                rotate_refresh_token(&app_state, refresh_token, Some(&client.client_id))
+                    .await
+                    .map_err(|err| match err {
+                        MyError::Validation(_) => MyError::OAuth("invalid_grant"),
+                        err => err,
+                    })?;
+
+            OAuthTokenResponse::bearer(access_token, Some(refresh_token), None)
+        }
+        CLIENT_CREDENTIALS_GRANT => {
+            let scope = client.grant_scopes(payload.scope.as_deref())?;
+            let audience = client
+                .token_audience(payload.audience.as_deref())?
+                .unwrap_or_else(|| resource_audience().to_string());
+            let access_token = issue_client_token(&client, &scope, &audience)?;
+
+            OAuthTokenResponse::bearer(access_token, None, Some(scope))
+        }
+        TOKEN_EXCHANGE_GRANT => exchange_token(&app_state, &client, &payload).await?,
+        _ => return Err(MyError::OAuth("unsupported_grant_type")),
+    };
+
+    // RFC 6749 §5.1: token responses must not be cached
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/userinfo",
+    responses(
+        (status = 200, description = "Claims about the signed-in user, as allowed by the token's scope", body = UserInfo),
+        (status = 401, description = "Unauthorized or missing openid scope"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "oauth"
+)]
+pub async fn userinfo_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+) -> Result<Json<UserInfo>, MyError> {
+    // Tokens from a direct login carry no scope and see every claim; tokens
+    // issued to a client need openid and only see what they were granted,
+    // so a client token without any scope sees nothing
+    let granted = |wanted: &str| match claims.scope.as_deref() {
+        Some(scope) => has_scope(scope, wanted),
+        None => claims.client_id.is_none(),
+    };
+
+    if !granted(OPENID_SCOPE) {
+        return Err(MyError::Unauthorized);
+    }
+
+    let user = get_user_by_id(&app_state.pool, claims.sub).await?;
+
+    Ok(Json(UserInfo {
+        sub: user.id,
+        name: granted(PROFILE_SCOPE).then_some(user.name),
+        email: granted(EMAIL_SCOPE).then_some(user.email),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/oauth/introspect",
+    request_body(content = IntrospectionRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token state, `active: false` for any invalid, expired or revoked token", body = IntrospectionResponse),
+        (status = 401, description = "Unauthorized, or not a client token with the tokens:introspect scope"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "oauth"
+)]
+pub async fn introspect_handler(
+    State(app_state): State<AppState>,
+    Form(payload): Form<IntrospectionRequest>,
+) -> Result<Json<IntrospectionResponse>, MyError> {
+    let mut redis_conn = app_state.redis;
+
+    // The hint is only an optimisation (RFC 7662 §2.1): the token itself says what it is.
+    // Tokens for any audience are described, resource servers check `aud` themselves.
+    let claims = match validate_token(&app_state.pool, &mut redis_conn, &payload.token, None).await {
+        Ok(claims) => claims,
+        Err(_) => return Ok(Json(IntrospectionResponse::inactive())),
+    };
+
+    // Refresh tokens must also still be redeemable
+    if claims.token_type == TokenType::Refresh {
+        let stored_token = get_refresh_token(&app_state.pool, &payload.token).await?;
+
+        let redeemable = stored_token
+            .map(|token| token.consumed_at.is_none() && token.revoked_at.is_none())
+            .unwrap_or(false);
+
+        if !redeemable {
+            return Ok(Json(IntrospectionResponse::inactive()));
+        }
+    }
+
+    Ok(Json(IntrospectionResponse::active(claims)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/oauth/revoke",
+    request_body(content = RevocationRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token revoked, or it was already invalid or not the client's"),
+        (status = 401, description = "Client authentication failed"),
+    ),
+    tag = "oauth"
+)]
+pub async fn revoke_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Form(payload): Form<RevocationRequest>,
+) -> Result<StatusCode, MyError> {
+    // A client that identifies itself must authenticate like at the token endpoint
+    let client = match (headers.contains_key(header::AUTHORIZATION), payload.client_id.as_deref()) {
+        (false, None) => None,
+        _ => Some(
+            authenticate_client(
+                &app_state,
+                &headers,
+                payload.client_id.as_deref(),
+                payload.client_secret.as_deref(),
+            )
+            .await?,
+        ),
+    };
+
+    // Invalid or expired tokens are answered like valid ones (RFC 7009 §2.2), so the
+    // endpoint reveals nothing
+    let claims = match decode_token(&payload.token, None) {
+        Ok(claims) => claims,
+        Err(_) => return Ok(StatusCode::OK),
+    };
+
+    // A client's tokens can only be revoked by that client (RFC 7009 §2.1); a direct
+    // login's tokens by whoever holds them. Anything else is left alone.
+    if claims.client_id.as_deref() != client.as_ref().map(|client| client.client_id.as_str()) {
+        return Ok(StatusCode::OK);
+    }
+
+    let mut redis_conn = app_state.redis;
+
+    // Revoking a refresh token ends its session, so no new access tokens can be minted from it
+    // and the ones already issued stop working
+    if claims.token_type == TokenType::Refresh
+        && let Some(stored_token) = get_refresh_token(&app_state.pool, &payload.token).await?
+    {
+        revoke_token_family(&app_state.pool, stored_token.family_id).await?;
+        revoke_session_tokens(&mut redis_conn, &[stored_token.family_id]).await?;
+    }
+
+    revoke_token(&mut redis_conn, &claims).await?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn exchange_authorization_code(
+    app_state: &AppState,
+    client: &OAuthClient,
+    client_info: ClientInfo,
+    payload: &TokenRequest,
+) -> Result<OAuthTokenResponse, MyError> {
+    let (Some(code), Some(redirect_uri), Some(code_verifier)) = (
+        payload.code.as_deref(),
+        payload.redirect_uri.as_deref(),
+        payload.code_verifier.as_deref(),
+    ) else {
+        return Err(MyError::OAuth("invalid_request"));
+    };
+
+    let stored_code = get_authorization_code(&app_state.pool, code)
+        .await?
+        .ok_or(MyError::OAuth("invalid_grant"))?;
+
+    // The code is bound to the client and redirect URI of the authorization request, and
+    // to the verifier behind its challenge. A request failing these leaves the code alone,
+    // so whoever intercepted it cannot burn it for the client that holds the verifier.
+    if stored_code.client_id != client.client_id
+        || stored_code.redirect_uri != redirect_uri
+        || stored_code.expires_at < Utc::now()
+        || !verify_code_challenge(code_verifier, &stored_code.code_challenge)
+    {
+        return Err(MyError::OAuth("invalid_grant"));
+    }
+
+    // A code is single use: a replay ends the session it opened (RFC 6749 §4.1.2)
+    if !consume_authorization_code(&app_state.pool, &stored_code.code_hash).await? {
+        if let Some(session_id) = stored_code.session_id {
+            revoke_token_family(&app_state.pool, session_id).await?;
+            revoke_session_tokens(&mut app_state.redis.clone(), &[session_id]).await?;
+        }
+
+        tracing::warn!(
+            "Authorization code reuse detected for user {}, client {}",
+            stored_code.user_id,
+            stored_code.client_id
+        );
+
+        record_security_event(
+            &app_state.pool,
+            SecurityEvent::new(
+                Some(stored_code.user_id),
+                SecurityEventType::AuthorizationCodeReuse,
+                serde_json::json!({
+                    "client_id": stored_code.client_id,
+                    "session_id": stored_code.session_id,
+                }),
+            ),
+        )
+        .await?;
+
+        return Err(MyError::OAuth("invalid_grant"));
+    }
+
+    let user = get_user_account_by_id(&app_state.pool, stored_code.user_id)
+        .await?
+        .ok_or(MyError::OAuth("invalid_grant"))?;
+
+    // The account may have been suspended or its address unverified since it signed in
+    if user.status.check_sign_in().is_err()
+        || (email_verification_required() && user.email_verified_at.is_none())
+    {
+        return Err(MyError::OAuth("invalid_grant"));
+    }
+
+    // The user signed in when the code was issued: its tokens, the ID token and the
+    // refreshed ones all report that time as auth_time
+    let mut session = Session::new(user.id, client_info);
+    session.created_at = stored_code.created_at;
+    session.client_id = Some(client.client_id.clone());
+    session.scope = stored_code.scope.clone();
+    session.audience = stored_code.audience.clone();
+
+    let (access_token, refresh_token) = start_session(&app_state.pool, &user, &session).await?;
+
+    set_authorization_code_session(&app_state.pool, &stored_code.code_hash, session.id).await?;
+
+    let mut response =
+        OAuthTokenResponse::bearer(access_token, Some(refresh_token), stored_code.scope.clone());
+
+    // OpenID Connect: the ID token tells the client who signed in and when
+    if let Some(scope) = stored_code.scope.as_deref().filter(|scope| has_scope(scope, OPENID_SCOPE)) {
+        let context = IdTokenContext {
+            client_id: &client.client_id,
+            scope,
+            auth_time: stored_code.created_at.timestamp() as usize,
+            nonce: stored_code.nonce.as_deref(),
+            session_id: Some(session.id),
+        };
+
+        response.id_token = Some(issue_id_token(key_ring().active(), &user, &context)?);
+    }
+
+    Ok(response)
+}
+
+// RFC 8693 impersonation: the actor token proves an admin is asking, the subject token
+// names the user to act as. The access token issued for that user carries an act claim
+// naming the admin, lives 5 minutes and comes without a refresh token.
+async fn exchange_token(
+    app_state: &AppState,
+    client: &OAuthClient,
+    payload: &TokenRequest,
+) -> Result<OAuthTokenResponse, MyError> {
+    if payload
+        .requested_token_type
+        .as_deref()
+        .is_some_and(|token_type| token_type != ACCESS_TOKEN_TYPE)
+    {
+        return Err(MyError::OAuth("invalid_request"));
+    }
+
+    let actor = match (payload.actor_token.as_deref(), payload.actor_token_type.as_deref()) {
+        (Some(token), Some(ACCESS_TOKEN_TYPE)) => exchanged_access_token(app_state, token).await?,
+        _ => return Err(MyError::OAuth("invalid_request")),
+    };
+
+    // Only an admin signed in as themselves, with the admin scope if the token is a
+    // client's; an impersonation cannot be chained
+    let admin_scope = match actor.scope.as_deref() {
+        Some(scope) => has_scope(scope, ADMIN_SCOPE),
+        None => actor.client_id.is_none(),
+    };
+
+    if !actor.roles.iter().any(|role| role == "Admin") || !admin_scope || actor.is_impersonated() {
+        return Err(MyError::OAuth("invalid_grant"));
+    }
+
+    let subject = match (payload.subject_token.as_deref(), payload.subject_token_type.as_deref()) {
+        (Some(token), Some(ACCESS_TOKEN_TYPE)) => exchanged_access_token(app_state, token).await?.sub,
+        (Some(user_id), Some(USER_ID_TOKEN_TYPE)) => {
+            uuid::Uuid::parse_str(user_id).map_err(|_| MyError::OAuth("invalid_grant"))?
+        }
+        _ => return Err(MyError::OAuth("invalid_request")),
+    };
+
+    if subject == actor.sub {
+        return Err(MyError::OAuth("invalid_request"));
+    }
+
+    let user = get_user_account_by_id(&app_state.pool, subject)
+        .await?
+        .ok_or(MyError::OAuth("invalid_grant"))?;
+
+    // Acting as another admin would hide who did what behind a second admin account
+    let roles = get_user_roles(&app_state.pool, user.id).await?;
+    if roles.iter().any(|role| role == "Admin") {
+        return Err(MyError::OAuth("invalid_grant"));
+    }
+
+    let (access_token, impersonation) =
+        issue_impersonation_token(&app_state.pool, &user, actor.sub).await?;
+
+    tracing::info!("Admin {} is impersonating user {}", actor.sub, user.id);
+
+    record_security_event(
+        &app_state.pool,
+        SecurityEvent::new(
+            Some(user.id),
+            SecurityEventType::Impersonation,
+            serde_json::json!({
+                "actor": actor.sub,
+                "client_id": client.client_id,
+                "jti": impersonation.jti,
+                "expires_at": impersonation.exp,
+            }),
+        ),
+    )
+    .await?;
+
+    Ok(OAuthTokenResponse {
+        expires_in: IMPERSONATION_TOKEN_TTL,
+        issued_token_type: Some(ACCESS_TOKEN_TYPE.to_string()),
+        ..OAuthTokenResponse::bearer(access_token, None, None)
+    })
+}
+
+// A subject or actor token: a live access token issued to a user, not to a client
+async fn exchanged_access_token(app_state: &AppState, token: &str) -> Result<Claims, MyError> {
+    let mut redis_conn = app_state.redis.clone();
+
+    let claims = validate_token(&app_state.pool, &mut redis_conn, token, Some(resource_audience()))
+        .await
+        .map_err(|err| match err {
+            MyError::Validation(_) => MyError::OAuth("invalid_grant"),
+            err => err,
+        })?;
+
+    if claims.token_type != TokenType::Access || claims.machine {
+        return Err(MyError::OAuth("invalid_grant"));
+    }
+
+    Ok(claims)
+}
+
+// RFC 6749 §2.3.1: credentials come through HTTP Basic or the form. Confidential
+// clients must present their secret, public clients are identified by client_id alone.
+async fn authenticate_client(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<OAuthClient, MyError> {
+    let basic = headers.typed_get::<Authorization<Basic>>();
+
+    let (client_id, client_secret) = match &basic {
+        Some(credentials) => {
+            // Only one authentication method per request
+            if client_secret.is_some() || client_id.is_some_and(|id| id != credentials.username()) {
+                return Err(MyError::OAuth("invalid_request"));
+            }
+
+            (credentials.username(), Some(credentials.password()))
+        }
+        None => (client_id.ok_or(MyError::OAuth("invalid_client"))?, client_secret),
+    };
+
+    let client = get_oauth_client(&app_state.pool, client_id)
+        .await?
+        .ok_or(MyError::OAuth("invalid_client"))?;
+
+    let authenticated = match client_secret {
+        Some(client_secret) => client.verify_secret(client_secret),
+        None => !client.is_confidential(),
+    };
+
+    if !authenticated {
+        return Err(MyError::OAuth("invalid_client"));
+    }
+
+    Ok(client)
+}
+
+// Sends the user back to the client; the redirect URI must already be a registered one
+fn authorization_redirect(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    state: Option<&str>,
+) -> Result<Redirect, MyError> {
+    let mut url = url::Url::parse(redirect_uri).map_err(|_| MyError::OAuth("invalid_request"))?;
+
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+
+    Ok(Redirect::to(url.as_str()))
+}
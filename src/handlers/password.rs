@@ -0,0 +1,163 @@
+use axum::{
+    Extension,
+    extract::{Json, State},
+    http::HeaderMap,
+};
+
+use crate::{
+    auth::{epoch::sign_out_everywhere, grants::start_session, oidc::resource_audience},
+    db::{
+        security_event::record_security_event,
+        user::{get_user_account_by_id, get_user_by_email},
+    },
+    errors::my_error::MyError,
+    models::{
+        app::AppState,
+        auth::{Claims, TokenResponse},
+        security_event::{SecurityEvent, SecurityEventType},
+        session::Session,
+        user::{ChangePasswordInput, ForgotPasswordInput, ResetPasswordInput},
+    },
+    services::{
+        client_info::{ClientInfo, ClientIp},
+        password_change::change_password,
+        password_reset::{reset_password, send_password_reset_email},
+    },
+};
+
+#[utoipa::path(
+    post,
+    path = "/api/password/forgot",
+    request_body = ForgotPasswordInput,
+    responses(
+        (status = 200, description = "A reset link was sent if the account exists"),
+        (status = 429, description = "Too many requests"),
+    ),
+    tag = "auth"
+)]
+pub async fn forgot_password_handler(
+    State(app_state): State<AppState>,
+    Json(payload): Json<ForgotPasswordInput>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    let email = payload.email.trim().to_lowercase();
+
+    // The answer is the same either way, so it cannot be used to probe for accounts. The
+    // link is mailed in the background and a failed send only logged, so neither a slower
+    // response nor an error gives a registered address away. Accounts that cannot sign in
+    // get no link either.
+    let user = get_user_by_email(&app_state.pool, email).await?;
+
+    if let Some(user) = user.filter(|user| user.status.allows_sign_in()) {
+        let pool = app_state.pool.clone();
+        let mailer = app_state.mailer.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = send_password_reset_email(&pool, mailer.as_ref(), user.id, &user.email).await {
+                tracing::warn!("Could not send the password reset email to user {}: {}", user.id, err);
+            }
+        });
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "If the account exists, a password reset link was sent",
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/password/reset",
+    request_body = ResetPasswordInput,
+    responses(
+        (status = 200, description = "Password changed and every session ended"),
+        (status = 401, description = "Invalid, expired or already used token"),
+        (status = 422, description = "The password breaks the password policy"),
+        (status = 429, description = "Too many requests"),
+    ),
+    tag = "auth"
+)]
+pub async fn reset_password_handler(
+    State(app_state): State<AppState>,
+    Json(payload): Json<ResetPasswordInput>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    let user_id = reset_password(&app_state.pool, &payload.token, &payload.password).await?;
+
+    // Whoever got hold of the old password must not stay signed in
+    let mut redis_conn = app_state.redis;
+    let sessions_revoked = sign_out_everywhere(&app_state.pool, &mut redis_conn, user_id).await?;
+
+    record_security_event(
+        &app_state.pool,
+        SecurityEvent::new(
+            Some(user_id),
+            SecurityEventType::PasswordReset,
+            serde_json::json!({ "sessions_revoked": sessions_revoked }),
+        ),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Password reset successfully",
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/me/password",
+    request_body = ChangePasswordInput,
+    responses(
+        (status = 200, description = "Password changed; every other session ended and a new token pair issued", body = TokenResponse),
+        (status = 401, description = "Unauthorized, wrong current password or unchanged password"),
+        (status = 422, description = "The new password breaks the password policy"),
+        (status = 429, description = "Too many failed attempts for this account or client"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "auth"
+)]
+pub async fn change_password_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    client_ip: ClientIp,
+    Json(payload): Json<ChangePasswordInput>,
+) -> Result<Json<TokenResponse>, MyError> {
+    change_password(
+        &app_state,
+        claims.sub,
+        &payload.current_password,
+        &payload.new_password,
+        client_ip.0.as_deref(),
+    )
+    .await?;
+
+    // Every token issued so far dies with the old password, this one included
+    let mut redis_conn = app_state.redis;
+    let sessions_revoked = sign_out_everywhere(&app_state.pool, &mut redis_conn, claims.sub).await?;
+
+    record_security_event(
+        &app_state.pool,
+        SecurityEvent::new(
+            Some(claims.sub),
+            SecurityEventType::PasswordChange,
+            serde_json::json!({ "sessions_revoked": sessions_revoked }),
+        ),
+    )
+    .await?;
+
+    // The device that made the change stays signed in through a fresh session
+    let user = get_user_account_by_id(&app_state.pool, claims.sub)
+        .await?
+        .ok_or(MyError::Unauthorized)?;
+
+    let mut session = Session::new(user.id, ClientInfo::new(&headers, &client_ip));
+    session.client_id = claims.client_id;
+    session.scope = claims.scope;
+    session.audience = Some(claims.aud).filter(|audience| audience != resource_audience());
+    let (access_token, refresh_token) = start_session(&app_state.pool, &user, &session).await?;
+
+    Ok(Json(TokenResponse {
+        access_token,
+        refresh_token,
+    }))
+}
@@ -0,0 +1,102 @@
+use axum::{
+    Extension,
+    extract::{Json, Path, State},
+};
+
+use crate::{
+    auth::revocation::revoke_session_tokens,
+    db::auth::{get_user_sessions, revoke_session, revoke_user_sessions},
+    errors::my_error::MyError,
+    models::{app::AppState, auth::Claims, session::SessionOutput},
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/sessions",
+    responses(
+        (status = 200, description = "Active sessions of the current user", body = Vec<SessionOutput>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "auth"
+)]
+pub async fn list_sessions_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<SessionOutput>>, MyError> {
+    let sessions = get_user_sessions(&app_state.pool, claims.sub).await?;
+
+    Ok(Json(
+        sessions
+            .iter()
+            .map(|session| session.to_output(claims.sid))
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/sessions/{session_id}",
+    params(
+        ("session_id" = uuid::Uuid, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 200, description = "Session revoked"),
+        (status = 404, description = "Session not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "auth"
+)]
+pub async fn revoke_session_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+    Path(session_id): Path<uuid::Uuid>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    let revoked = revoke_session(&app_state.pool, claims.sub, session_id).await?;
+
+    if !revoked {
+        return Err(MyError::NotFound);
+    }
+
+    let mut redis_conn = app_state.redis;
+    revoke_session_tokens(&mut redis_conn, &[session_id]).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Session revoked successfully",
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/sessions/revoke-others",
+    responses(
+        (status = 200, description = "Every other session revoked"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "auth"
+)]
+pub async fn revoke_other_sessions_handler(
+    Extension(claims): Extension<Claims>,
+    State(app_state): State<AppState>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    // Without a session id we cannot tell which session to keep
+    let current_session = claims.sid.ok_or(MyError::BadRequest)?;
+
+    let revoked = revoke_user_sessions(&app_state.pool, claims.sub, None, Some(current_session)).await?;
+
+    let mut redis_conn = app_state.redis;
+    revoke_session_tokens(&mut redis_conn, &revoked).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Signed out of other devices",
+        "revoked_sessions": revoked.len(),
+    })))
+}
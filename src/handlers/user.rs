@@ -1,13 +1,23 @@
-use crate::db::user::{create_user, delete_user, get_user_by_email, get_user_by_id, update_user};
+use crate::auth::{account_status::change_status, epoch::sign_out_everywhere};
+use crate::db::security_event::record_security_event;
+use crate::db::user::{get_user_account_by_id, get_user_by_id, get_user_status, update_user};
 use crate::errors::my_error::MyError;
 
 use crate::models::user::UserWithRoles;
 use crate::models::{
     app::AppState,
-    user::{UserOutput, UserRegister},
+    auth::Claims,
+    security_event::{SecurityEvent, SecurityEventType},
+    user::{UserOutput, UserRegister, UserStatus},
+};
+use crate::services::{
+    email_verification::send_verification_email, password::hash_password,
+    password_policy::password_policy, registration::register_user,
+};
+use axum::{
+    Extension,
+    extract::{Json, Path, State},
 };
-use crate::services::password::hash_password;
-use axum::extract::{Json, Path, State};
 
 #[utoipa::path(
     get,
@@ -19,6 +29,7 @@ use axum::extract::{Json, Path, State};
         (status = 200, description = "User retrieved successfully", body = UserWithRoles),
         (status = 404, description = "User not found"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Another user's account, without the Admin role"),
     ),
     tag = "users"
 )]
@@ -36,40 +47,34 @@ pub async fn get_user_handler(
     path = "/api/users",
     request_body = UserRegister,
     responses(
-        (status = 200, description = "User created successfully", body = UserOutput),
-        (status = 400, description = "Validation error"),
-        (status = 409, description = "User already exists"),
+        (status = 200, description = "Sign-up accepted; the address receives a verification link, or a notice if it is already registered"),
+        (status = 401, description = "Validation error"),
+        (status = 422, description = "The password breaks the password policy"),
     ),
     tag = "users"
 )]
 pub async fn create_user_handler(
     State(app_state): State<AppState>,
     Json(payload): Json<UserRegister>,
-) -> Result<Json<UserOutput>, MyError> {
+) -> Result<Json<serde_json::Value>, MyError> {
     if payload.name.is_none() || payload.email.is_none() || payload.password.is_none() {
         return Err(MyError::Validation(
             "Name, email and password are required".to_string(),
         ));
     }
 
-    let exist_user =
-        get_user_by_email(&app_state.pool, Option::expect(payload.email.clone(), "")).await?;
-
-    if exist_user.is_some() {
-        return Err(MyError::Validation("User already exists".to_string()));
+    // Only an address that can receive the verification email
+    let email = payload.email.as_deref().unwrap_or_default().trim();
+    if email.parse::<lettre::Address>().is_err() {
+        return Err(MyError::Validation("Invalid email address".to_string()));
     }
 
-    let user: UserRegister = UserRegister {
-        name: payload.name,
-        email: payload.email,
-        password: payload.password,
-    };
-
-    let result = create_user(&app_state.pool, user).await?;
-
-
+    // Whether the address was already registered is only ever told to its owner, by email
+    register_user(&app_state.pool, app_state.mailer.as_ref(), payload).await?;
 
-    Ok(Json(result))
+    Ok(Json(serde_json::json!({
+        "message": "Check your email to finish signing up",
+    })))
 }
 
 #[utoipa::path(
@@ -82,8 +87,11 @@ pub async fn create_user_handler(
     responses(
         (status = 200, description = "User updated successfully", body = UserOutput),
         (status = 400, description = "Validation error"),
-        (status = 404, description = "User not found"),
+        (status = 409, description = "The email address belongs to another account"),
+        (status = 422, description = "The password breaks the password policy"),
+        (status = 404, description = "User not found or deleted"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Another user's account, without the Admin role"),
     ),
     tag = "users"
 )]
@@ -98,19 +106,59 @@ pub async fn update_user_handler(
         ));
     }
 
-    if payload.password.is_some() {
-        let hash_password = hash_password(&payload.password.unwrap()).unwrap();
+    // A deleted account is closed for good, edits included
+    let current = get_user_account_by_id(&app_state.pool, user_id)
+        .await?
+        .filter(|user| user.status != UserStatus::Deleted)
+        .ok_or(MyError::NotFound)?;
+
+    payload.email = payload.email.map(|email| email.trim().to_lowercase());
+
+    // Only an address that can receive the verification email, as at sign-up
+    if payload.email.as_deref().is_some_and(|email| email.parse::<lettre::Address>().is_err()) {
+        return Err(MyError::Validation("Invalid email address".to_string()));
+    }
+
+    let password_changed = payload.password.is_some();
+    let email_changed = payload.email.as_ref().is_some_and(|email| *email != current.email);
+
+    if let Some(password) = payload.password.as_deref() {
+        let name = payload.name.as_deref().unwrap_or(&current.name);
+        let email = payload.email.as_deref().unwrap_or(&current.email);
+        password_policy().check(password, &[name, email])?;
+
+        let hash_password = hash_password(password).unwrap();
         payload.password = Some(hash_password);
     }
 
     let user = UserRegister {
         name: payload.name,
-        email: Some(payload.email.unwrap().to_lowercase()),
+        email: payload.email,
         password: payload.password,
     };
 
     let result = update_user(&app_state.pool, user_id, user).await?;
 
+    // Whoever knew the old password must not stay signed in
+    if password_changed {
+        let mut redis_conn = app_state.redis;
+        sign_out_everywhere(&app_state.pool, &mut redis_conn, user_id).await?;
+    }
+
+    // The new address is unverified until its owner opens the link; sent in the background
+    // like the other verification mails, and a failed send can be retried through resend
+    if email_changed {
+        let pool = app_state.pool.clone();
+        let mailer = app_state.mailer.clone();
+        let email = result.email.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = send_verification_email(&pool, mailer.as_ref(), user_id, &email).await {
+                tracing::warn!("Could not send the verification email to user {}: {}", user_id, err);
+            }
+        });
+    }
+
     Ok(Json(result))
 }
 
@@ -121,17 +169,46 @@ pub async fn update_user_handler(
         ("user_id" = uuid::Uuid, Path, description = "User ID")
     ),
     responses(
-        (status = 200, description = "User deleted successfully", body = String),
-        (status = 404, description = "User not found"),
+        (status = 200, description = "Account closed and signed out everywhere; its data is kept", body = String),
+        (status = 404, description = "User not found or already deleted"),
         (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Another user's account, without the Admin role"),
     ),
     tag = "users"
 )]
 pub async fn delete_user_handler(
+    Extension(claims): Extension<Claims>,
     State(app_state): State<AppState>,
     Path(user_id): Path<uuid::Uuid>,
 ) -> Result<Json<String>, MyError> {
-    delete_user(&app_state.pool, user_id).await?;
+    let status = get_user_status(&app_state.pool, user_id).await?;
+
+    if status.is_none_or(|status| status == UserStatus::Deleted) {
+        return Err(MyError::NotFound);
+    }
+
+    // Closed rather than removed, so the row and its audit trail stay. The Deleted status
+    // keeps the account from signing in again, and leaving Active signs it out everywhere.
+    let reason = if claims.sub == user_id { "Deleted by the user" } else { "Deleted by an admin" };
+    let mut redis_conn = app_state.redis.clone();
+    let previous =
+        change_status(&app_state.pool, &mut redis_conn, user_id, UserStatus::Deleted, Some(reason))
+            .await?;
+
+    record_security_event(
+        &app_state.pool,
+        SecurityEvent::new(
+            Some(user_id),
+            SecurityEventType::StatusChange,
+            serde_json::json!({
+                "actor": claims.sub,
+                "from": previous,
+                "to": UserStatus::Deleted,
+                "reason": reason,
+            }),
+        ),
+    )
+    .await?;
 
     Ok(Json("User deleted successfully".to_string()))
 }
@@ -0,0 +1,67 @@
+use axum::extract::{Json, State};
+
+use crate::{
+    db::user::get_user_by_email,
+    errors::my_error::MyError,
+    models::{
+        app::AppState,
+        user::{ResendVerificationInput, VerifyEmailInput},
+    },
+    services::email_verification::{send_verification_email, verify_email},
+};
+
+#[utoipa::path(
+    post,
+    path = "/api/verify-email",
+    request_body = VerifyEmailInput,
+    responses(
+        (status = 200, description = "Email address verified"),
+        (status = 401, description = "Invalid, expired or already used token"),
+    ),
+    tag = "users"
+)]
+pub async fn verify_email_handler(
+    State(app_state): State<AppState>,
+    Json(payload): Json<VerifyEmailInput>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    verify_email(&app_state.pool, &payload.token).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Email verified successfully",
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/verify-email/resend",
+    request_body = ResendVerificationInput,
+    responses(
+        (status = 200, description = "A new link was sent if the account exists and is unverified"),
+        (status = 429, description = "Too many requests"),
+    ),
+    tag = "users"
+)]
+pub async fn resend_verification_handler(
+    State(app_state): State<AppState>,
+    Json(payload): Json<ResendVerificationInput>,
+) -> Result<Json<serde_json::Value>, MyError> {
+    // The answer is the same either way, so it cannot be used to probe for accounts. Like
+    // a password reset link, the new link goes out in the background and a failed send is
+    // only logged.
+    if let Some(user) = get_user_by_email(&app_state.pool, payload.email.trim().to_lowercase()).await?
+        && user.email_verified_at.is_none()
+    {
+        let pool = app_state.pool.clone();
+        let mailer = app_state.mailer.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = send_verification_email(&pool, mailer.as_ref(), user.id, &user.email).await {
+                tracing::warn!("Could not send the verification email to user {}: {}", user.id, err);
+            }
+        });
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "If the account exists and is not verified yet, a new link was sent",
+    })))
+}
@@ -0,0 +1,33 @@
+use axum::extract::Json;
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::{
+    auth::{keys::key_ring, oidc::openid_configuration},
+    errors::my_error::MyError,
+    models::oidc::OpenIdConfiguration,
+};
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "Public keys used to verify the tokens issued by this service"),
+    ),
+    tag = "auth"
+)]
+pub async fn jwks_handler() -> Json<JwkSet> {
+    Json(key_ring().jwks())
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    responses(
+        (status = 200, description = "OpenID Connect discovery document", body = OpenIdConfiguration),
+        (status = 404, description = "OpenID Connect is off: the active signing key is HS256"),
+    ),
+    tag = "oauth"
+)]
+pub async fn openid_configuration_handler() -> Result<Json<OpenIdConfiguration>, MyError> {
+    openid_configuration(&key_ring()).map(Json).ok_or(MyError::NotFound)
+}
@@ -1,10 +1,13 @@
 use rust_auth_service::{
+    auth::keys::{load_key_ring, spawn_key_ring_refresh},
     config::{init_pool, init_redis},
     docs::ApiDoc,
     middleware::cors::cors,
     models::app::AppState,
     routes::routes::routes,
+    services::mailer::mailer_from_env,
 };
+use std::net::SocketAddr;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -27,7 +30,12 @@ async fn main() {
     tracing::info!("Pool initialized");
     tracing::info!("Redis initialized");
 
-    let app_state = AppState { pool, redis };
+    load_key_ring(&pool).await.expect("Failed to load signing keys");
+    spawn_key_ring_refresh(pool.clone());
+
+    tracing::info!("Signing keys loaded");
+
+    let app_state = AppState { pool, redis, mailer: mailer_from_env() };
 
     let app = routes(&app_state)
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
@@ -40,5 +48,8 @@ async fn main() {
 
     tracing::info!("Listening on http://localhost:4000");
 
-    axum::serve(listener, app).await.unwrap();
+    // The peer address is what lockouts and rate limits key on, see services::client_info
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 }
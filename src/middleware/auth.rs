@@ -1,6 +1,6 @@
 use axum::{
     body::Body,
-    extract::{FromRequestParts, State},
+    extract::{FromRequestParts, Path, State},
     http::request::{Parts, Request},
     middleware::Next,
     response::Response,
@@ -10,9 +10,12 @@ use headers::{Authorization, HeaderMapExt, authorization::Bearer};
 use tracing::{info, instrument};
 use std::{future::Future, pin::Pin};
 use crate::{
-    auth::auth::{decode_access_token, validate_jwt},
+    auth::{
+        account_status::current_status,
+        auth::{decode_access_token, validate_jwt},
+    },
     errors::my_error::MyError,
-    models::{app::AppState, auth::Claims},
+    models::{app::AppState, auth::Claims, oidc::has_scope},
 };
 
 // Middleware Extractor Pattern Axum (you can se how to use it in the logout_handler that is commented)
@@ -60,7 +63,15 @@ pub async fn auth_middleware(
 
     let token = auth_header.token();
 
-    let claims = validate_jwt(&mut redis_conn, token).await?;
+    let claims = validate_jwt(&app_state.pool, &mut redis_conn, token).await?;
+
+    // Machine tokens belong to a client, which has no account status
+    if !claims.machine {
+        current_status(&app_state.pool, &mut redis_conn, claims.sub)
+            .await?
+            .ok_or(MyError::Unauthorized)?
+            .check_sign_in()?;
+    }
 
     request.extensions_mut().insert(claims);
 
@@ -70,15 +81,131 @@ pub async fn auth_middleware(
     Ok(next.run(request).await)
 }
 
+// Machine tokens authenticate a client, not a user: keep them off routes that act on
+// the caller's own account or sessions
+pub async fn require_user(request: Request<Body>, next: Next) -> Result<Response, MyError> {
+    let claims = request.extensions().get::<Claims>().ok_or(MyError::Unauthorized)?;
+
+    if claims.machine {
+        return Err(MyError::Unauthorized);
+    }
+
+    Ok(next.run(request).await)
+}
+
+// The reverse: routes for services acting on their own, such as introspection, are
+// closed to users and to clients acting for one
+pub async fn require_client(request: Request<Body>, next: Next) -> Result<Response, MyError> {
+    let claims = request.extensions().get::<Claims>().ok_or(MyError::Unauthorized)?;
+
+    if !claims.machine {
+        return Err(MyError::Unauthorized);
+    }
+
+    Ok(next.run(request).await)
+}
+
+// Impersonation lets an admin look around as the user, not act irreversibly for them:
+// keep it off routes that change credentials, the account or its sessions
+pub async fn deny_impersonation(request: Request<Body>, next: Next) -> Result<Response, MyError> {
+    let claims = request.extensions().get::<Claims>().ok_or(MyError::Unauthorized)?;
+
+    if claims.is_impersonated() {
+        return Err(MyError::Unauthorized);
+    }
+
+    Ok(next.run(request).await)
+}
+
+type MiddlewareFuture = Pin<Box<dyn Future<Output = Result<Response<Body>, MyError>> + Send>>;
+
+// Tokens from a direct login without a scope are unrestricted; any token issued to a
+// client, or narrowed at login, must carry `required_scope`
+pub fn require_scope(
+    required_scope: &str,
+) -> impl Clone + Send + Sync + 'static + Fn(Request<Body>, Next) -> MiddlewareFuture
+{
+    let required_scope = required_scope.to_string();
+
+    move | req: Request<Body>, next: Next| {
+        let required_scope = required_scope.clone();
+
+        Box::pin(async move {
+            let claims = req.extensions().get::<Claims>().cloned().ok_or(MyError::Unauthorized)?;
+
+            let has_scope = match claims.scope.as_deref() {
+                Some(scope) => has_scope(scope, &required_scope),
+                None => claims.client_id.is_none(),
+            };
+
+            if !has_scope {
+                return Err(MyError::Unauthorized);
+            }
+
+            Ok(next.run(req).await)
+        })
+    }
+}
+
+// Sensitive operations need a user who entered their password within `max_age`
+// seconds, however fresh the token itself is: refreshing does not count
+pub fn require_recent_auth(
+    max_age: i64,
+) -> impl Clone + Send + Sync + 'static + Fn(Request<Body>, Next) -> MiddlewareFuture
+{
+    move | req: Request<Body>, next: Next| {
+        Box::pin(async move {
+            let claims = req.extensions().get::<Claims>().cloned().ok_or(MyError::Unauthorized)?;
+
+            if !claims.authenticated_within(max_age, chrono::Utc::now().timestamp()) {
+                return Err(MyError::StepUpRequired(max_age));
+            }
+
+            Ok(next.run(req).await)
+        })
+    }
+}
+
+// Users may act on their own account (the `{user_id}` path parameter); someone
+// else's needs one of `roles`
+pub fn require_owner_or_role(
+    roles: Vec<String>,
+) -> impl Clone + Send + Sync + 'static + Fn(Path<uuid::Uuid>, Request<Body>, Next) -> MiddlewareFuture
+{
+    move |Path(user_id): Path<uuid::Uuid>, req: Request<Body>, next: Next| {
+        let roles = roles.clone();
+
+        Box::pin(async move {
+            let claims = req.extensions().get::<Claims>().cloned().ok_or(MyError::Unauthorized)?;
+
+            if !can_access_user(&claims, user_id, &roles) {
+                return Err(MyError::Forbidden);
+            }
+
+            Ok(next.run(req).await)
+        })
+    }
+}
+
+pub fn can_access_user(claims: &Claims, user_id: uuid::Uuid, roles: &[String]) -> bool {
+    claims.sub == user_id || claims.roles.iter().any(|role| roles.contains(role))
+}
+
 pub fn require_role(
     required_roles: Vec<String>,
-) -> impl Clone + Send + Sync + 'static + Fn(Request<Body>, Next) -> Pin<Box<dyn Future<Output = Result<Response<Body>, MyError>> + Send>>
+) -> impl Clone + Send + Sync + 'static + Fn(Request<Body>, Next) -> MiddlewareFuture
 {
     move | req: Request<Body>, next: Next| {
         let required_roles = required_roles.clone();
 
         Box::pin(async move {
             let claims = req.extensions().get::<Claims>().cloned().ok_or(MyError::Unauthorized)?;
+
+            // The roles of an impersonated user never grant the admin anything extra
+            if claims.is_impersonated() {
+                return Err(MyError::Unauthorized);
+            }
+
             let has_role = claims.roles.iter().any(|role| required_roles.contains(role));
 
             if !has_role {
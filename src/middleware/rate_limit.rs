@@ -1,8 +1,8 @@
 use axum::{body::Body, extract::State, http::Request, middleware::Next, response::Response};
-use redis::AsyncCommands;
-use std::net::IpAddr;
+use redis::{AsyncCommands, aio::ConnectionManager};
+use std::sync::LazyLock;
 
-use crate::{errors::my_error::MyError, models::app::AppState};
+use crate::{errors::my_error::MyError, models::app::AppState, services::client_info::request_client_ip};
 
 pub async fn rate_limit_middleware(
     State(app_state): State<AppState>,
@@ -10,17 +10,9 @@ pub async fn rate_limit_middleware(
     next: Next,
 ) -> Result<Response, MyError> {
     let mut redis_conn = app_state.redis;
-    let headers = request.headers();
-
-    let client_ip = headers
-        .get("x-forwarded-for")
-        .and_then(|ip| ip.to_str().ok())
-        .and_then(|ip| ip.split(',').next().map(|s| s.trim()))
-        .filter(|ip| ip.parse::<IpAddr>().is_ok())
-        .or_else(|| headers.get("x-real-ip").and_then(|ip| ip.to_str().ok()).filter(|ip| ip.parse::<IpAddr>().is_ok()))
-        .or_else(|| headers.get("cf-connecting-ip").and_then(|ip| ip.to_str().ok()).filter(|ip| ip.parse::<IpAddr>().is_ok()))
-        .unwrap_or("unknown")
-        .to_string();
+    let client_ip = request_client_ip(request.extensions(), request.headers())
+        .0
+        .unwrap_or_else(|| "unknown".to_string());
 
     println!("Client IP: {}", &client_ip);
 
@@ -28,17 +20,45 @@ pub async fn rate_limit_middleware(
 
     println!("Rate limit key: {}", key);
 
-    let limit: i32 = redis_conn.incr(&key, 1).await.unwrap();
+    check_rate_limit(&mut redis_conn, &key, IP_RATE_LIMIT).await?;
 
-    if limit == 1 {
-        redis_conn.expire::<_, ()>(&key, 60).await.unwrap();
+    Ok(next.run(request).await)
+}
+
+// Requests per minute and client IP on the public endpoints
+pub const IP_RATE_LIMIT: u64 = 10;
+
+// OAUTH_CLIENT_RATE_LIMIT: token requests per minute an authenticated client may make.
+// Services behind one gateway share its IP, so clients are counted on their own.
+static CLIENT_RATE_LIMIT: LazyLock<u64> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+
+    std::env::var("OAUTH_CLIENT_RATE_LIMIT")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(600)
+});
+
+pub fn client_rate_limit() -> u64 {
+    *CLIENT_RATE_LIMIT
+}
+
+// Counts one request under `key` for the current minute and refuses it past `limit`
+pub async fn check_rate_limit(
+    redis: &mut ConnectionManager,
+    key: &str,
+    limit: u64,
+) -> Result<(), MyError> {
+    let count: u64 = redis.incr(key, 1).await.map_err(|_| MyError::Internal)?;
+
+    if count == 1 {
+        let _: () = redis.expire(key, 60).await.map_err(|_| MyError::Internal)?;
     }
 
-    if limit > 10 {
+    if count > limit {
         return Err(MyError::TooManyRequests);
     }
 
-    println!("Rate limit: {}", limit);
-
-    Ok(next.run(request).await)
+    Ok(())
 }
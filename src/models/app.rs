@@ -1,8 +1,13 @@
+use std::sync::Arc;
+
 use redis::aio::ConnectionManager;
 use sqlx::{ Pool, Postgres };
 
+use crate::services::mailer::Mailer;
+
 #[derive(Clone)]
 pub struct AppState {
     pub pool: Pool<Postgres>,
     pub redis: ConnectionManager,
+    pub mailer: Arc<dyn Mailer>,
 }
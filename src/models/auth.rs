@@ -2,10 +2,29 @@ use serde::{Deserialize, Serialize};
 use sqlx::Type;
 use utoipa::ToSchema;
 
+use crate::{auth::auth::REFRESH_TOKEN_TTL, services::token_hash::hash_token};
+
 #[derive(Deserialize, ToSchema)]
 pub struct Login {
     pub email: String,
     pub password: String,
+    pub audience: Option<String>, // resource server the access token is for, defaults to this one
+    pub scope: Option<String>, // narrows the tokens, unrestricted when omitted
+}
+
+pub const AMR_PASSWORD: &str = "pwd";
+pub const ACR_SESSION: &str = "session"; // authenticated at login, possibly refreshed since
+pub const ACR_ELEVATED: &str = "elevated"; // issued right after re-authentication
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReauthenticateInput {
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ElevatedTokenResponse {
+    pub access_token: String,
+    pub expires_in: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
@@ -23,6 +42,8 @@ pub enum TokenType {
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct Claims {
+    pub iss: String,
+    pub aud: String, // resource server the token is addressed to
     pub sub: uuid::Uuid, // user_id
     pub email: String,
     pub roles: Vec<String>, // ["Admin", "User"]
@@ -30,23 +51,65 @@ pub struct Claims {
     pub iat: usize, //
     pub exp: usize, //
     pub token_type: TokenType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<uuid::Uuid>, // session the token belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>, // OAuth client the token was issued to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>, // space separated, as in RFC 6749
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub machine: bool, // issued to a client acting on its own behalf, `sub` is then the client
+    #[serde(default)]
+    pub epoch: i64, // user's token epoch at issue time, stale once it is bumped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>, // set while an admin impersonates `sub`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>, // when the user last entered their credentials
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<String>, // how they did, RFC 8176 values such as "pwd"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acr: Option<String>, // ACR_SESSION or ACR_ELEVATED
+}
+
+impl Claims {
+    pub fn is_impersonated(&self) -> bool {
+        self.act.is_some()
+    }
+
+    // Whether the user entered their credentials at most `max_age` seconds before `now`
+    pub fn authenticated_within(&self, max_age: i64, now: i64) -> bool {
+        self.auth_time.is_some_and(|auth_time| now - auth_time as i64 <= max_age)
+    }
+}
+
+// RFC 8693 §4.1 actor: who is really behind a token issued for someone else
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+pub struct Actor {
+    pub sub: uuid::Uuid,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct RefreshToken {
     pub id: uuid::Uuid,
-    pub token: String,
+    pub token_hash: String, // see services::token_hash, the token itself is never stored
     pub user_id: uuid::Uuid,
+    pub family_id: uuid::Uuid, // every token issued from the same login
     pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub consumed_at: Option<chrono::DateTime<chrono::Utc>>, // set once exchanged for a new pair
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl RefreshToken {
-    pub fn new(token: String, user_id: uuid::Uuid) -> Self {
+    pub fn new(token: &str, user_id: uuid::Uuid, family_id: uuid::Uuid) -> Self {
         RefreshToken {
             id: uuid::Uuid::new_v4(),
-            token,
+            token_hash: hash_token(token),
             user_id,
-            expires_at: chrono::Utc::now() + chrono::Duration::days(7),
+            family_id,
+            // Same lifetime as the token's own `exp`
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(REFRESH_TOKEN_TTL),
+            consumed_at: None,
+            revoked_at: None,
         }
     }
 }
@@ -2,3 +2,11 @@ pub mod auth;
 pub mod role;
 pub mod user;
 pub mod app;
+pub mod oauth;
+pub mod oauth_client;
+pub mod oidc;
+pub mod one_time_token;
+pub mod scope;
+pub mod security_event;
+pub mod session;
+pub mod signing_key;
@@ -0,0 +1,166 @@
+use serde::{ Deserialize, Serialize };
+use utoipa::{ IntoParams, ToSchema };
+
+use crate::{ auth::auth::ACCESS_TOKEN_TTL, models::auth::{ Actor, Claims } };
+
+// The client's authorization request (RFC 6749 §4.1.1), sent by the browser to the login
+// page and carried in that page's form
+#[derive(Debug, Clone, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorizationParams {
+    pub response_type: String, // only "code"
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>, // only "S256"
+    pub scope: Option<String>,
+    pub nonce: Option<String>,
+    pub audience: Option<String>, // resource server the access tokens are for
+}
+
+// Posted by the login page: the client's request plus the user's credentials and answer
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AuthorizeRequest {
+    #[serde(flatten)]
+    pub params: AuthorizationParams,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub password: String,
+    pub decision: Option<String>, // "deny" when the user declines; allowing is the default
+}
+
+pub const DENY_DECISION: &str = "deny";
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct TokenRequest {
+    pub grant_type: String, // "authorization_code", "refresh_token", "client_credentials" or token exchange
+    pub client_id: Option<String>, // may come through HTTP Basic instead
+    pub client_secret: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub audience: Option<String>, // client_credentials only, set at authorization otherwise
+    pub subject_token: Option<String>, // token exchange only, from here on
+    pub subject_token_type: Option<String>, // ACCESS_TOKEN_TYPE or USER_ID_TOKEN_TYPE
+    pub actor_token: Option<String>, // the admin's access token
+    pub actor_token_type: Option<String>, // only ACCESS_TOKEN_TYPE
+    pub requested_token_type: Option<String>, // only ACCESS_TOKEN_TYPE
+}
+
+// RFC 8693 §3 token types. The user id type names the subject of an impersonation,
+// since the admin holds no token of theirs.
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+pub const USER_ID_TOKEN_TYPE: &str = "urn:rust-auth-service:params:oauth:token-type:user_id";
+
+// RFC 6749 §5.1 response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>, // when the openid scope was granted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued_token_type: Option<String>, // token exchange only (RFC 8693 §2.2.1)
+}
+
+impl OAuthTokenResponse {
+    pub fn bearer(access_token: String, refresh_token: Option<String>, scope: Option<String>) -> Self {
+        OAuthTokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_TTL,
+            refresh_token,
+            scope,
+            id_token: None,
+            issued_token_type: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct IntrospectionRequest {
+    pub token: String,
+    pub token_type_hint: Option<String>, // "access_token" or "refresh_token"
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct RevocationRequest {
+    pub token: String,
+    pub token_type_hint: Option<String>, // "access_token" or "refresh_token"
+    pub client_id: Option<String>, // required for a client's tokens, may come through HTTP Basic
+    pub client_secret: Option<String>,
+}
+
+// RFC 7662 response; every field but `active` is omitted for inactive tokens
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acr: Option<String>,
+}
+
+impl IntrospectionResponse {
+    pub fn inactive() -> Self {
+        IntrospectionResponse::default()
+    }
+
+    pub fn active(claims: Claims) -> Self {
+        IntrospectionResponse {
+            active: true,
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            sub: Some(claims.sub),
+            // Machine tokens have no user behind them
+            username: (!claims.machine).then_some(claims.email),
+            roles: Some(claims.roles),
+            // RFC 7662 §2.2: the token type of RFC 6749 §7.1, not access or refresh
+            token_type: Some("Bearer".to_string()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            jti: Some(claims.jti),
+            sid: claims.sid,
+            client_id: claims.client_id,
+            scope: claims.scope,
+            act: claims.act,
+            auth_time: claims.auth_time,
+            acr: claims.acr,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{ DateTime, Duration, Utc };
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+use crate::{
+    auth::oidc::{ is_known_audience, resource_audience },
+    errors::my_error::MyError,
+    models::oidc::OIDC_SCOPES,
+    services::token_hash::{ generate_opaque_token, hash_token },
+};
+
+pub const AUTHORIZATION_CODE_GRANT: &str = "authorization_code";
+pub const REFRESH_TOKEN_GRANT: &str = "refresh_token";
+pub const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
+pub const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+pub const SUPPORTED_GRANT_TYPES: [&str; 4] =
+    [AUTHORIZATION_CODE_GRANT, REFRESH_TOKEN_GRANT, CLIENT_CREDENTIALS_GRANT, TOKEN_EXCHANGE_GRANT];
+pub const DEFAULT_GRANT_TYPES: [&str; 2] = [AUTHORIZATION_CODE_GRANT, REFRESH_TOKEN_GRANT];
+
+pub const AUTHORIZATION_CODE_TTL: i64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>, // what the client may request for itself with client_credentials
+    pub audiences: Vec<String>, // resource servers it may request tokens for, besides this one
+    #[serde(skip)]
+    pub client_secret_hash: Option<String>, // none for public clients
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreateOAuthClientInput {
+    pub name: String,
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Option<Vec<String>>, // defaults to authorization_code and refresh_token
+    #[serde(default)]
+    pub confidential: bool, // issues a client secret, required for client_credentials
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub audiences: Vec<String>, // each must be listed in JWT_EXTERNAL_AUDIENCES
+}
+
+// Returned once at registration, the secret cannot be recovered afterwards
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreatedOAuthClient {
+    #[serde(flatten)]
+    pub client: OAuthClient,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+// RFC 6749 §3.3: scope tokens are printable ASCII without spaces, quotes or backslashes
+pub fn is_valid_scope_token(scope: &str) -> bool {
+    !scope.is_empty() && scope.chars().all(|c| matches!(c, '!' | '#'..='[' | ']'..='~'))
+}
+
+impl OAuthClient {
+    pub fn register(input: CreateOAuthClientInput) -> Result<CreatedOAuthClient, MyError> {
+        if input.name.trim().is_empty() {
+            return Err(MyError::BadRequest);
+        }
+
+        // RFC 6749 §3.1.2: absolute URIs without a fragment, compared verbatim later on
+        for redirect_uri in &input.redirect_uris {
+            let url = url::Url::parse(redirect_uri).map_err(|_| MyError::BadRequest)?;
+
+            if url.fragment().is_some() {
+                return Err(MyError::BadRequest);
+            }
+        }
+
+        let grant_types = input
+            .grant_types
+            .unwrap_or_else(|| DEFAULT_GRANT_TYPES.iter().map(|grant| grant.to_string()).collect());
+
+        if grant_types.is_empty() || grant_types.iter().any(|grant| !SUPPORTED_GRANT_TYPES.contains(&grant.as_str())) {
+            return Err(MyError::BadRequest);
+        }
+
+        if grant_types.iter().any(|grant| grant == AUTHORIZATION_CODE_GRANT) && input.redirect_uris.is_empty() {
+            return Err(MyError::BadRequest);
+        }
+
+        // Only a client that can keep a secret may obtain tokens without a user's consent
+        let unattended = [CLIENT_CREDENTIALS_GRANT, TOKEN_EXCHANGE_GRANT];
+        if grant_types.iter().any(|grant| unattended.contains(&grant.as_str())) && !input.confidential {
+            return Err(MyError::BadRequest);
+        }
+
+        if input.scopes.iter().any(|scope| !is_valid_scope_token(scope)) {
+            return Err(MyError::BadRequest);
+        }
+
+        if input.audiences.iter().any(|audience| !is_known_audience(audience)) {
+            return Err(MyError::BadRequest);
+        }
+
+        let client_secret = input.confidential.then(generate_opaque_token);
+
+        let client = OAuthClient {
+            client_id: uuid::Uuid::new_v4().simple().to_string(),
+            name: input.name.trim().to_string(),
+            redirect_uris: input.redirect_uris,
+            grant_types,
+            scopes: input.scopes,
+            audiences: input.audiences,
+            client_secret_hash: client_secret.as_deref().map(hash_token),
+            created_at: Utc::now(),
+        };
+
+        Ok(CreatedOAuthClient { client, client_secret })
+    }
+
+    pub fn is_confidential(&self) -> bool {
+        self.client_secret_hash.is_some()
+    }
+
+    pub fn verify_secret(&self, client_secret: &str) -> bool {
+        self.client_secret_hash.as_deref() == Some(hash_token(client_secret).as_str())
+    }
+
+    // Audience of the client's tokens: this service unless it asks for one it was assigned
+    pub fn token_audience(&self, requested: Option<&str>) -> Result<Option<String>, MyError> {
+        match requested {
+            None => Ok(None),
+            Some(audience) if audience == resource_audience() => Ok(None),
+            Some(audience) if self.audiences.iter().any(|allowed| allowed == audience) => {
+                Ok(Some(audience.to_string()))
+            }
+            Some(_) => Err(MyError::OAuth("invalid_target")),
+        }
+    }
+
+    // Scopes a user may grant the client: the OpenID Connect ones and those assigned to it
+    pub fn authorize_scopes(&self, requested: &str) -> Result<String, MyError> {
+        let scopes: Vec<&str> = requested.split(' ').filter(|scope| !scope.is_empty()).collect();
+
+        let allowed = |scope: &&str| {
+            OIDC_SCOPES.contains(scope) || self.scopes.iter().any(|allowed| allowed == scope)
+        };
+
+        if !scopes.iter().all(allowed) {
+            return Err(MyError::OAuth("invalid_scope"));
+        }
+
+        Ok(scopes.join(" "))
+    }
+
+    // Requested scopes must all be assigned to the client; none requested means all of them
+    pub fn grant_scopes(&self, requested: Option<&str>) -> Result<String, MyError> {
+        let Some(requested) = requested else {
+            return Ok(self.scopes.join(" "));
+        };
+
+        let scopes: Vec<&str> = requested.split(' ').filter(|scope| !scope.is_empty()).collect();
+
+        if scopes.iter().any(|scope| !self.scopes.iter().any(|allowed| allowed == scope)) {
+            return Err(MyError::OAuth("invalid_scope"));
+        }
+
+        Ok(scopes.join(" "))
+    }
+
+    pub fn allows_grant(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|grant| grant == grant_type)
+    }
+
+    // Exact match only, so an attacker cannot smuggle a code to a lookalike URI
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AuthorizationCode {
+    pub code_hash: String,
+    pub client_id: String,
+    pub user_id: uuid::Uuid,
+    pub redirect_uri: String,
+    pub code_challenge: String,
+    pub scope: Option<String>,
+    pub nonce: Option<String>, // echoed in the ID token
+    pub audience: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+    pub session_id: Option<uuid::Uuid>, // set once the code has been exchanged
+    pub created_at: DateTime<Utc>, // when the user authenticated
+}
+
+impl AuthorizationCode {
+    pub fn new(
+        code: &str,
+        client_id: String,
+        user_id: uuid::Uuid,
+        redirect_uri: String,
+        code_challenge: String,
+    ) -> Self {
+        let now = Utc::now();
+
+        AuthorizationCode {
+            code_hash: hash_token(code),
+            client_id,
+            user_id,
+            redirect_uri,
+            code_challenge,
+            scope: None,
+            nonce: None,
+            audience: None,
+            expires_at: now + Duration::seconds(AUTHORIZATION_CODE_TTL),
+            consumed_at: None,
+            session_id: None,
+            created_at: now,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+pub const OPENID_SCOPE: &str = "openid";
+pub const PROFILE_SCOPE: &str = "profile";
+pub const EMAIL_SCOPE: &str = "email";
+pub const OIDC_SCOPES: [&str; 3] = [OPENID_SCOPE, PROFILE_SCOPE, EMAIL_SCOPE];
+
+pub fn has_scope(scope: &str, wanted: &str) -> bool {
+    scope.split(' ').any(|granted| granted == wanted)
+}
+
+// OpenID Connect Core §2; `name` and `email` follow the profile and email scopes
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: uuid::Uuid,
+    pub aud: String, // the client the ID token is meant for
+    pub exp: usize,
+    pub iat: usize,
+    pub auth_time: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserInfo {
+    pub sub: uuid::Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+// OpenID Connect Discovery §3
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub revocation_endpoint: String,
+    pub introspection_endpoint: String,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub scopes_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+}
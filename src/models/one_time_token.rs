@@ -0,0 +1,48 @@
+use chrono::{ DateTime, Duration, Utc };
+
+use crate::services::token_hash::hash_token;
+
+pub const EMAIL_VERIFICATION_TTL: i64 = 60 * 60 * 24;
+pub const PASSWORD_RESET_TTL: i64 = 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    EmailVerification,
+    PasswordReset,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::EmailVerification => "email_verification",
+            TokenPurpose::PasswordReset => "password_reset",
+        }
+    }
+}
+
+// A secret mailed to the user; only its keyed hash is stored, so the hash doubles as
+// the server's signature and a leaked table yields nothing redeemable
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OneTimeToken {
+    pub token_hash: String,
+    pub user_id: uuid::Uuid,
+    pub purpose: String,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OneTimeToken {
+    pub fn new(token: &str, user_id: uuid::Uuid, purpose: TokenPurpose, ttl: i64) -> Self {
+        let now = Utc::now();
+
+        OneTimeToken {
+            token_hash: hash_token(token),
+            user_id,
+            purpose: purpose.as_str().to_string(),
+            expires_at: now + Duration::seconds(ttl),
+            consumed_at: None,
+            created_at: now,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::models::oidc::OIDC_SCOPES;
+
+// Scopes guarding this service's own API, see middleware::auth::require_scope
+pub const USERS_READ_SCOPE: &str = "users:read";
+pub const USERS_WRITE_SCOPE: &str = "users:write";
+pub const SESSIONS_READ_SCOPE: &str = "sessions:read";
+pub const SESSIONS_WRITE_SCOPE: &str = "sessions:write";
+pub const ADMIN_SCOPE: &str = "admin";
+// Lets a client introspect tokens. Assigned to clients at registration, never derived from roles.
+pub const INTROSPECT_SCOPE: &str = "tokens:introspect";
+pub const API_SCOPES: [&str; 5] = [
+    USERS_READ_SCOPE,
+    USERS_WRITE_SCOPE,
+    SESSIONS_READ_SCOPE,
+    SESSIONS_WRITE_SCOPE,
+    ADMIN_SCOPE,
+];
+
+const USER_ROLE_SCOPES: [&str; 4] = [
+    USERS_READ_SCOPE,
+    USERS_WRITE_SCOPE,
+    SESSIONS_READ_SCOPE,
+    SESSIONS_WRITE_SCOPE,
+];
+
+// API scopes a user holding `roles` may have in a token, including one delegated to a client
+pub fn role_scopes(roles: &[String]) -> Vec<&'static str> {
+    let mut scopes = Vec::new();
+
+    for role in roles {
+        let granted: &[&'static str] = match role.as_str() {
+            "Admin" => &API_SCOPES,
+            "User" => &USER_ROLE_SCOPES,
+            _ => &[],
+        };
+
+        for scope in granted {
+            if !scopes.contains(scope) {
+                scopes.push(*scope);
+            }
+        }
+    }
+
+    scopes
+}
+
+// Requested scopes the user may have: the OpenID Connect ones and those of their roles.
+// Others are dropped rather than refused, RFC 6749 §3.3 lets the server grant less.
+pub fn user_scopes(requested: &str, roles: &[String]) -> String {
+    let allowed = role_scopes(roles);
+
+    requested
+        .split(' ')
+        .filter(|scope| OIDC_SCOPES.contains(scope) || allowed.contains(scope))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Drops the API scopes the user's roles do not allow. Scopes for other resource
+// servers are theirs to enforce and were already checked against the client.
+pub fn restrict_to_roles(scope: &str, roles: &[String]) -> String {
+    let allowed = role_scopes(roles);
+
+    scope
+        .split(' ')
+        .filter(|scope| !scope.is_empty())
+        .filter(|scope| !API_SCOPES.contains(scope) || allowed.contains(scope))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
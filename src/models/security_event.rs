@@ -0,0 +1,54 @@
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventType {
+    RefreshTokenReuse,
+    AuthorizationCodeReuse,
+    Impersonation,
+    PasswordReset,
+    PasswordChange,
+    StatusChange,
+    AccountLocked,
+}
+
+impl SecurityEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityEventType::RefreshTokenReuse => "refresh_token_reuse",
+            SecurityEventType::AuthorizationCodeReuse => "authorization_code_reuse",
+            SecurityEventType::Impersonation => "impersonation",
+            SecurityEventType::PasswordReset => "password_reset",
+            SecurityEventType::PasswordChange => "password_change",
+            SecurityEventType::StatusChange => "status_change",
+            SecurityEventType::AccountLocked => "account_locked",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct SecurityEvent {
+    pub id: uuid::Uuid,
+    pub user_id: Option<uuid::Uuid>,
+    pub event_type: String,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SecurityEvent {
+    pub fn new(
+        user_id: Option<uuid::Uuid>,
+        event_type: SecurityEventType,
+        details: serde_json::Value,
+    ) -> Self {
+        SecurityEvent {
+            id: uuid::Uuid::new_v4(),
+            user_id,
+            event_type: event_type.as_str().to_string(),
+            details,
+            created_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
+
+use crate::services::client_info::ClientInfo;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Session {
+    pub id: uuid::Uuid, // also the family id of the session's refresh tokens
+    pub user_id: uuid::Uuid,
+    pub device_name: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub client_id: Option<String>, // OAuth client the session was granted to, none for direct logins
+    pub scope: Option<String>, // granted to the client, none for direct logins
+    pub audience: Option<String>, // of the session's access tokens, none for this service
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionOutput {
+    pub id: uuid::Uuid,
+    pub device_name: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub client_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub current: bool, // the session the request was made from
+}
+
+impl Session {
+    pub fn new(user_id: uuid::Uuid, client: ClientInfo) -> Self {
+        let now = Utc::now();
+
+        Session {
+            id: uuid::Uuid::new_v4(),
+            user_id,
+            device_name: client.device_name,
+            user_agent: client.user_agent,
+            ip_address: client.ip_address,
+            client_id: None,
+            scope: None,
+            audience: None,
+            created_at: now,
+            last_used_at: now,
+            revoked_at: None,
+        }
+    }
+
+    pub fn to_output(&self, current_session: Option<uuid::Uuid>) -> SessionOutput {
+        SessionOutput {
+            id: self.id,
+            device_name: self.device_name.clone(),
+            user_agent: self.user_agent.clone(),
+            ip_address: self.ip_address.clone(),
+            client_id: self.client_id.clone(),
+            created_at: self.created_at,
+            last_used_at: self.last_used_at,
+            current: current_session == Some(self.id),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::{ DateTime, Utc };
+use serde::{ Deserialize, Serialize };
+use sqlx::Type;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type, ToSchema)]
+#[sqlx(type_name = "key_state", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum KeyState {
+    Active,     // signs new tokens and verifies
+    VerifyOnly, // only verifies tokens issued before the last rotation
+    Retired,    // no longer trusted
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SigningKeyRecord {
+    pub kid: String,
+    pub algorithm: String,
+    pub key_material: String, // PEM for asymmetric keys, base64 secret for HS256; encrypted in the database
+    pub state: KeyState,
+    pub created_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct SigningKeyOutput {
+    pub kid: String,
+    pub algorithm: String,
+    pub state: KeyState,
+    pub created_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct RotateKeyInput {
+    pub algorithm: Option<String>,       // defaults to the algorithm of the active key
+    pub private_key_pem: Option<String>, // required for RS256, generated otherwise
+}
+
+impl SigningKeyRecord {
+    pub fn new(algorithm: String, key_material: String) -> Self {
+        SigningKeyRecord {
+            kid: uuid::Uuid::new_v4().to_string(),
+            algorithm,
+            key_material,
+            state: KeyState::Active,
+            created_at: Utc::now(),
+            rotated_at: None,
+            retired_at: None,
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use chrono::{ DateTime, Utc };
 use serde::{ Deserialize, Serialize };
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::services::password::hash_password;
+use crate::{errors::my_error::MyError, services::password::hash_password};
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UserRegister {
@@ -20,17 +20,173 @@ pub struct UserOutput {
     pub updated_at: DateTime<Utc>,
 }
 
+// Transitions are listed in UserStatus::can_transition_to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "user_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum UserStatus {
+    Pending,   // signed up, email not verified yet
+    Active,
+    Suspended, // by an admin, until reactivated
+    Locked,    // for the account's protection, until unlocked
+    Deleted,   // closed for good
+}
+
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Pending => "pending",
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Locked => "locked",
+            UserStatus::Deleted => "deleted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(UserStatus::Pending),
+            "active" => Some(UserStatus::Active),
+            "suspended" => Some(UserStatus::Suspended),
+            "locked" => Some(UserStatus::Locked),
+            "deleted" => Some(UserStatus::Deleted),
+            _ => None,
+        }
+    }
+
+    // Pending accounts may sign in unless email verification is required, which
+    // login_handler checks on its own
+    pub fn allows_sign_in(&self) -> bool {
+        matches!(self, UserStatus::Pending | UserStatus::Active)
+    }
+
+    pub fn can_transition_to(&self, next: UserStatus) -> bool {
+        use UserStatus::*;
+
+        matches!(
+            (self, next),
+            (Pending, Active | Suspended | Deleted)
+                | (Active, Suspended | Locked | Deleted)
+                | (Suspended, Active | Deleted)
+                | (Locked, Active | Deleted)
+        )
+    }
+
+    // The error for a token or login of an account in this status, if any
+    pub fn check_sign_in(&self) -> Result<(), MyError> {
+        if self.allows_sign_in() {
+            return Ok(());
+        }
+
+        Err(MyError::LoginError(format!("Account {}", self.as_str())))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct UserWithRoles {
     pub id: uuid::Uuid,
     pub name: String,
     pub email: String,
+    pub status: UserStatus,
     pub roles: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserListQuery {
+    pub page: Option<i64>, // from 1
+    pub per_page: Option<i64>, // up to MAX_PAGE_SIZE
+    pub email: Option<String>, // case-insensitive substring
+    pub role: Option<String>,
+    pub status: Option<UserStatus>,
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    pub sort: Option<String>, // "created_at", "email" or "name", prefixed with "-" for descending
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserSort {
+    CreatedAt,
+    Email,
+    Name,
+}
+
+// A validated UserListQuery
+#[derive(Debug, Clone)]
+pub struct UserFilter {
+    pub email: Option<String>,
+    pub role: Option<String>,
+    pub status: Option<UserStatus>,
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    pub sort: UserSort,
+    pub descending: bool,
+    pub page: i64,
+    pub per_page: i64,
+}
 
+impl UserListQuery {
+    pub fn into_filter(self) -> Result<UserFilter, MyError> {
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        if page < 1 || !(1..=MAX_PAGE_SIZE).contains(&per_page) {
+            return Err(MyError::BadRequest);
+        }
+
+        if let (Some(from), Some(to)) = (self.created_from, self.created_to)
+            && from > to
+        {
+            return Err(MyError::BadRequest);
+        }
+
+        // Newest first unless asked otherwise
+        let sort = self.sort.as_deref().unwrap_or("-created_at");
+        let (descending, field) = match sort.strip_prefix('-') {
+            Some(field) => (true, field),
+            None => (false, sort),
+        };
+
+        let sort = match field {
+            "created_at" => UserSort::CreatedAt,
+            "email" => UserSort::Email,
+            "name" => UserSort::Name,
+            _ => return Err(MyError::BadRequest),
+        };
+
+        Ok(UserFilter {
+            email: self.email.map(|email| email.trim().to_lowercase()).filter(|email| !email.is_empty()),
+            role: self.role,
+            status: self.status,
+            created_from: self.created_from,
+            created_to: self.created_to,
+            sort,
+            descending,
+            page,
+            per_page,
+        })
+    }
+}
+
+impl UserFilter {
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserPage {
+    pub items: Vec<UserWithRoles>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64, // users matching the filters, across all pages
+    pub total_pages: i64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
@@ -38,22 +194,58 @@ pub struct User {
     pub name: String,
     pub email: String,
     pub password: String,
+    pub email_verified_at: Option<DateTime<Utc>>, // none until the emailed link is followed
+    pub status: UserStatus,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct VerifyEmailInput {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ResendVerificationInput {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ForgotPasswordInput {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ResetPasswordInput {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct StatusChangeInput {
+    pub reason: String, // kept on the account and in the audit log
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ChangePasswordInput {
+    pub current_password: String,
+    pub new_password: String,
+}
+
 impl User {
     pub fn new(name: String, email: String, password: String) -> Self {
         let name = name.trim().to_string();
         let email = email.trim().to_string().to_lowercase();
-        let password = password.trim().to_string();
         let now = Utc::now();
 
         Self {
             id: uuid::Uuid::new_v4(),
             name,
             email,
+            // Spaces are part of the password, as typed at login
             password: hash_password(password.as_str()).unwrap(),
+            email_verified_at: None,
+            status: UserStatus::Pending,
             created_at: now,
             updated_at: now,
         }
@@ -1,10 +1,39 @@
 use crate::{
+    auth::auth::STEP_UP_MAX_AGE,
     handlers::{
-        auth::{login_handler, logout_handler, refresh_token_handler},
+        admin::{
+            create_oauth_client_handler, delete_oauth_client_handler, list_keys_handler,
+            list_oauth_clients_handler, list_users_handler, lock_user_handler,
+            reactivate_user_handler, rotate_keys_handler, suspend_user_handler, unlock_user_handler,
+        },
+        auth::{
+            login_handler, logout_all_handler, logout_handler, reauthenticate_handler,
+            refresh_token_handler,
+        },
+        oauth::{
+            authorize_handler, authorize_page_handler, introspect_handler, revoke_handler,
+            token_handler, userinfo_handler,
+        },
+        password::{change_password_handler, forgot_password_handler, reset_password_handler},
+        session::{list_sessions_handler, revoke_other_sessions_handler, revoke_session_handler},
         user::{create_user_handler, delete_user_handler, get_user_handler, update_user_handler},
+        verification::{resend_verification_handler, verify_email_handler},
+        well_known::{jwks_handler, openid_configuration_handler},
+    },
+    middleware::{
+        auth::{
+            auth_middleware, deny_impersonation, require_client, require_owner_or_role,
+            require_recent_auth, require_role, require_scope, require_user,
+        },
+        rate_limit::rate_limit_middleware,
+    },
+    models::{
+        app::AppState,
+        scope::{
+            ADMIN_SCOPE, INTROSPECT_SCOPE, SESSIONS_READ_SCOPE, SESSIONS_WRITE_SCOPE,
+            USERS_READ_SCOPE, USERS_WRITE_SCOPE,
+        },
     },
-    middleware::{auth::{auth_middleware, require_role}, rate_limit::rate_limit_middleware},
-    models::app::AppState,
 };
 use axum::{
     Router,
@@ -18,31 +47,121 @@ pub fn routes(state: &AppState) -> Router<AppState> {
     let public = Router::new()
         .route("/refresh", post(refresh_token_handler))
         .route("/users", post(create_user_handler))
-        .route("/login", post(login_handler))
+        // Failed attempts also count towards a lockout, see auth::lockout
+        .route(
+            "/login",
+            post(login_handler).layer(from_fn_with_state(state.clone(), rate_limit_middleware)),
+        )
+        .route("/verify-email", post(verify_email_handler))
+        // Sends mail, so kept from being used to flood an inbox
+        .route(
+            "/verify-email/resend",
+            post(resend_verification_handler)
+                .layer(from_fn_with_state(state.clone(), rate_limit_middleware)),
+        )
+        .route(
+            "/password/forgot",
+            post(forgot_password_handler)
+                .layer(from_fn_with_state(state.clone(), rate_limit_middleware)),
+        )
+        .route(
+            "/password/reset",
+            post(reset_password_handler)
+                .layer(from_fn_with_state(state.clone(), rate_limit_middleware)),
+        )
         .route("/health", get(|| async { "OK" }));
 
+    // Off limits while an admin impersonates the user
+    let sensitive = Router::new()
+        .route("/logout/all", post(logout_all_handler))
+        .route("/sessions/revoke-others", post(revoke_other_sessions_handler).layer(from_fn(require_scope(SESSIONS_WRITE_SCOPE))))
+        .route("/sessions/{session_id}", delete(revoke_session_handler).layer(from_fn(require_scope(SESSIONS_WRITE_SCOPE))))
+        .route("/reauthenticate", post(reauthenticate_handler))
+        .route("/me/password", post(change_password_handler).layer(from_fn(require_scope(USERS_WRITE_SCOPE))))
+        // Changing the password or deleting the account needs a recent login
+        .route(
+            "/users/{user_id}",
+            patch(update_user_handler)
+                .layer(from_fn(require_scope(USERS_WRITE_SCOPE)))
+                .layer(from_fn(require_recent_auth(STEP_UP_MAX_AGE)))
+                .layer(from_fn(require_owner_or_role(vec!["Admin".to_string()]))),
+        )
+        .route(
+            "/users/{user_id}",
+            delete(delete_user_handler)
+                .layer(from_fn(require_scope(USERS_WRITE_SCOPE)))
+                .layer(from_fn(require_recent_auth(STEP_UP_MAX_AGE)))
+                .layer(from_fn(require_owner_or_role(vec!["Admin".to_string()]))),
+        )
+        .layer(from_fn(deny_impersonation));
+
     let protected = Router::new()
         .route("/logout", post(logout_handler))
+        // Sessions
+        .route("/sessions", get(list_sessions_handler).layer(from_fn(require_scope(SESSIONS_READ_SCOPE))))
+        .route("/userinfo", get(userinfo_handler))
         // Users
-        .route("/users/{user_id}", get(get_user_handler))
-        .route("/users/{user_id}", patch(update_user_handler))
-        .route("/users/{user_id}", delete(delete_user_handler))
+        .route(
+            "/users/{user_id}",
+            get(get_user_handler)
+                .layer(from_fn(require_scope(USERS_READ_SCOPE)))
+                .layer(from_fn(require_owner_or_role(vec!["Admin".to_string()]))),
+        )
+        .merge(sensitive)
+        .layer(from_fn(require_user))
         .layer(from_fn_with_state(state.clone(), auth_middleware))
         .layer(from_fn_with_state(state.clone(), rate_limit_middleware));
 
+    // Called by gateways and other services on every request, so not rate limited per IP.
+    // Only clients granted the introspect scope, with a client_credentials token: users
+    // must not look into tokens that are not theirs.
+    let oauth_router = Router::new()
+        .route("/oauth/introspect", post(introspect_handler))
+        .layer(from_fn(require_scope(INTROSPECT_SCOPE)))
+        .layer(from_fn(require_client))
+        .layer(from_fn_with_state(state.clone(), auth_middleware));
+
+    // Clients reach these without an access token: they are how one is obtained,
+    // and revocation authenticates the client like the token endpoint does. The token
+    // endpoint limits each client itself, see token_handler.
+    let oauth_public = Router::new()
+        .route("/oauth/authorize", get(authorize_page_handler).post(authorize_handler))
+        .route("/oauth/revoke", post(revoke_handler))
+        .layer(from_fn_with_state(state.clone(), rate_limit_middleware))
+        .route("/oauth/token", post(token_handler));
+
     let admin_router = Router::new()
         .route("/admin", get(|| async { "Route only for Admin" }))
+        .route("/admin/users", get(list_users_handler))
+        .route("/admin/users/{user_id}/suspend", post(suspend_user_handler))
+        .route("/admin/users/{user_id}/lock", post(lock_user_handler))
+        .route("/admin/users/{user_id}/reactivate", post(reactivate_user_handler))
+        .route("/admin/users/{user_id}/unlock", post(unlock_user_handler))
+        .route("/admin/keys", get(list_keys_handler))
+        .route("/admin/keys/rotate", post(rotate_keys_handler))
+        .route("/admin/oauth/clients", get(list_oauth_clients_handler))
+        .route("/admin/oauth/clients", post(create_oauth_client_handler))
+        .route("/admin/oauth/clients/{client_id}", delete(delete_oauth_client_handler))
+        .layer(from_fn(require_scope(ADMIN_SCOPE)))
         .layer(from_fn(require_role(vec!["Admin".to_string()])))
         .layer(from_fn_with_state(state.clone(), auth_middleware));
 
     let app_routes = Router::new()
         .merge(admin_router)
+        .merge(oauth_router)
+        .merge(oauth_public)
         .merge(root_router)
         .merge(public)
         .merge(protected);
 
 
-    let app = Router::new().nest("/api", app_routes);
+    let well_known = Router::new()
+        .route("/.well-known/jwks.json", get(jwks_handler))
+        .route("/.well-known/openid-configuration", get(openid_configuration_handler));
+
+    let app = Router::new()
+        .nest("/api", app_routes)
+        .merge(well_known);
 
     app
 }
@@ -0,0 +1,136 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{Extensions, HeaderMap, request::Parts},
+};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::LazyLock,
+};
+
+// Details about the device behind a request, recorded on the sessions it opens
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub device_name: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn new(headers: &HeaderMap, client_ip: &ClientIp) -> Self {
+        ClientInfo {
+            ip_address: client_ip.0.clone(),
+            user_agent: header_value(headers, "user-agent"),
+            device_name: header_value(headers, "x-device-name"),
+        }
+    }
+}
+
+// The address of the client behind a request, as lockouts, rate limits and sessions see
+// it. None only when the server was started without connection info.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientIp(pub Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(request_client_ip(&parts.extensions, &parts.headers))
+    }
+}
+
+pub fn request_client_ip(extensions: &Extensions, headers: &HeaderMap) -> ClientIp {
+    let peer = extensions.get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+
+    ClientIp(client_ip(peer, headers, trusted_proxies()).map(|ip| ip.to_string()))
+}
+
+// A proxy allowed to say who its client is: an address or a CIDR range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustedProxy {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl TrustedProxy {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (address, prefix) = match value.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix.parse::<u8>().ok()?)),
+            None => (value.trim(), None),
+        };
+        let network: IpAddr = address.parse().ok()?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+
+        (prefix <= max).then_some(TrustedProxy { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let (network, ip, bits) = match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => (network.to_bits() as u128, ip.to_bits() as u128, 32),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => (network.to_bits(), ip.to_bits(), 128),
+            _ => return false,
+        };
+        let shift = bits - self.prefix as u32;
+        let mask = if shift >= 128 { 0 } else { u128::MAX << shift };
+
+        network & mask == ip & mask
+    }
+}
+
+// TRUSTED_PROXIES: comma separated addresses or CIDR ranges of the reverse proxies in
+// front of the service. None by default, so forwarding headers are ignored.
+static TRUSTED_PROXIES: LazyLock<Vec<TrustedProxy>> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+
+    std::env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| TrustedProxy::parse(value).expect("Invalid address in TRUSTED_PROXIES"))
+        .collect()
+});
+
+pub fn trusted_proxies() -> &'static [TrustedProxy] {
+    &TRUSTED_PROXIES
+}
+
+// Anyone can send X-Forwarded-For, so it is only believed from a trusted proxy. Read from
+// the right, each hop a trusted proxy added names the one before it; the first address
+// no trusted proxy vouches for is the client.
+pub fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[TrustedProxy]) -> Option<IpAddr> {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|proxy| proxy.contains(ip));
+
+    let mut client = peer?.to_canonical();
+
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+
+    for hop in forwarded.into_iter().rev() {
+        if !is_trusted(client) {
+            break;
+        }
+
+        match hop.parse::<IpAddr>() {
+            Ok(ip) => client = ip.to_canonical(),
+            // A garbled hop cannot be followed further; the proxy that forwarded it is
+            // the last address known for sure
+            Err(_) => break,
+        }
+    }
+
+    Some(client)
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().chars().take(255).collect::<String>())
+        .filter(|value| !value.is_empty())
+}
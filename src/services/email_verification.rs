@@ -0,0 +1,70 @@
+use std::sync::LazyLock;
+
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::oidc::issuer,
+    db::{
+        one_time_token::{consume_one_time_token, create_one_time_token, revoke_one_time_tokens},
+        user::mark_email_verified,
+    },
+    errors::my_error::MyError,
+    models::one_time_token::{EMAIL_VERIFICATION_TTL, OneTimeToken, TokenPurpose},
+    services::{
+        mailer::{Email, Mailer},
+        token_hash::generate_opaque_token,
+    },
+};
+
+// Whether login_handler turns away users who have not verified their email yet
+static REQUIRE_EMAIL_VERIFICATION: LazyLock<bool> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+
+    std::env::var("REQUIRE_EMAIL_VERIFICATION").map_or(true, |value| value != "false")
+});
+
+// Page of the frontend that posts the token to /api/verify-email
+static EMAIL_VERIFICATION_URL: LazyLock<String> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+
+    std::env::var("EMAIL_VERIFICATION_URL").unwrap_or_else(|_| format!("{}/verify-email", issuer()))
+});
+
+pub fn email_verification_required() -> bool {
+    *REQUIRE_EMAIL_VERIFICATION
+}
+
+// Mails a fresh verification link; links sent earlier stop working
+pub async fn send_verification_email(
+    pool: &Pool<Postgres>,
+    mailer: &dyn Mailer,
+    user_id: uuid::Uuid,
+    email: &str,
+) -> Result<(), MyError> {
+    revoke_one_time_tokens(pool, user_id, TokenPurpose::EmailVerification).await?;
+
+    let token = generate_opaque_token();
+    let stored = OneTimeToken::new(&token, user_id, TokenPurpose::EmailVerification, EMAIL_VERIFICATION_TTL);
+    create_one_time_token(pool, &stored).await?;
+
+    mailer
+        .send(Email {
+            to: email.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Confirm your email address by opening the link below within 24 hours:\n\n{}?token={}\n\nIf you did not sign up, ignore this email.",
+                *EMAIL_VERIFICATION_URL, token
+            ),
+        })
+        .await
+}
+
+pub async fn verify_email(pool: &Pool<Postgres>, token: &str) -> Result<uuid::Uuid, MyError> {
+    let user_id = consume_one_time_token(pool, token, TokenPurpose::EmailVerification)
+        .await?
+        .ok_or(MyError::Validation("Invalid or expired verification token".to_string()))?;
+
+    mark_email_verified(pool, user_id).await?;
+
+    Ok(user_id)
+}
@@ -0,0 +1,78 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::sync::LazyLock;
+
+use crate::errors::my_error::MyError;
+
+// Marks key material sealed with SIGNING_KEY_ENCRYPTION_KEY; older rows hold it in plaintext
+const ENCRYPTED_PREFIX: &str = "aes256gcm:";
+
+static ENCRYPTION_KEY: LazyLock<LessSafeKey> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+    let key = std::env::var("SIGNING_KEY_ENCRYPTION_KEY")
+        .expect("SIGNING_KEY_ENCRYPTION_KEY must be set in .env file");
+    let bytes = STANDARD
+        .decode(key.trim())
+        .expect("SIGNING_KEY_ENCRYPTION_KEY must be base64");
+
+    encryption_key(&bytes).expect("SIGNING_KEY_ENCRYPTION_KEY must be 32 bytes")
+});
+
+pub fn encryption_key(bytes: &[u8]) -> Result<LessSafeKey, MyError> {
+    let key = UnboundKey::new(&AES_256_GCM, bytes)
+        .map_err(|_| MyError::Key("Invalid key encryption key".to_string()))?;
+
+    Ok(LessSafeKey::new(key))
+}
+
+pub fn is_encrypted(key_material: &str) -> bool {
+    key_material.starts_with(ENCRYPTED_PREFIX)
+}
+
+// AES-256-GCM with a random nonce, stored as the prefix and base64(nonce || ciphertext).
+// The kid is authenticated too, so material cannot be moved to another key's row.
+pub fn encrypt_key_material(kid: &str, key_material: &str) -> Result<String, MyError> {
+    encrypt_with(&ENCRYPTION_KEY, kid, key_material)
+}
+
+pub fn decrypt_key_material(kid: &str, stored: &str) -> Result<String, MyError> {
+    decrypt_with(&ENCRYPTION_KEY, kid, stored)
+}
+
+pub fn encrypt_with(key: &LessSafeKey, kid: &str, key_material: &str) -> Result<String, MyError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| MyError::Internal)?;
+
+    let mut sealed = key_material.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(kid), &mut sealed)
+        .map_err(|_| MyError::Internal)?;
+
+    let mut stored = nonce.to_vec();
+    stored.extend(sealed);
+
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(stored)))
+}
+
+pub fn decrypt_with(key: &LessSafeKey, kid: &str, stored: &str) -> Result<String, MyError> {
+    let invalid = || MyError::Key(format!("Cannot decrypt the material of signing key {}", kid));
+
+    let encoded = stored.strip_prefix(ENCRYPTED_PREFIX).ok_or_else(invalid)?;
+    let mut bytes = STANDARD.decode(encoded).map_err(|_| invalid())?;
+
+    if bytes.len() < NONCE_LEN {
+        return Err(invalid());
+    }
+
+    let mut sealed = bytes.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&bytes).map_err(|_| invalid())?;
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(kid), &mut sealed)
+        .map_err(|_| invalid())?;
+
+    String::from_utf8(plaintext.to_vec()).map_err(|_| invalid())
+}
@@ -0,0 +1,125 @@
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+
+use crate::errors::my_error::MyError;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String, // plain text
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), MyError>;
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    // STARTTLS on `port`, authenticating when credentials are given
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, String)>,
+        from: &str,
+    ) -> Result<Self, MyError> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|err| MyError::Validation(err.to_string()))?
+            .port(port);
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        let from = from.parse().map_err(|_| MyError::Validation("Invalid MAIL_FROM".to_string()))?;
+
+        Ok(SmtpMailer { transport: builder.build(), from })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), MyError> {
+        let to: Mailbox = email.to.parse().map_err(|_| MyError::BadRequest)?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body)
+            .map_err(|_| MyError::Internal)?;
+
+        self.transport.send(message).await.map_err(|err| {
+            tracing::error!("Failed to send email: {}", err);
+            MyError::Internal
+        })?;
+
+        Ok(())
+    }
+}
+
+// For local development and tests: every message is logged and written to its own
+// file in `dir`, where a developer or a test can pick the links up
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileMailer { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), MyError> {
+        tokio::fs::create_dir_all(&self.dir).await.map_err(|_| MyError::Internal)?;
+
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            uuid::Uuid::new_v4().simple()
+        ));
+        let contents = format!("To: {}\nSubject: {}\n\n{}\n", email.to, email.subject, email.body);
+
+        tokio::fs::write(&path, contents).await.map_err(|_| MyError::Internal)?;
+
+        tracing::info!("Email to {} written to {}", email.to, path.display());
+
+        Ok(())
+    }
+}
+
+// MAIL_TRANSPORT=smtp sends through SMTP_HOST; anything else writes files to MAIL_DIR
+pub fn mailer_from_env() -> Arc<dyn Mailer> {
+    dotenvy::dotenv().ok();
+
+    if std::env::var("MAIL_TRANSPORT").as_deref() != Ok("smtp") {
+        let dir = std::env::var("MAIL_DIR").unwrap_or_else(|_| "./mail".to_string());
+        return Arc::new(FileMailer::new(dir));
+    }
+
+    let host = std::env::var("SMTP_HOST").expect("SMTP_HOST must be set when MAIL_TRANSPORT=smtp");
+    let port = std::env::var("SMTP_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(587);
+    let credentials = std::env::var("SMTP_USERNAME")
+        .ok()
+        .zip(std::env::var("SMTP_PASSWORD").ok());
+    let from = std::env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string());
+
+    Arc::new(SmtpMailer::new(&host, port, credentials, &from).expect("Invalid SMTP configuration"))
+}
@@ -1 +1,11 @@
+pub mod client_info;
+pub mod email_verification;
+pub mod key_encryption;
+pub mod mailer;
 pub mod password;
+pub mod password_change;
+pub mod password_policy;
+pub mod password_reset;
+pub mod pkce;
+pub mod registration;
+pub mod token_hash;
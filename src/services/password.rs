@@ -1,3 +1,5 @@
+use std::sync::LazyLock;
+
 use argon2::{
     Argon2,
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
@@ -33,3 +35,19 @@ pub fn verify_password(
 
     Ok(result)
 }
+
+// Hash of a password nobody has, checked when there is no account so that a login for an
+// unknown email costs the same Argon2 work as one for a registered email
+static DUMMY_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password(&uuid::Uuid::new_v4().to_string()).unwrap());
+
+// Whether `password` matches `hash`, spending the same time when there is no hash
+pub fn verify_password_or_dummy(hash: Option<&str>, password: &str) -> bool {
+    match hash {
+        Some(hash) => verify_password(hash, password).unwrap_or(false),
+        None => {
+            let _ = verify_password(&DUMMY_HASH, password);
+            false
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{
+    auth::lockout::verify_current_password,
+    db::user::{get_user_account_by_id, set_password},
+    errors::my_error::MyError,
+    models::app::AppState,
+    services::{
+        password::{hash_password, verify_password},
+        password_policy::password_policy,
+    },
+};
+
+// Stores `new_password` once the user has proven they know `current_password`, wrong
+// guesses counting towards the login lockout; the caller must still end the user's
+// other sessions
+pub async fn change_password(
+    app_state: &AppState,
+    user_id: uuid::Uuid,
+    current_password: &str,
+    new_password: &str,
+    ip: Option<&str>,
+) -> Result<(), MyError> {
+    let pool = &app_state.pool;
+    let user = get_user_account_by_id(pool, user_id)
+        .await?
+        .ok_or(MyError::Unauthorized)?;
+
+    verify_current_password(app_state, &user, current_password, ip).await?;
+
+    password_policy().check(new_password, &[&user.name, &user.email])?;
+
+    if verify_password(&user.password, new_password).unwrap_or(false) {
+        return Err(MyError::Validation(
+            "The new password must differ from the current one".to_string(),
+        ));
+    }
+
+    let password_hash = hash_password(new_password).map_err(MyError::HashingError)?;
+    set_password(pool, user_id, &password_hash).await?;
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::errors::my_error::MyError;
+
+// Shorter words would ban too many passwords, e.g. a name like "Al"
+const MIN_BANNED_WORD_LENGTH: usize = 3;
+
+// One rule a password breaks, serialized as {"code": "too_short", "min": 8} etc. so
+// frontends can show their own message for each
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum PasswordViolation {
+    TooShort { min: usize },
+    TooLong { max: usize },
+    MissingLowercase,
+    MissingUppercase,
+    MissingDigit,
+    MissingSymbol,
+    ContainsPersonalInfo, // the user's name or email
+    ContainsBannedWord,   // one of the deployment's PASSWORD_BANNED_WORDS
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub min_length: usize, // in characters
+    pub max_length: usize, // bounds the Argon2 work a single request can ask for
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub banned_words: Vec<String>, // lowercase
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: false,
+            banned_words: vec!["password".to_string()],
+        }
+    }
+}
+
+impl PasswordPolicy {
+    // PASSWORD_MIN_LENGTH, PASSWORD_MAX_LENGTH, PASSWORD_REQUIRE_LOWERCASE,
+    // PASSWORD_REQUIRE_UPPERCASE, PASSWORD_REQUIRE_DIGIT, PASSWORD_REQUIRE_SYMBOL and
+    // PASSWORD_BANNED_WORDS (comma separated) override the defaults
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    // Same as from_env, reading the variables through `lookup`
+    pub fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = PasswordPolicy::default();
+        let length = |name: &str, default: usize| {
+            lookup(name)
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+        };
+        let flag = |name: &str, default: bool| {
+            lookup(name).map_or(default, |value| value.trim().eq_ignore_ascii_case("true"))
+        };
+
+        let banned_words = match lookup("PASSWORD_BANNED_WORDS") {
+            Some(words) => words
+                .split(',')
+                .map(|word| word.trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+            None => defaults.banned_words,
+        };
+
+        let min_length = length("PASSWORD_MIN_LENGTH", defaults.min_length);
+
+        PasswordPolicy {
+            min_length,
+            max_length: length("PASSWORD_MAX_LENGTH", defaults.max_length).max(min_length),
+            require_lowercase: flag("PASSWORD_REQUIRE_LOWERCASE", defaults.require_lowercase),
+            require_uppercase: flag("PASSWORD_REQUIRE_UPPERCASE", defaults.require_uppercase),
+            require_digit: flag("PASSWORD_REQUIRE_DIGIT", defaults.require_digit),
+            require_symbol: flag("PASSWORD_REQUIRE_SYMBOL", defaults.require_symbol),
+            banned_words,
+        }
+    }
+
+    // Every rule `password` breaks, empty when it is acceptable. `personal` holds what the
+    // password must not contain for this user, e.g. their name and email.
+    pub fn violations(&self, password: &str, personal: &[&str]) -> Vec<PasswordViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            violations.push(PasswordViolation::TooShort { min: self.min_length });
+        }
+
+        if length > self.max_length {
+            violations.push(PasswordViolation::TooLong { max: self.max_length });
+        }
+
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push(PasswordViolation::MissingLowercase);
+        }
+
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push(PasswordViolation::MissingUppercase);
+        }
+
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push(PasswordViolation::MissingDigit);
+        }
+
+        if self.require_symbol && !password.chars().any(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
+            violations.push(PasswordViolation::MissingSymbol);
+        }
+
+        let lowercase = password.to_lowercase();
+        let contains = |word: &str| word.chars().count() >= MIN_BANNED_WORD_LENGTH && lowercase.contains(word);
+
+        if personal_words(personal).iter().any(|word| contains(word)) {
+            violations.push(PasswordViolation::ContainsPersonalInfo);
+        }
+
+        if self.banned_words.iter().any(|word| contains(word)) {
+            violations.push(PasswordViolation::ContainsBannedWord);
+        }
+
+        violations
+    }
+
+    pub fn check(&self, password: &str, personal: &[&str]) -> Result<(), MyError> {
+        let violations = self.violations(password, personal);
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(MyError::PasswordPolicy(violations))
+    }
+}
+
+// Splits names and emails into the words a password is checked against, e.g.
+// "ana.silva@example.com" into "ana.silva", "ana" and "silva". Email domains are
+// left out, they are shared by many users and say little about this one.
+fn personal_words(personal: &[&str]) -> Vec<String> {
+    personal
+        .iter()
+        .flat_map(|value| {
+            let local = value.split('@').next().unwrap_or_default().trim().to_lowercase();
+            let parts: Vec<String> = local
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|part| !part.is_empty())
+                .map(str::to_string)
+                .collect();
+
+            std::iter::once(local).chain(parts)
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+static POLICY: LazyLock<PasswordPolicy> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+    PasswordPolicy::from_env()
+});
+
+pub fn password_policy() -> &'static PasswordPolicy {
+    &POLICY
+}
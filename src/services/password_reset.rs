@@ -0,0 +1,86 @@
+use std::sync::LazyLock;
+
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::oidc::issuer,
+    db::{
+        one_time_token::{
+            consume_one_time_token, create_one_time_token, find_one_time_token_user,
+            revoke_one_time_tokens,
+        },
+        user::{get_user_account_by_id, mark_email_verified, set_password},
+    },
+    errors::my_error::MyError,
+    models::one_time_token::{OneTimeToken, PASSWORD_RESET_TTL, TokenPurpose},
+    services::{
+        mailer::{Email, Mailer},
+        password::hash_password,
+        password_policy::password_policy,
+        token_hash::generate_opaque_token,
+    },
+};
+
+// Page of the frontend that asks for the new password and posts it to /api/password/reset
+static PASSWORD_RESET_URL: LazyLock<String> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+
+    std::env::var("PASSWORD_RESET_URL").unwrap_or_else(|_| format!("{}/reset-password", issuer()))
+});
+
+// Mails a fresh reset link; links sent earlier stop working
+pub async fn send_password_reset_email(
+    pool: &Pool<Postgres>,
+    mailer: &dyn Mailer,
+    user_id: uuid::Uuid,
+    email: &str,
+) -> Result<(), MyError> {
+    revoke_one_time_tokens(pool, user_id, TokenPurpose::PasswordReset).await?;
+
+    let token = generate_opaque_token();
+    let stored = OneTimeToken::new(&token, user_id, TokenPurpose::PasswordReset, PASSWORD_RESET_TTL);
+    create_one_time_token(pool, &stored).await?;
+
+    mailer
+        .send(Email {
+            to: email.to_string(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Choose a new password by opening the link below within an hour:\n\n{}?token={}\n\nIf you did not ask for this, ignore this email; your password stays the same.",
+                *PASSWORD_RESET_URL, token
+            ),
+        })
+        .await
+}
+
+// Redeems the token and stores the new password; the caller must still end the user's sessions
+pub async fn reset_password(
+    pool: &Pool<Postgres>,
+    token: &str,
+    password: &str,
+) -> Result<uuid::Uuid, MyError> {
+    let invalid_token = || MyError::Validation("Invalid or expired reset token".to_string());
+
+    // The policy is checked before the token is spent, so the user can pick another password
+    let user_id = find_one_time_token_user(pool, token, TokenPurpose::PasswordReset)
+        .await?
+        .ok_or_else(invalid_token)?;
+    let user = get_user_account_by_id(pool, user_id)
+        .await?
+        .filter(|user| user.status.allows_sign_in())
+        .ok_or_else(invalid_token)?;
+
+    password_policy().check(password, &[&user.name, &user.email])?;
+
+    consume_one_time_token(pool, token, TokenPurpose::PasswordReset)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    let password_hash = hash_password(password).map_err(MyError::HashingError)?;
+    set_password(pool, user_id, &password_hash).await?;
+
+    // Following the mailed link proves the address is theirs
+    mark_email_verified(pool, user_id).await?;
+
+    Ok(user_id)
+}
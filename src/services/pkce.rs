@@ -0,0 +1,27 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ring::digest;
+
+// RFC 7636 §4.1: 43 to 128 characters from the unreserved set
+pub fn is_valid_code_verifier(verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && verifier
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+}
+
+// An S256 challenge is the unpadded base64url encoding of a SHA-256 digest
+pub fn is_valid_code_challenge(challenge: &str) -> bool {
+    URL_SAFE_NO_PAD
+        .decode(challenge)
+        .map(|digest| digest.len() == digest::SHA256_OUTPUT_LEN)
+        .unwrap_or(false)
+}
+
+// BASE64URL(SHA256(code_verifier)), the only method accepted since "plain" offers no protection
+pub fn s256_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, verifier.as_bytes()))
+}
+
+pub fn verify_code_challenge(verifier: &str, challenge: &str) -> bool {
+    is_valid_code_verifier(verifier) && s256_challenge(verifier) == challenge
+}
@@ -0,0 +1,63 @@
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    db::user::{create_user, get_user_by_email},
+    errors::my_error::MyError,
+    models::user::UserRegister,
+    services::{
+        email_verification::send_verification_email,
+        mailer::{Email, Mailer},
+        password::hash_password,
+        password_policy::password_policy,
+    },
+};
+
+// Signs up a new account or, when the address is already registered, mails its owner
+// instead. Both paths hash a password and send one email, so neither the answer nor its
+// timing tells a caller whether the address was taken.
+pub async fn register_user(
+    pool: &Pool<Postgres>,
+    mailer: &dyn Mailer,
+    payload: UserRegister,
+) -> Result<(), MyError> {
+    let email = payload.email.clone().unwrap_or_default().trim().to_lowercase();
+    let name = payload.name.clone().unwrap_or_default();
+    let password = payload.password.clone().unwrap_or_default();
+
+    // Checked first, so a weak password is refused whether or not the address is taken
+    password_policy().check(&password, &[&name, &email])?;
+
+    if let Some(existing) = get_user_by_email(pool, email.clone()).await? {
+        let _ = hash_password(&password);
+
+        if let Err(err) = mailer.send(existing_account_notice(&existing.email)).await {
+            tracing::warn!("Could not send the sign-up notice to user {}: {}", existing.id, err);
+        }
+
+        return Ok(());
+    }
+
+    let user = match create_user(pool, payload).await {
+        Ok(user) => user,
+        // Registered concurrently by someone else, who has just been sent the link
+        Err(MyError::DatabaseError(sqlx::Error::Database(err))) if err.is_unique_violation() => {
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+
+    // The account exists either way; a failed send can be retried through the resend endpoint
+    if let Err(err) = send_verification_email(pool, mailer, user.id, &user.email).await {
+        tracing::warn!("Could not send the verification email to user {}: {}", user.id, err);
+    }
+
+    Ok(())
+}
+
+fn existing_account_notice(email: &str) -> Email {
+    Email {
+        to: email.to_string(),
+        subject: "You already have an account".to_string(),
+        body: "Someone tried to sign up with this email address, which already has an account.\n\nIf it was you, sign in, or reset your password if you forgot it. Otherwise you can ignore this email.".to_string(),
+    }
+}
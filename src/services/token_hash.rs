@@ -0,0 +1,30 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::sync::LazyLock;
+
+static TOKEN_HASH_KEY: LazyLock<hmac::Key> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+    let pepper = std::env::var("TOKEN_HASH_SECRET").expect("TOKEN_HASH_SECRET must be set in .env file");
+
+    hmac::Key::new(hmac::HMAC_SHA256, pepper.as_bytes())
+});
+
+// Keyed hash (HMAC-SHA256, hex) of a bearer secret, so a database dump alone yields no usable token
+pub fn hash_token(token: &str) -> String {
+    let tag = hmac::sign(&TOKEN_HASH_KEY, token.as_bytes());
+
+    tag.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Random URL-safe secret (256 bits) for opaque bearer values such as authorization codes
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("System random number generator failed");
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
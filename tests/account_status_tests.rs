@@ -0,0 +1,214 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::{delete, post},
+};
+use common::{TEST_PASSWORD, TestClaims, create_test_user, create_test_user_with_email, mail_dir};
+use rust_auth_service::{
+    db::{
+        auth::{create_session, get_session},
+        user::{
+            delete_user, get_user_by_email, get_user_by_id, get_user_status, mark_email_verified,
+            set_user_status,
+        },
+    },
+    errors::my_error::MyError,
+    handlers::{auth::login_handler, user::delete_user_handler},
+    models::{
+        auth::Claims,
+        session::Session,
+        user::{UserOutput, UserStatus},
+    },
+    services::client_info::ClientInfo,
+};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+async fn setup() -> (PgPool, UserOutput) {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Status User").await;
+
+    (pool, user)
+}
+
+async fn app() -> Router {
+    Router::new()
+        .route("/login", post(login_handler))
+        .route("/users/{user_id}", delete(delete_user_handler))
+        .with_state(common::app_state(&mail_dir()).await)
+}
+
+async fn delete_account(user: &UserOutput) -> StatusCode {
+    let mut request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/users/{}", user.id))
+        .body(Body::empty())
+        .unwrap();
+    request.extensions_mut().insert(Claims::for_test(user.id));
+
+    app().await.oneshot(request).await.unwrap().status()
+}
+
+async fn login(user: &UserOutput) -> StatusCode {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::json!({ "email": user.email, "password": TEST_PASSWORD }).to_string(),
+        ))
+        .unwrap();
+
+    app().await.oneshot(request).await.unwrap().status()
+}
+
+#[test]
+fn should_only_allow_listed_transitions() {
+    use UserStatus::*;
+
+    assert!(Pending.can_transition_to(Active));
+    assert!(Active.can_transition_to(Suspended));
+    assert!(Active.can_transition_to(Locked));
+    assert!(Suspended.can_transition_to(Active));
+    assert!(Locked.can_transition_to(Active));
+    assert!(Suspended.can_transition_to(Deleted));
+
+    assert!(!Suspended.can_transition_to(Locked));
+    assert!(!Active.can_transition_to(Pending));
+    assert!(!Active.can_transition_to(Active));
+    for next in [Pending, Active, Suspended, Locked] {
+        assert!(!Deleted.can_transition_to(next));
+    }
+}
+
+#[test]
+fn should_only_let_pending_and_active_accounts_sign_in() {
+    assert!(UserStatus::Pending.check_sign_in().is_ok());
+    assert!(UserStatus::Active.check_sign_in().is_ok());
+
+    for status in [UserStatus::Suspended, UserStatus::Locked, UserStatus::Deleted] {
+        match status.check_sign_in() {
+            Err(MyError::LoginError(message)) => {
+                assert_eq!(message, format!("Account {}", status.as_str()))
+            }
+            other => panic!("expected a login error, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn should_round_trip_status_names() {
+    for status in [
+        UserStatus::Pending,
+        UserStatus::Active,
+        UserStatus::Suspended,
+        UserStatus::Locked,
+        UserStatus::Deleted,
+    ] {
+        assert_eq!(UserStatus::parse(status.as_str()), Some(status));
+    }
+
+    assert_eq!(UserStatus::parse("banned"), None);
+}
+
+#[tokio::test]
+async fn should_start_pending_and_activate_on_verification() {
+    let (pool, user) = setup().await;
+
+    assert_eq!(get_user_status(&pool, user.id).await.unwrap(), Some(UserStatus::Pending));
+
+    mark_email_verified(&pool, user.id).await.unwrap();
+    assert_eq!(get_user_by_id(&pool, user.id).await.unwrap().status, UserStatus::Active);
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_not_reactivate_suspended_account_on_verification() {
+    let (pool, user) = setup().await;
+
+    let suspended =
+        set_user_status(&pool, user.id, UserStatus::Pending, UserStatus::Suspended, Some("Spam"));
+    assert!(suspended.await.unwrap());
+
+    mark_email_verified(&pool, user.id).await.unwrap();
+    assert_eq!(get_user_status(&pool, user.id).await.unwrap(), Some(UserStatus::Suspended));
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_only_change_status_from_the_expected_one() {
+    let (pool, user) = setup().await;
+
+    // Someone else already moved the account on
+    let suspended = set_user_status(&pool, user.id, UserStatus::Active, UserStatus::Suspended, None);
+    assert!(!suspended.await.unwrap());
+    assert_eq!(get_user_status(&pool, user.id).await.unwrap(), Some(UserStatus::Pending));
+
+    delete_user(&pool, user.id).await.unwrap();
+    assert_eq!(get_user_status(&pool, user.id).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn should_keep_a_deleted_account_and_its_history() {
+    let (pool, user) = setup().await;
+    mark_email_verified(&pool, user.id).await.unwrap();
+
+    let session = Session::new(user.id, ClientInfo::default());
+    create_session(&pool, &session).await.unwrap();
+
+    assert_eq!(delete_account(&user).await, StatusCode::OK);
+
+    // Still there, closed and signed out everywhere
+    assert_eq!(get_user_status(&pool, user.id).await.unwrap(), Some(UserStatus::Deleted));
+    assert!(get_session(&pool, session.id).await.unwrap().unwrap().revoked_at.is_some());
+
+    let events: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM security_events WHERE user_id = $1 AND event_type = 'status_change'",
+    )
+    .bind(user.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(events, 1);
+
+    assert_eq!(delete_account(&user).await, StatusCode::NOT_FOUND);
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_refuse_sign_in_to_a_deleted_account() {
+    let (pool, user) = setup().await;
+    mark_email_verified(&pool, user.id).await.unwrap();
+
+    assert_eq!(login(&user).await, StatusCode::OK);
+
+    assert_eq!(delete_account(&user).await, StatusCode::OK);
+    assert_eq!(login(&user).await, StatusCode::UNAUTHORIZED);
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_hide_a_deleted_account_and_release_its_email() {
+    let (pool, user) = setup().await;
+    mark_email_verified(&pool, user.id).await.unwrap();
+
+    assert_eq!(delete_account(&user).await, StatusCode::OK);
+
+    assert!(matches!(get_user_by_id(&pool, user.id).await, Err(MyError::NotFound)));
+    assert!(get_user_by_email(&pool, user.email.clone()).await.unwrap().is_none());
+
+    // The address can be signed up with again, and then names the new account only
+    let successor = create_test_user_with_email(&pool, "Successor", &user.email).await;
+    let found = get_user_by_email(&pool, user.email.clone()).await.unwrap().unwrap();
+    assert_eq!(found.id, successor.id);
+
+    delete_user(&pool, successor.id).await.unwrap();
+    delete_user(&pool, user.id).await.unwrap();
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use common::{create_test_user_with_email, delete_test_user};
+use rust_auth_service::{
+    db::{
+        role::{get_role_by_name, set_user_role},
+        user::{list_users, set_user_status},
+    },
+    models::user::{UserListQuery, UserOutput, UserSort, UserStatus},
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Users whose emails share a unique marker, so filtering on it ignores other tests' users
+async fn setup(names: &[&str]) -> (PgPool, String, Vec<UserOutput>) {
+    let pool = common::pool();
+    let marker = format!("list_{}", Uuid::new_v4().simple());
+
+    let mut users = Vec::new();
+    for name in names {
+        let email = format!("{}_{}@example.com", name.to_lowercase(), marker);
+        users.push(create_test_user_with_email(&pool, name, &email).await);
+    }
+
+    (pool, marker, users)
+}
+
+async fn teardown(pool: &PgPool, users: &[UserOutput]) {
+    for user in users {
+        delete_test_user(pool, user.id).await;
+    }
+}
+
+fn query(marker: &str) -> UserListQuery {
+    UserListQuery {
+        email: Some(marker.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn should_default_to_first_page_newest_first() {
+    let filter = UserListQuery::default().into_filter().unwrap();
+
+    assert_eq!(filter.page, 1);
+    assert_eq!(filter.per_page, 20);
+    assert_eq!(filter.offset(), 0);
+    assert_eq!(filter.sort, UserSort::CreatedAt);
+    assert!(filter.descending);
+}
+
+#[test]
+fn should_reject_invalid_list_queries() {
+    let invalid = [
+        UserListQuery { page: Some(0), ..Default::default() },
+        UserListQuery { per_page: Some(0), ..Default::default() },
+        UserListQuery { per_page: Some(101), ..Default::default() },
+        UserListQuery { sort: Some("password".to_string()), ..Default::default() },
+        UserListQuery {
+            created_from: Some(chrono::Utc::now()),
+            created_to: Some(chrono::Utc::now() - chrono::Duration::days(1)),
+            ..Default::default()
+        },
+    ];
+
+    for query in invalid {
+        assert!(query.into_filter().is_err());
+    }
+}
+
+#[tokio::test]
+async fn should_paginate_with_total_count() {
+    let (pool, marker, users) = setup(&["Ana", "Bruno", "Carla"]).await;
+    let page = |page: i64| {
+        UserListQuery {
+            page: Some(page),
+            per_page: Some(2),
+            sort: Some("name".to_string()),
+            ..query(&marker)
+        }
+        .into_filter()
+        .unwrap()
+    };
+
+    let (first, total) = list_users(&pool, &page(1)).await.unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(first.iter().map(|user| user.name.as_str()).collect::<Vec<_>>(), ["Ana", "Bruno"]);
+
+    let (second, total) = list_users(&pool, &page(2)).await.unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(second.iter().map(|user| user.name.as_str()).collect::<Vec<_>>(), ["Carla"]);
+
+    teardown(&pool, &users).await;
+}
+
+#[tokio::test]
+async fn should_sort_descending() {
+    let (pool, marker, users) = setup(&["Ana", "Bruno"]).await;
+
+    let filter = UserListQuery { sort: Some("-email".to_string()), ..query(&marker) }
+        .into_filter()
+        .unwrap();
+    let (listed, _) = list_users(&pool, &filter).await.unwrap();
+
+    assert_eq!(listed.iter().map(|user| user.name.as_str()).collect::<Vec<_>>(), ["Bruno", "Ana"]);
+
+    teardown(&pool, &users).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_role_with_roles_listed() {
+    let (pool, marker, users) = setup(&["Ana", "Bruno"]).await;
+    let admin = get_role_by_name(&pool, "Admin".to_string()).await.unwrap();
+    set_user_role(&pool, users[1].id, admin.id).await.unwrap();
+
+    let filter = UserListQuery { role: Some("Admin".to_string()), ..query(&marker) }
+        .into_filter()
+        .unwrap();
+    let (listed, total) = list_users(&pool, &filter).await.unwrap();
+
+    assert_eq!(total, 1);
+    assert_eq!(listed[0].id, users[1].id);
+    assert!(listed[0].roles.contains(&"Admin".to_string()));
+    assert!(listed[0].roles.contains(&"User".to_string()));
+
+    teardown(&pool, &users).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_creation_date() {
+    let (pool, marker, users) = setup(&["Ana"]).await;
+
+    let after = UserListQuery { created_from: Some(users[0].created_at), ..query(&marker) };
+    assert_eq!(list_users(&pool, &after.into_filter().unwrap()).await.unwrap().1, 1);
+
+    let before = UserListQuery {
+        created_to: Some(users[0].created_at - chrono::Duration::seconds(1)),
+        ..query(&marker)
+    };
+    assert_eq!(list_users(&pool, &before.into_filter().unwrap()).await.unwrap().1, 0);
+
+    teardown(&pool, &users).await;
+}
+
+#[tokio::test]
+async fn should_match_email_wildcards_literally() {
+    let (pool, marker, users) = setup(&["Ana"]).await;
+
+    // "_" and "%" would match any character in a raw LIKE pattern
+    let filter = UserListQuery { email: Some(format!("ana%{}", marker)), ..Default::default() };
+    assert_eq!(list_users(&pool, &filter.into_filter().unwrap()).await.unwrap().1, 0);
+
+    let filter = UserListQuery { email: Some(format!("ANA_{}", marker)), ..Default::default() };
+    assert_eq!(list_users(&pool, &filter.into_filter().unwrap()).await.unwrap().1, 1);
+
+    teardown(&pool, &users).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_status() {
+    let (pool, marker, users) = setup(&["Ana", "Bruno"]).await;
+    set_user_status(&pool, users[1].id, UserStatus::Pending, UserStatus::Suspended, Some("Spam"))
+        .await
+        .unwrap();
+
+    let filter = UserListQuery { status: Some(UserStatus::Suspended), ..query(&marker) }
+        .into_filter()
+        .unwrap();
+    let (listed, total) = list_users(&pool, &filter).await.unwrap();
+
+    assert_eq!(total, 1);
+    assert_eq!(listed[0].id, users[1].id);
+    assert_eq!(listed[0].status, UserStatus::Suspended);
+
+    teardown(&pool, &users).await;
+}
+
+#[tokio::test]
+async fn should_only_list_deleted_users_when_asked_for() {
+    let (pool, marker, users) = setup(&["Alice", "Bob"]).await;
+    set_user_status(&pool, users[1].id, UserStatus::Pending, UserStatus::Deleted, None)
+        .await
+        .unwrap();
+
+    let (listed, total) = list_users(&pool, &query(&marker).into_filter().unwrap()).await.unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(listed[0].id, users[0].id);
+
+    let filter = UserListQuery { status: Some(UserStatus::Deleted), ..query(&marker) }
+        .into_filter()
+        .unwrap();
+    let (listed, _) = list_users(&pool, &filter).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, users[1].id);
+
+    teardown(&pool, &users).await;
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::{JWT_SECRET, TestClaims};
+use jsonwebtoken::{EncodingKey, Header, encode};
+use rust_auth_service::{
+    auth::{
+        auth::{decode_access_token, decode_token, issue_client_token},
+        oidc::{is_known_audience, issuer, resource_audience},
+    },
+    models::{
+        auth::Claims,
+        oauth_client::{CreateOAuthClientInput, OAuthClient},
+    },
+};
+use uuid::Uuid;
+
+fn token_for(iss: &str, aud: &str) -> String {
+    let claims = Claims {
+        iss: iss.to_string(),
+        aud: aud.to_string(),
+        ..Claims::for_test(Uuid::new_v4())
+    };
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap()
+}
+
+#[test]
+fn should_accept_tokens_addressed_to_this_service() {
+    common::init();
+    let claims = decode_access_token(&token_for(issuer(), resource_audience())).unwrap();
+
+    assert_eq!(claims.iss, issuer());
+    assert_eq!(claims.aud, resource_audience());
+}
+
+#[test]
+fn should_reject_tokens_for_another_audience() {
+    common::init();
+    let token = token_for(issuer(), "https://reports.example.com");
+
+    assert!(decode_access_token(&token).is_err());
+
+    // Introspection and revocation still recognise the token
+    let claims = decode_token(&token, None).unwrap();
+    assert_eq!(claims.aud, "https://reports.example.com");
+    assert!(decode_token(&token, Some("https://reports.example.com")).is_ok());
+}
+
+#[test]
+fn should_reject_tokens_from_another_issuer() {
+    common::init();
+    let token = token_for("https://evil.example.com", resource_audience());
+
+    assert!(decode_access_token(&token).is_err());
+    assert!(decode_token(&token, None).is_err());
+}
+
+#[test]
+fn should_reject_tokens_without_issuer_or_audience() {
+    common::init();
+    let now = chrono::Utc::now().timestamp() as usize;
+    let legacy = serde_json::json!({
+        "sub": Uuid::new_v4(),
+        "email": "test@example.com",
+        "roles": [],
+        "jti": Uuid::new_v4().to_string(),
+        "iat": now,
+        "exp": now + 900,
+        "token_type": "Access",
+    });
+    let token =
+        encode(&Header::default(), &legacy, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap();
+
+    assert!(decode_access_token(&token).is_err());
+    assert!(decode_token(&token, None).is_err());
+}
+
+#[test]
+fn should_only_issue_client_tokens_for_assigned_audiences() {
+    common::init();
+    let client = OAuthClient::register(CreateOAuthClientInput {
+        name: "Report Worker".to_string(),
+        redirect_uris: vec![],
+        grant_types: Some(vec!["client_credentials".to_string()]),
+        confidential: true,
+        scopes: vec![],
+        audiences: vec![],
+    })
+    .unwrap()
+    .client;
+
+    assert_eq!(client.token_audience(None).unwrap(), None);
+    assert_eq!(client.token_audience(Some(resource_audience())).unwrap(), None);
+    assert!(client.token_audience(Some("https://reports.example.com")).is_err());
+
+    let token = issue_client_token(&client, "", resource_audience()).unwrap();
+    assert_eq!(decode_access_token(&token).unwrap().aud, resource_audience());
+
+    // Audiences must be resource servers configured to trust this issuer
+    assert!(!is_known_audience("https://reports.example.com"));
+    assert!(is_known_audience(resource_audience()));
+}
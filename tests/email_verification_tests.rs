@@ -0,0 +1,177 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    routing::post,
+};
+use common::{
+    create_test_user, delete_test_user, latest_token, mail_dir, remove_mail_dir, unique_email,
+    wait_for_emails,
+};
+use rust_auth_service::{
+    db::{
+        one_time_token::{consume_one_time_token, create_one_time_token},
+        user::{get_user_by_email, mark_email_verified},
+    },
+    handlers::verification::resend_verification_handler,
+    models::{
+        app::AppState,
+        one_time_token::{OneTimeToken, TokenPurpose},
+        user::UserOutput,
+    },
+    services::{
+        email_verification::{send_verification_email, verify_email},
+        mailer::{Email, FileMailer, Mailer},
+    },
+};
+use sqlx::PgPool;
+use std::path::{Path, PathBuf};
+use tower::ServiceExt;
+
+async fn setup() -> (PgPool, UserOutput, PathBuf) {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Verify User").await;
+
+    (pool, user, mail_dir())
+}
+
+async fn teardown(pool: &PgPool, user: &UserOutput, dir: &Path) {
+    delete_test_user(pool, user.id).await;
+    remove_mail_dir(dir);
+}
+
+async fn resend(app_state: AppState, email: &str) -> (StatusCode, String) {
+    let app = Router::new()
+        .route("/", post(resend_verification_handler))
+        .with_state(app_state);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::json!({ "email": email }).to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn should_write_emails_to_directory() {
+    let dir = mail_dir();
+    let mailer = FileMailer::new(&dir);
+
+    mailer
+        .send(Email {
+            to: "someone@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Body".to_string(),
+        })
+        .await
+        .unwrap();
+
+    let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+
+    let contents = std::fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+    assert!(contents.contains("To: someone@example.com"));
+    assert!(contents.contains("Subject: Hello"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn should_verify_email_once() {
+    let (pool, user, dir) = setup().await;
+    let mailer = FileMailer::new(&dir);
+
+    let stored = get_user_by_email(&pool, user.email.clone()).await.unwrap().unwrap();
+    assert!(stored.email_verified_at.is_none());
+
+    send_verification_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    let token = latest_token(&dir);
+
+    assert_eq!(verify_email(&pool, &token).await.unwrap(), user.id);
+
+    let stored = get_user_by_email(&pool, user.email.clone()).await.unwrap().unwrap();
+    assert!(stored.email_verified_at.is_some());
+
+    // The link is single-use
+    assert!(verify_email(&pool, &token).await.is_err());
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_invalidate_earlier_link_on_resend() {
+    let (pool, user, dir) = setup().await;
+    let mailer = FileMailer::new(&dir);
+
+    send_verification_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    let first = latest_token(&dir);
+
+    send_verification_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    let second = latest_token(&dir);
+
+    assert_ne!(first, second);
+    assert!(verify_email(&pool, &first).await.is_err());
+    assert!(verify_email(&pool, &second).await.is_ok());
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_reject_expired_or_unknown_tokens() {
+    let (pool, user, dir) = setup().await;
+
+    let expired = OneTimeToken::new("expired-token", user.id, TokenPurpose::EmailVerification, -60);
+    create_one_time_token(&pool, &expired).await.unwrap();
+
+    assert!(
+        consume_one_time_token(&pool, "expired-token", TokenPurpose::EmailVerification)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(verify_email(&pool, "not-a-token").await.is_err());
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_resend_the_link_in_the_background() {
+    let (pool, user, dir) = setup().await;
+
+    let (status, _) = resend(common::app_state(&dir).await, &user.email).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let sent = wait_for_emails(&dir, 1).await;
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].contains(&user.email));
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_answer_resend_alike_for_unknown_and_verified_emails() {
+    let (pool, user, dir) = setup().await;
+    let (_, verified, _) = setup().await;
+    mark_email_verified(&pool, verified.id).await.unwrap();
+
+    // Even a mail server that is down must not set an unverified address apart
+    let unverified = resend(common::app_state_without_mail().await, &user.email).await;
+    let verified_answer = resend(common::app_state_without_mail().await, &verified.email).await;
+    let unknown = resend(common::app_state_without_mail().await, &unique_email("nobody")).await;
+
+    assert_eq!(unverified.0, StatusCode::OK);
+    assert_eq!(unverified, unknown);
+    assert_eq!(verified_answer, unknown);
+
+    delete_test_user(&pool, verified.id).await;
+    teardown(&pool, &user, &dir).await;
+}
@@ -0,0 +1,315 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    routing::post,
+};
+use common::{create_test_account, mail_dir, remove_mail_dir};
+use rust_auth_service::{
+    auth::auth::{
+        IMPERSONATION_TOKEN_TTL, decode_access_token, generate_tokens, issue_impersonation_token,
+    },
+    db::{
+        oauth::{create_oauth_client, delete_oauth_client},
+        role::{get_role_by_name, set_user_role},
+        user::{delete_user, get_token_epoch},
+    },
+    handlers::oauth::token_handler,
+    models::{
+        auth::Actor,
+        oauth::{ACCESS_TOKEN_TYPE, IntrospectionResponse, USER_ID_TOKEN_TYPE},
+        oauth_client::{CreateOAuthClientInput, OAuthClient, TOKEN_EXCHANGE_GRANT},
+        user::User,
+    },
+};
+use sqlx::PgPool;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+async fn setup() -> (PgPool, User) {
+    let pool = common::pool();
+    let user = create_test_account(&pool, "Customer").await;
+
+    (pool, user)
+}
+
+#[tokio::test]
+async fn should_issue_token_for_user_with_actor() {
+    let (pool, user) = setup().await;
+    let admin_id = Uuid::new_v4();
+
+    let (token, issued) = issue_impersonation_token(&pool, &user, admin_id).await.unwrap();
+    let claims = decode_access_token(&token).unwrap();
+
+    assert_eq!(claims.sub, user.id);
+    assert_eq!(claims.email, user.email);
+    assert_eq!(claims.roles, vec!["User".to_string()]);
+    assert_eq!(claims.act, Some(Actor { sub: admin_id }));
+    assert!(claims.is_impersonated());
+    assert_eq!(claims.jti, issued.jti);
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_keep_impersonation_short_lived_and_sessionless() {
+    let (pool, user) = setup().await;
+
+    let (token, _) = issue_impersonation_token(&pool, &user, Uuid::new_v4()).await.unwrap();
+    let claims = decode_access_token(&token).unwrap();
+
+    assert_eq!(claims.exp - claims.iat, IMPERSONATION_TOKEN_TTL as usize);
+    assert!(claims.sid.is_none());
+
+    // Logging the user out everywhere also ends the impersonation
+    assert_eq!(
+        Some(claims.epoch),
+        get_token_epoch(&pool, user.id).await.unwrap()
+    );
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_not_mark_regular_tokens_as_impersonated() {
+    let (pool, user) = setup().await;
+
+    let (access_token, _) = generate_tokens(&pool, &user).await.unwrap();
+    let claims = decode_access_token(&access_token).unwrap();
+
+    assert!(claims.act.is_none());
+    assert!(!claims.is_impersonated());
+
+    let payload = access_token.split('.').nth(1).unwrap();
+    let payload = String::from_utf8(
+        base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, payload)
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(!payload.contains("\"act\""));
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_expose_actor_on_introspection() {
+    let (pool, user) = setup().await;
+    let admin_id = Uuid::new_v4();
+
+    let (_, claims) = issue_impersonation_token(&pool, &user, admin_id).await.unwrap();
+    let response = serde_json::to_value(IntrospectionResponse::active(claims)).unwrap();
+
+    assert_eq!(response["act"]["sub"], admin_id.to_string());
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+// A confidential client registered for token exchange, such as a support console
+async fn support_console(pool: &PgPool) -> (OAuthClient, String) {
+    let created = OAuthClient::register(CreateOAuthClientInput {
+        name: "Support Console".to_string(),
+        redirect_uris: vec![],
+        grant_types: Some(vec![TOKEN_EXCHANGE_GRANT.to_string()]),
+        confidential: true,
+        scopes: vec![],
+        audiences: vec![],
+    })
+    .unwrap();
+    create_oauth_client(pool, &created.client).await.unwrap();
+
+    (created.client, created.client_secret.unwrap())
+}
+
+// An admin and an access token of theirs
+async fn create_admin(pool: &PgPool) -> (User, String) {
+    let admin = create_test_account(pool, "Support Admin").await;
+    let role = get_role_by_name(pool, "Admin".to_string()).await.unwrap();
+    set_user_role(pool, admin.id, role.id).await.unwrap();
+
+    // Issued after the role change, which ends every token issued before it
+    let (access_token, _) = generate_tokens(pool, &admin).await.unwrap();
+
+    (admin, access_token)
+}
+
+async fn exchange(
+    client: &OAuthClient,
+    secret: &str,
+    params: &[(&str, &str)],
+) -> (StatusCode, serde_json::Value) {
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/token", post(token_handler))
+        .with_state(common::app_state(&dir).await);
+
+    let mut form = url::form_urlencoded::Serializer::new(String::new());
+    form.extend_pairs([
+        ("grant_type", TOKEN_EXCHANGE_GRANT),
+        ("client_id", client.client_id.as_str()),
+        ("client_secret", secret),
+    ]);
+    form.extend_pairs(params);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/token")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form.finish()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    remove_mail_dir(&dir);
+
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+// An admin's request to act as the user with the given id
+async fn impersonate(
+    client: &OAuthClient,
+    secret: &str,
+    user_id: Uuid,
+    actor_token: &str,
+) -> (StatusCode, serde_json::Value) {
+    let user_id = user_id.to_string();
+
+    exchange(
+        client,
+        secret,
+        &[
+            ("subject_token", &user_id),
+            ("subject_token_type", USER_ID_TOKEN_TYPE),
+            ("actor_token", actor_token),
+            ("actor_token_type", ACCESS_TOKEN_TYPE),
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn should_exchange_an_admin_token_for_a_token_of_the_user() {
+    let (pool, user) = setup().await;
+    let (admin, admin_token) = create_admin(&pool).await;
+    let (client, secret) = support_console(&pool).await;
+
+    let (status, body) = impersonate(&client, &secret, user.id, &admin_token).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["issued_token_type"], ACCESS_TOKEN_TYPE);
+    assert_eq!(body["token_type"], "Bearer");
+    assert_eq!(body["expires_in"], IMPERSONATION_TOKEN_TTL);
+    assert!(body.get("refresh_token").is_none());
+
+    let claims = decode_access_token(body["access_token"].as_str().unwrap()).unwrap();
+    assert_eq!(claims.sub, user.id);
+    assert_eq!(claims.act, Some(Actor { sub: admin.id }));
+
+    let events: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM security_events WHERE user_id = $1 AND event_type = 'impersonation'",
+    )
+    .bind(user.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(events, 1);
+
+    delete_oauth_client(&pool, &client.client_id).await.unwrap();
+    delete_user(&pool, admin.id).await.unwrap();
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_accept_an_access_token_of_the_user_as_subject() {
+    let (pool, user) = setup().await;
+    let (admin, admin_token) = create_admin(&pool).await;
+    let (client, secret) = support_console(&pool).await;
+    let (user_token, _) = generate_tokens(&pool, &user).await.unwrap();
+
+    let (status, body) = exchange(
+        &client,
+        &secret,
+        &[
+            ("subject_token", &user_token),
+            ("subject_token_type", ACCESS_TOKEN_TYPE),
+            ("actor_token", &admin_token),
+            ("actor_token_type", ACCESS_TOKEN_TYPE),
+        ],
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let claims = decode_access_token(body["access_token"].as_str().unwrap()).unwrap();
+    assert_eq!(claims.sub, user.id);
+    assert_eq!(claims.act, Some(Actor { sub: admin.id }));
+
+    delete_oauth_client(&pool, &client.client_id).await.unwrap();
+    delete_user(&pool, admin.id).await.unwrap();
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_only_let_admins_impersonate_non_admins() {
+    let (pool, user) = setup().await;
+    let (admin, admin_token) = create_admin(&pool).await;
+    let (other_admin, _) = create_admin(&pool).await;
+    let (client, secret) = support_console(&pool).await;
+    let (user_token, _) = generate_tokens(&pool, &user).await.unwrap();
+
+    // A regular user cannot act as someone else, nor can an admin as another admin
+    let attempts = [
+        (admin.id, user_token.as_str()),
+        (other_admin.id, admin_token.as_str()),
+        (user.id, "not-a-token"),
+    ];
+
+    for (subject, actor_token) in attempts {
+        let (status, body) = impersonate(&client, &secret, subject, actor_token).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_grant");
+    }
+
+    delete_oauth_client(&pool, &client.client_id).await.unwrap();
+    delete_user(&pool, other_admin.id).await.unwrap();
+    delete_user(&pool, admin.id).await.unwrap();
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_require_known_token_types() {
+    let (pool, user) = setup().await;
+    let (admin, admin_token) = create_admin(&pool).await;
+    let (client, secret) = support_console(&pool).await;
+    let user_id = user.id.to_string();
+
+    let (status, body) = exchange(
+        &client,
+        &secret,
+        &[
+            ("subject_token", &user_id),
+            ("subject_token_type", "urn:ietf:params:oauth:token-type:saml2"),
+            ("actor_token", &admin_token),
+            ("actor_token_type", ACCESS_TOKEN_TYPE),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_request");
+
+    // The actor is not optional: without one there is no admin to check
+    let (status, body) = exchange(
+        &client,
+        &secret,
+        &[("subject_token", &user_id), ("subject_token_type", USER_ID_TOKEN_TYPE)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_request");
+
+    delete_oauth_client(&pool, &client.client_id).await.unwrap();
+    delete_user(&pool, admin.id).await.unwrap();
+    delete_user(&pool, user.id).await.unwrap();
+}
@@ -0,0 +1,256 @@
+mod common;
+
+use common::TestClaims;
+use jsonwebtoken::{
+    Algorithm, Header, Validation, decode, encode,
+    jwk::{AlgorithmParameters, EllipticCurve},
+};
+use rust_auth_service::{
+    auth::keys::{KeyRing, SigningKey, generate_key_material, parse_algorithm},
+    models::{
+        auth::Claims,
+        signing_key::{KeyState, SigningKeyRecord},
+    },
+    services::key_encryption::{
+        decrypt_key_material, decrypt_with, encrypt_key_material, encryption_key, is_encrypted,
+    },
+};
+use uuid::Uuid;
+
+// Tokens are audience bound, the validation must name the audience
+fn validation(algorithm: Algorithm) -> Validation {
+    let mut validation = Validation::new(algorithm);
+    validation.set_audience(&["http://localhost:4000"]);
+
+    validation
+}
+
+fn assert_round_trip(key: &SigningKey) {
+    let claims = Claims::for_test(Uuid::new_v4());
+
+    let token = encode(&Header::new(key.algorithm), &claims, key.encoding_key()).unwrap();
+    let decoded = decode::<Claims>(&token, key.decoding_key(), &validation(key.algorithm))
+        .unwrap()
+        .claims;
+
+    assert_eq!(decoded.sub, claims.sub);
+    assert_eq!(decoded.jti, claims.jti);
+}
+
+#[test]
+fn should_sign_and_verify_with_rs256() {
+    let pem = include_bytes!("fixtures/rsa_private.pem");
+    let key = SigningKey::from_pem("test", Algorithm::RS256, pem).unwrap();
+
+    assert_round_trip(&key);
+
+    let jwk = key.public_jwk().expect("RS256 key should publish a JWK");
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(params) => {
+            assert!(!params.n.is_empty());
+            assert_eq!(params.e, "AQAB");
+        }
+        _ => panic!("Expected RSA parameters"),
+    }
+}
+
+#[test]
+fn should_sign_and_verify_with_es256() {
+    let pem = include_bytes!("fixtures/ec_private.pem");
+    let key = SigningKey::from_pem("test", Algorithm::ES256, pem).unwrap();
+
+    assert_round_trip(&key);
+
+    let jwk = key.public_jwk().expect("ES256 key should publish a JWK");
+    match &jwk.algorithm {
+        AlgorithmParameters::EllipticCurve(params) => {
+            assert_eq!(params.curve, EllipticCurve::P256);
+            assert_eq!(params.x.len(), 43);
+            assert_eq!(params.y.len(), 43);
+        }
+        _ => panic!("Expected EC parameters"),
+    }
+}
+
+#[test]
+fn should_sign_and_verify_with_eddsa() {
+    let pem = include_bytes!("fixtures/ed25519_private.pem");
+    let key = SigningKey::from_pem("test", Algorithm::EdDSA, pem).unwrap();
+
+    assert_round_trip(&key);
+
+    let jwk = key.public_jwk().expect("EdDSA key should publish a JWK");
+    match &jwk.algorithm {
+        AlgorithmParameters::OctetKeyPair(params) => {
+            assert_eq!(params.curve, EllipticCurve::Ed25519);
+            assert_eq!(params.x.len(), 43);
+        }
+        _ => panic!("Expected OKP parameters"),
+    }
+}
+
+#[test]
+fn should_not_publish_hs256_secret() {
+    let key = SigningKey::from_secret("test", b"test-secret-key-for-testing-only");
+
+    assert_round_trip(&key);
+    assert!(key.public_jwk().is_none());
+}
+
+#[test]
+fn should_not_expose_private_key_material_in_jwk() {
+    let pem = include_bytes!("fixtures/ec_private.pem");
+    let key = SigningKey::from_pem("test", Algorithm::ES256, pem).unwrap();
+
+    let json = serde_json::to_value(key.public_jwk().unwrap()).unwrap();
+
+    assert!(json.get("d").is_none());
+    assert_eq!(json["kty"], "EC");
+    assert_eq!(json["alg"], "ES256");
+    assert_eq!(json["use"], "sig");
+}
+
+#[test]
+fn should_reject_token_signed_by_another_key() {
+    let rsa_pem = include_bytes!("fixtures/rsa_private.pem");
+    let ec_pem = include_bytes!("fixtures/ec_private.pem");
+    let rsa = SigningKey::from_pem("rsa", Algorithm::RS256, rsa_pem).unwrap();
+    let ec = SigningKey::from_pem("ec", Algorithm::ES256, ec_pem).unwrap();
+
+    let token = encode(&Header::new(rsa.algorithm), &Claims::for_test(Uuid::new_v4()), rsa.encoding_key()).unwrap();
+
+    assert!(decode::<Claims>(&token, ec.decoding_key(), &validation(ec.algorithm)).is_err());
+}
+
+#[test]
+fn should_reject_mismatched_pem_and_algorithm() {
+    let pem = include_bytes!("fixtures/ed25519_private.pem");
+
+    assert!(SigningKey::from_pem("test", Algorithm::RS256, pem).is_err());
+    assert!(SigningKey::from_pem("test", Algorithm::HS256, pem).is_err());
+}
+
+#[test]
+fn should_parse_supported_algorithms() {
+    assert_eq!(parse_algorithm("HS256").unwrap(), Algorithm::HS256);
+    assert_eq!(parse_algorithm("RS256").unwrap(), Algorithm::RS256);
+    assert_eq!(parse_algorithm("ES256").unwrap(), Algorithm::ES256);
+    assert_eq!(parse_algorithm("EdDSA").unwrap(), Algorithm::EdDSA);
+    assert!(parse_algorithm("none").is_err());
+}
+
+fn generated_key(algorithm: &str, state: KeyState) -> SigningKey {
+    let material = generate_key_material(parse_algorithm(algorithm).unwrap()).unwrap();
+    let mut record = SigningKeyRecord::new(algorithm.to_string(), material);
+    record.state = state;
+
+    SigningKey::from_record(&record).unwrap()
+}
+
+#[test]
+fn should_load_generated_key_material() {
+    for algorithm in ["HS256", "ES256", "EdDSA"] {
+        let key = generated_key(algorithm, KeyState::Active);
+
+        assert_round_trip(&key);
+    }
+
+    assert!(generate_key_material(Algorithm::RS256).is_err());
+}
+
+#[test]
+fn should_encrypt_stored_key_material() {
+    common::init();
+    let material = generate_key_material(Algorithm::ES256).unwrap();
+
+    let stored = encrypt_key_material("kid-1", &material).unwrap();
+
+    assert!(is_encrypted(&stored));
+    assert!(!is_encrypted(&material));
+    assert!(!stored.contains("PRIVATE KEY"));
+    // A fresh nonce every time
+    assert_ne!(stored, encrypt_key_material("kid-1", &material).unwrap());
+    assert_eq!(decrypt_key_material("kid-1", &stored).unwrap(), material);
+}
+
+#[test]
+fn should_refuse_moved_or_tampered_key_material() {
+    common::init();
+    let material = generate_key_material(Algorithm::EdDSA).unwrap();
+    let stored = encrypt_key_material("kid-1", &material).unwrap();
+
+    assert!(decrypt_key_material("kid-2", &stored).is_err());
+    assert!(decrypt_with(&encryption_key(&[7; 32]).unwrap(), "kid-1", &stored).is_err());
+    assert!(decrypt_key_material("kid-1", &material).is_err());
+
+    let middle = stored.len() / 2;
+    let flipped = if &stored[middle..=middle] == "A" { "B" } else { "A" };
+    let tampered = format!("{}{}{}", &stored[..middle], flipped, &stored[middle + 1..]);
+    assert!(decrypt_key_material("kid-1", &tampered).is_err());
+
+    assert!(encryption_key(&[7; 16]).is_err());
+}
+
+#[test]
+fn should_publish_kid_in_jwk() {
+    let pem = include_bytes!("fixtures/ed25519_private.pem");
+    let key = SigningKey::from_pem("key-1", Algorithm::EdDSA, pem).unwrap();
+
+    assert_eq!(key.public_jwk().unwrap().common.key_id.as_deref(), Some("key-1"));
+}
+
+#[test]
+fn should_verify_tokens_signed_by_previous_key_after_rotation() {
+    let previous = generated_key("ES256", KeyState::VerifyOnly);
+    let previous_kid = previous.kid.clone();
+    let previous_token = encode(
+        &Header { kid: Some(previous.kid.clone()), ..Header::new(previous.algorithm) },
+        &Claims::for_test(Uuid::new_v4()),
+        previous.encoding_key(),
+    )
+    .unwrap();
+
+    let ring = KeyRing::new(vec![generated_key("ES256", KeyState::Active), previous]).unwrap();
+
+    assert_ne!(ring.active().kid, previous_kid);
+
+    let key = ring.verification_key(Some(&previous_kid)).unwrap();
+    assert!(decode::<Claims>(&previous_token, key.decoding_key(), &validation(key.algorithm)).is_ok());
+
+    assert_eq!(ring.jwks().keys.len(), 2);
+    assert!(ring.jwks().find(&previous_kid).is_some());
+}
+
+#[test]
+fn should_not_trust_retired_or_unknown_keys() {
+    let retired = generated_key("EdDSA", KeyState::Retired);
+    let retired_kid = retired.kid.clone();
+
+    let ring = KeyRing::new(vec![generated_key("EdDSA", KeyState::Active), retired]).unwrap();
+
+    assert!(ring.verification_key(Some(&retired_kid)).is_none());
+    assert!(ring.verification_key(Some("unknown")).is_none());
+    assert_eq!(ring.jwks().keys.len(), 1);
+}
+
+#[test]
+fn should_fall_back_to_active_key_for_tokens_without_kid() {
+    let active = generated_key("HS256", KeyState::Active);
+    let active_kid = active.kid.clone();
+
+    let ring = KeyRing::new(vec![active, generated_key("HS256", KeyState::VerifyOnly)]).unwrap();
+
+    assert_eq!(ring.verification_key(None).unwrap().kid, active_kid);
+}
+
+#[test]
+fn should_require_exactly_one_active_key() {
+    assert!(KeyRing::new(vec![generated_key("HS256", KeyState::VerifyOnly)]).is_err());
+    assert!(
+        KeyRing::new(vec![
+            generated_key("HS256", KeyState::Active),
+            generated_key("HS256", KeyState::Active),
+        ])
+        .is_err()
+    );
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+};
+use serde_json::json;
+use common::{
+    TestClaims, create_test_account, delete_test_user, mail_dir, remove_mail_dir, unique_email,
+};
+use rust_auth_service::{
+    auth::lockout::{LockoutPolicy, check_login_allowed, lockout_policy, record_login_failure},
+    db::{
+        auth::{create_session, get_session},
+        user::{get_user_status, mark_email_verified},
+    },
+    errors::my_error::MyError,
+    handlers::admin::{lock_user_handler, unlock_user_handler},
+    models::{
+        app::AppState,
+        auth::Claims,
+        session::Session,
+        user::{User, UserStatus},
+    },
+    services::client_info::ClientInfo,
+};
+use std::{
+    collections::HashMap,
+    net::Ipv6Addr,
+    path::{Path, PathBuf},
+};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+async fn setup() -> (AppState, User, PathBuf) {
+    let dir = mail_dir();
+    let app_state = common::app_state(&dir).await;
+    let user = create_test_account(&app_state.pool, "Locked User").await;
+
+    // Verifying activates the account; only an active one can be locked by an admin
+    mark_email_verified(&app_state.pool, user.id).await.unwrap();
+
+    (app_state, user, dir)
+}
+
+async fn teardown(app_state: &AppState, user: &User, dir: &Path) {
+    delete_test_user(&app_state.pool, user.id).await;
+    remove_mail_dir(dir);
+}
+
+// An address of its own, so tests do not share the per-IP count
+fn unique_ip() -> String {
+    Ipv6Addr::from(Uuid::new_v4().as_u128()).to_string()
+}
+
+async fn fail_until_locked(app_state: &AppState, email: &str) {
+    for _ in 0..lockout_policy().max_account_failures {
+        record_login_failure(app_state, email, None).await.unwrap();
+    }
+}
+
+#[test]
+fn should_lock_once_the_threshold_is_reached() {
+    let policy = LockoutPolicy::default();
+
+    assert!(!policy.locks_account(4));
+    assert!(policy.locks_account(5));
+    assert!(!policy.locks_ip(19));
+    assert!(policy.locks_ip(20));
+    assert_eq!(policy.window, 900);
+    assert_eq!(policy.duration, 900);
+}
+
+#[test]
+fn should_read_policy_from_variables() {
+    let vars = HashMap::from([
+        ("LOGIN_MAX_FAILURES", "3"),
+        ("LOGIN_MAX_IP_FAILURES", "50"),
+        ("LOGIN_FAILURE_WINDOW", "not-a-number"),
+        ("LOGIN_LOCKOUT_DURATION", "0"),
+    ]);
+
+    let policy = LockoutPolicy::from_vars(|name| vars.get(name).map(|value| value.to_string()));
+
+    assert_eq!(policy.max_account_failures, 3);
+    assert_eq!(policy.max_ip_failures, 50);
+    // Unusable values fall back to the defaults rather than disabling the lockout
+    assert_eq!(policy.window, LockoutPolicy::default().window);
+    assert_eq!(policy.duration, LockoutPolicy::default().duration);
+    assert!(policy.locks_account(3));
+}
+
+// Calls an admin route on the user's status as a fresh admin
+async fn admin_call(app_state: &AppState, action: &str, user: &User) -> StatusCode {
+    let app = Router::new()
+        .route("/users/{user_id}/lock", post(lock_user_handler))
+        .route("/users/{user_id}/unlock", post(unlock_user_handler))
+        .with_state(app_state.clone());
+    let mut request = Request::builder()
+        .method("POST")
+        .uri(format!("/users/{}/{}", user.id, action))
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "reason": "Suspected takeover" }).to_string()))
+        .unwrap();
+    request.extensions_mut().insert(Claims {
+        roles: vec!["Admin".to_string()],
+        ..Claims::for_test(Uuid::new_v4())
+    });
+
+    app.oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn should_lock_the_account_after_repeated_failures() {
+    let (app_state, user, dir) = setup().await;
+    let mut redis = app_state.redis.clone();
+
+    for _ in 1..lockout_policy().max_account_failures {
+        record_login_failure(&app_state, &user.email, None).await.unwrap();
+    }
+    assert!(check_login_allowed(&mut redis, &user.email, None).await.is_ok());
+
+    record_login_failure(&app_state, &user.email, None).await.unwrap();
+
+    // Refused whatever the password
+    assert!(matches!(
+        check_login_allowed(&mut redis, &user.email.to_uppercase(), None).await,
+        Err(MyError::TooManyRequests)
+    ));
+
+    teardown(&app_state, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_keep_the_owner_signed_in_when_someone_else_fails() {
+    let (app_state, user, dir) = setup().await;
+    let session = Session::new(user.id, ClientInfo::default());
+    create_session(&app_state.pool, &session).await.unwrap();
+
+    fail_until_locked(&app_state, &user.email).await;
+
+    // Only new logins are refused: the account and its sessions are left alone
+    assert_eq!(get_user_status(&app_state.pool, user.id).await.unwrap(), Some(UserStatus::Active));
+    assert!(get_session(&app_state.pool, session.id).await.unwrap().unwrap().revoked_at.is_none());
+
+    teardown(&app_state, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_lift_the_lockout_for_an_admin() {
+    let (app_state, user, dir) = setup().await;
+    let mut redis = app_state.redis.clone();
+
+    fail_until_locked(&app_state, &user.email).await;
+
+    assert_eq!(admin_call(&app_state, "unlock", &user).await, StatusCode::OK);
+    assert!(check_login_allowed(&mut redis, &user.email, None).await.is_ok());
+
+    teardown(&app_state, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_lock_and_unlock_the_account_status_for_an_admin() {
+    let (app_state, user, dir) = setup().await;
+    let session = Session::new(user.id, ClientInfo::default());
+    create_session(&app_state.pool, &session).await.unwrap();
+
+    assert_eq!(admin_call(&app_state, "lock", &user).await, StatusCode::OK);
+
+    // An admin's lock signs the user out everywhere
+    assert_eq!(get_user_status(&app_state.pool, user.id).await.unwrap(), Some(UserStatus::Locked));
+    assert!(get_session(&app_state.pool, session.id).await.unwrap().unwrap().revoked_at.is_some());
+    assert!(UserStatus::Locked.check_sign_in().is_err());
+
+    assert_eq!(admin_call(&app_state, "unlock", &user).await, StatusCode::OK);
+    assert_eq!(get_user_status(&app_state.pool, user.id).await.unwrap(), Some(UserStatus::Active));
+
+    teardown(&app_state, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_lock_out_a_client_ip_across_accounts() {
+    let dir = mail_dir();
+    let app_state = common::app_state(&dir).await;
+    let mut redis = app_state.redis.clone();
+    let ip = unique_ip();
+
+    // Each email stays below its own threshold
+    for _ in 0..lockout_policy().max_ip_failures {
+        record_login_failure(&app_state, &unique_email("guess"), Some(&ip)).await.unwrap();
+    }
+
+    let email = unique_email("someone");
+    assert!(matches!(
+        check_login_allowed(&mut redis, &email, Some(&ip)).await,
+        Err(MyError::TooManyRequests)
+    ));
+    assert!(check_login_allowed(&mut redis, &email, Some(&unique_ip())).await.is_ok());
+
+    remove_mail_dir(&dir);
+}
@@ -0,0 +1,700 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    extract::ConnectInfo,
+    routing::{get, post},
+};
+use common::{TEST_PASSWORD, create_test_user, delete_test_user, mail_dir, remove_mail_dir};
+use rust_auth_service::{
+    auth::{
+        auth::decode_token,
+        grants::{rotate_refresh_token, start_session},
+        revocation::{is_token_revoked, revoke_session_tokens, revoke_token},
+    },
+    db::{
+        auth::{create_session, get_refresh_token, get_session, get_user_sessions, revoke_session},
+        oauth::{
+            consume_authorization_code, create_authorization_code, create_oauth_client,
+            delete_oauth_client, get_authorization_code, get_oauth_client,
+        },
+        user::{get_user_account_by_id, get_user_by_id, mark_email_verified, set_user_status},
+    },
+    handlers::oauth::{
+        authorize_handler, authorize_page_handler, introspect_handler, revoke_handler, token_handler,
+    },
+    middleware::rate_limit::check_rate_limit,
+    models::{
+        oauth_client::{
+            AUTHORIZATION_CODE_GRANT, AuthorizationCode, CLIENT_CREDENTIALS_GRANT, CreateOAuthClientInput,
+            OAuthClient,
+        },
+        session::Session,
+        user::UserStatus,
+    },
+    services::{
+        client_info::ClientInfo, pkce::s256_challenge, token_hash::generate_opaque_token,
+    },
+};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+async fn setup() -> (PgPool, Uuid, OAuthClient) {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    let client = OAuthClient::register(CreateOAuthClientInput {
+        name: "Test App".to_string(),
+        redirect_uris: vec!["https://app.example.com/callback".to_string()],
+        grant_types: None,
+        confidential: false,
+        scopes: vec![],
+        audiences: vec![],
+    })
+    .unwrap()
+    .client;
+    create_oauth_client(&pool, &client).await.unwrap();
+
+    (pool, user.id, client)
+}
+
+async fn teardown(pool: &PgPool, user_id: Uuid, client: &OAuthClient) {
+    delete_oauth_client(pool, &client.client_id).await.unwrap();
+    delete_test_user(pool, user_id).await;
+}
+
+// The client's authorization request, as a urlencoded query or form
+fn authorization_params(client: &OAuthClient, extra: &[(&str, &str)]) -> String {
+    let challenge = s256_challenge(CODE_VERIFIER);
+    let mut params = url::form_urlencoded::Serializer::new(String::new());
+    params.extend_pairs([
+        ("response_type", "code"),
+        ("client_id", client.client_id.as_str()),
+        ("redirect_uri", client.redirect_uris[0].as_str()),
+        ("state", "xyz"),
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ]);
+    params.extend_pairs(extra);
+
+    params.finish()
+}
+
+struct Answer {
+    status: StatusCode,
+    location: Option<String>,
+    frame_options: Option<String>,
+    body: String,
+}
+
+async fn authorize(request: Request<Body>) -> Answer {
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/authorize", get(authorize_page_handler).post(authorize_handler))
+        .with_state(common::app_state(&dir).await);
+
+    let response = app.oneshot(request).await.unwrap();
+    let header = |name| response.headers().get(name).map(|value| value.to_str().unwrap().to_string());
+    let (location, frame_options) = (header(header::LOCATION), header(header::X_FRAME_OPTIONS));
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    remove_mail_dir(&dir);
+
+    Answer {
+        status,
+        location,
+        frame_options,
+        body: String::from_utf8(body.to_vec()).unwrap(),
+    }
+}
+
+async fn login_page(query: String) -> Answer {
+    authorize(Request::builder().uri(format!("/authorize?{}", query)).body(Body::empty()).unwrap()).await
+}
+
+async fn submit(form: String) -> Answer {
+    authorize(
+        Request::builder()
+            .method("POST")
+            .uri("/authorize")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn should_store_registered_client() {
+    let (pool, user_id, client) = setup().await;
+
+    let stored = get_oauth_client(&pool, &client.client_id).await.unwrap().unwrap();
+    assert_eq!(stored.redirect_uris, client.redirect_uris);
+    assert_eq!(stored.grant_types, client.grant_types);
+
+    assert!(get_oauth_client(&pool, "unknown-client").await.unwrap().is_none());
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_store_confidential_client_secret_hash() {
+    let (pool, user_id, client) = setup().await;
+
+    let created = OAuthClient::register(CreateOAuthClientInput {
+        name: "Report Worker".to_string(),
+        redirect_uris: vec![],
+        grant_types: Some(vec!["client_credentials".to_string()]),
+        confidential: true,
+        scopes: vec!["reports:read".to_string()],
+        audiences: vec![],
+    })
+    .unwrap();
+    create_oauth_client(&pool, &created.client).await.unwrap();
+
+    let stored = get_oauth_client(&pool, &created.client.client_id).await.unwrap().unwrap();
+    assert!(stored.verify_secret(&created.client_secret.unwrap()));
+    assert_eq!(stored.scopes, vec!["reports:read".to_string()]);
+
+    delete_oauth_client(&pool, &stored.client_id).await.unwrap();
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_exchange_authorization_code_only_once() {
+    let (pool, user_id, client) = setup().await;
+    let code = generate_opaque_token();
+
+    create_authorization_code(
+        &pool,
+        &AuthorizationCode::new(
+            &code,
+            client.client_id.clone(),
+            user_id,
+            client.redirect_uris[0].clone(),
+            s256_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+        ),
+    )
+    .await
+    .unwrap();
+
+    let stored = get_authorization_code(&pool, &code).await.unwrap().unwrap();
+    assert_ne!(stored.code_hash, code);
+    assert_eq!(stored.user_id, user_id);
+
+    assert!(consume_authorization_code(&pool, &stored.code_hash).await.unwrap());
+    assert!(!consume_authorization_code(&pool, &stored.code_hash).await.unwrap());
+
+    assert!(get_authorization_code(&pool, "unknown-code").await.unwrap().is_none());
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_end_client_sessions_when_client_is_deleted() {
+    let (pool, user_id, client) = setup().await;
+
+    let mut session = Session::new(user_id, ClientInfo::default());
+    session.client_id = Some(client.client_id.clone());
+    create_session(&pool, &session).await.unwrap();
+
+    let sessions = get_user_sessions(&pool, user_id).await.unwrap();
+    assert_eq!(sessions[0].client_id.as_deref(), Some(client.client_id.as_str()));
+
+    assert!(delete_oauth_client(&pool, &client.client_id).await.unwrap());
+    assert!(get_user_sessions(&pool, user_id).await.unwrap().is_empty());
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_show_the_login_page_for_a_valid_request() {
+    let (pool, user_id, client) = setup().await;
+
+    let query = authorization_params(&client, &[]).replace("state=xyz", "state=%22%3E%3Cscript%3E");
+    let page = login_page(query).await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    assert_eq!(page.frame_options.as_deref(), Some("DENY"));
+    assert!(page.body.contains("Test App"));
+    assert!(page.body.contains(r#"name="password""#));
+    assert!(page.body.contains(&format!(r#"name="client_id" value="{}""#, client.client_id)));
+    // The client's values are carried in the form, never as markup
+    assert!(page.body.contains("&quot;&gt;&lt;script&gt;"));
+    assert!(!page.body.contains("\"><script>"));
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_not_redirect_to_an_unregistered_uri() {
+    let (pool, user_id, client) = setup().await;
+
+    let query = authorization_params(&client, &[]).replace("app.example.com", "evil.example.com");
+    let page = login_page(query).await;
+
+    assert_eq!(page.status, StatusCode::BAD_REQUEST);
+    assert!(page.location.is_none());
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_send_request_errors_back_to_the_client() {
+    let (pool, user_id, client) = setup().await;
+
+    let query = authorization_params(&client, &[]).replace("S256", "plain");
+    let page = login_page(query).await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    let location = page.location.unwrap();
+    assert!(location.starts_with("https://app.example.com/callback?"));
+    assert!(location.contains("error=invalid_request"));
+    assert!(location.contains("state=xyz"));
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_refuse_openid_while_the_signing_key_is_shared() {
+    let (pool, user_id, client) = setup().await;
+
+    // The tests sign with HS256, so OpenID Connect is off
+    let page = login_page(authorization_params(&client, &[("scope", "openid")])).await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert!(page.location.unwrap().contains("error=invalid_scope"));
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_send_access_denied_when_the_user_declines() {
+    let (pool, user_id, client) = setup().await;
+
+    let answer = submit(authorization_params(&client, &[("decision", "deny")])).await;
+
+    assert_eq!(answer.status, StatusCode::SEE_OTHER);
+    assert!(answer.location.unwrap().contains("error=access_denied"));
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_show_the_login_page_again_after_wrong_credentials() {
+    let (pool, user_id, client) = setup().await;
+    let user = get_user_by_id(&pool, user_id).await.unwrap();
+
+    let answer = submit(authorization_params(
+        &client,
+        &[("email", &user.email), ("password", "Wrong-secret3"), ("decision", "allow")],
+    ))
+    .await;
+
+    assert_eq!(answer.status, StatusCode::UNAUTHORIZED);
+    assert!(answer.location.is_none());
+    assert!(answer.body.contains("Invalid username or password"));
+    assert!(answer.body.contains(&format!(r#"name="client_id" value="{}""#, client.client_id)));
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_redirect_with_a_code_after_signing_in() {
+    let (pool, user_id, client) = setup().await;
+    mark_email_verified(&pool, user_id).await.unwrap();
+    let user = get_user_by_id(&pool, user_id).await.unwrap();
+
+    let answer = submit(authorization_params(
+        &client,
+        &[("email", &user.email), ("password", TEST_PASSWORD), ("decision", "allow")],
+    ))
+    .await;
+
+    assert_eq!(answer.status, StatusCode::SEE_OTHER);
+    let location = url::Url::parse(&answer.location.unwrap()).unwrap();
+    let query: std::collections::HashMap<_, _> = location.query_pairs().into_owned().collect();
+    assert_eq!(query["state"], "xyz");
+
+    let stored = get_authorization_code(&pool, &query["code"]).await.unwrap().unwrap();
+    assert_eq!(stored.user_id, user_id);
+    assert_eq!(stored.client_id, client.client_id);
+
+    teardown(&pool, user_id, &client).await;
+}
+
+// A session the user opened through the client, with its token pair
+async fn client_session(pool: &PgPool, user_id: Uuid, client: &OAuthClient) -> (Session, String, String) {
+    let user = get_user_account_by_id(pool, user_id).await.unwrap().unwrap();
+    let mut session = Session::new(user_id, ClientInfo::default());
+    session.client_id = Some(client.client_id.clone());
+    let (access_token, refresh_token) = start_session(pool, &user, &session).await.unwrap();
+
+    (session, access_token, refresh_token)
+}
+
+async fn revoke(token: &str, client_id: &str) -> StatusCode {
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/revoke", post(revoke_handler))
+        .with_state(common::app_state(&dir).await);
+
+    let form = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs([("token", token), ("client_id", client_id)])
+        .finish();
+    let request = Request::builder()
+        .method("POST")
+        .uri("/revoke")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form))
+        .unwrap();
+
+    let status = app.oneshot(request).await.unwrap().status();
+    remove_mail_dir(&dir);
+
+    status
+}
+
+#[tokio::test]
+async fn should_end_the_session_when_its_refresh_token_is_revoked() {
+    let (pool, user_id, client) = setup().await;
+    let (session, _, refresh_token) = client_session(&pool, user_id, &client).await;
+
+    assert_eq!(revoke(&refresh_token, &client.client_id).await, StatusCode::OK);
+
+    assert!(get_session(&pool, session.id).await.unwrap().unwrap().revoked_at.is_some());
+    assert!(get_refresh_token(&pool, &refresh_token).await.unwrap().unwrap().revoked_at.is_some());
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_deny_a_revoked_access_token() {
+    let (pool, user_id, client) = setup().await;
+    let (session, access_token, _) = client_session(&pool, user_id, &client).await;
+    let claims = decode_token(&access_token, None).unwrap();
+
+    assert_eq!(revoke(&access_token, &client.client_id).await, StatusCode::OK);
+
+    assert!(is_token_revoked(&mut common::redis().await, &claims.jti).await.unwrap());
+    // Only the token itself, the session goes on
+    assert!(get_session(&pool, session.id).await.unwrap().unwrap().revoked_at.is_none());
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_answer_ok_for_unknown_tokens() {
+    let (pool, user_id, client) = setup().await;
+
+    assert_eq!(revoke("not-a-token", &client.client_id).await, StatusCode::OK);
+    assert_eq!(revoke(&generate_opaque_token(), &client.client_id).await, StatusCode::OK);
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_not_let_a_client_revoke_another_clients_token() {
+    let (pool, user_id, client) = setup().await;
+    let (session, access_token, refresh_token) = client_session(&pool, user_id, &client).await;
+    let claims = decode_token(&access_token, None).unwrap();
+
+    let other = OAuthClient::register(CreateOAuthClientInput {
+        name: "Other App".to_string(),
+        redirect_uris: vec!["https://other.example.com/callback".to_string()],
+        grant_types: None,
+        confidential: false,
+        scopes: vec![],
+        audiences: vec![],
+    })
+    .unwrap()
+    .client;
+    create_oauth_client(&pool, &other).await.unwrap();
+
+    // Answered like any other token, and left alone
+    assert_eq!(revoke(&access_token, &other.client_id).await, StatusCode::OK);
+    assert_eq!(revoke(&refresh_token, &other.client_id).await, StatusCode::OK);
+
+    assert!(!is_token_revoked(&mut common::redis().await, &claims.jti).await.unwrap());
+    assert!(get_session(&pool, session.id).await.unwrap().unwrap().revoked_at.is_none());
+    // An unknown client is not authenticated at all
+    assert_eq!(revoke(&access_token, "unknown-client").await, StatusCode::UNAUTHORIZED);
+
+    delete_oauth_client(&pool, &other.client_id).await.unwrap();
+    teardown(&pool, user_id, &client).await;
+}
+
+async fn introspect(token: &str) -> serde_json::Value {
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/introspect", post(introspect_handler))
+        .with_state(common::app_state(&dir).await);
+
+    let form = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("token", token)
+        .finish();
+    let request = Request::builder()
+        .method("POST")
+        .uri("/introspect")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    remove_mail_dir(&dir);
+
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn should_report_a_denylisted_token_inactive() {
+    let (pool, user_id, client) = setup().await;
+    let (_, access_token, _) = client_session(&pool, user_id, &client).await;
+
+    assert_eq!(introspect(&access_token).await["active"], true);
+
+    let claims = decode_token(&access_token, None).unwrap();
+    revoke_token(&mut common::redis().await, &claims).await.unwrap();
+
+    assert_eq!(introspect(&access_token).await, serde_json::json!({ "active": false }));
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_report_a_rotated_refresh_token_inactive() {
+    let (pool, user_id, client) = setup().await;
+    let (_, _, refresh_token) = client_session(&pool, user_id, &client).await;
+
+    let dir = mail_dir();
+    let app_state = common::app_state(&dir).await;
+    let (_, rotated) = rotate_refresh_token(&app_state, &refresh_token, Some(&client.client_id))
+        .await
+        .unwrap();
+    remove_mail_dir(&dir);
+
+    assert_eq!(introspect(&refresh_token).await, serde_json::json!({ "active": false }));
+    assert_eq!(introspect(&rotated).await["active"], true);
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_report_tokens_of_a_revoked_session_inactive() {
+    let (pool, user_id, client) = setup().await;
+    let (session, access_token, refresh_token) = client_session(&pool, user_id, &client).await;
+
+    assert!(revoke_session(&pool, user_id, session.id).await.unwrap());
+    revoke_session_tokens(&mut common::redis().await, &[session.id]).await.unwrap();
+
+    assert_eq!(introspect(&access_token).await, serde_json::json!({ "active": false }));
+    assert_eq!(introspect(&refresh_token).await, serde_json::json!({ "active": false }));
+
+    teardown(&pool, user_id, &client).await;
+}
+
+// A code issued to the client when the user signed in `age` ago
+async fn issue_code(
+    pool: &PgPool,
+    user_id: Uuid,
+    client: &OAuthClient,
+    age: chrono::Duration,
+) -> (String, AuthorizationCode) {
+    let code = generate_opaque_token();
+    let mut authorization_code = AuthorizationCode::new(
+        &code,
+        client.client_id.clone(),
+        user_id,
+        client.redirect_uris[0].clone(),
+        s256_challenge(CODE_VERIFIER),
+    );
+    authorization_code.created_at -= age;
+    create_authorization_code(pool, &authorization_code).await.unwrap();
+
+    (code, authorization_code)
+}
+
+async fn exchange_code(
+    client: &OAuthClient,
+    code: &str,
+    code_verifier: &str,
+) -> (StatusCode, serde_json::Value) {
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/token", post(token_handler))
+        .with_state(common::app_state(&dir).await);
+
+    let form = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs([
+            ("grant_type", AUTHORIZATION_CODE_GRANT),
+            ("client_id", client.client_id.as_str()),
+            ("code", code),
+            ("redirect_uri", client.redirect_uris[0].as_str()),
+            ("code_verifier", code_verifier),
+        ])
+        .finish();
+    let request = Request::builder()
+        .method("POST")
+        .uri("/token")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    remove_mail_dir(&dir);
+
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn should_report_the_sign_in_time_as_auth_time() {
+    let (pool, user_id, client) = setup().await;
+    mark_email_verified(&pool, user_id).await.unwrap();
+    let (code, issued) = issue_code(&pool, user_id, &client, chrono::Duration::minutes(2)).await;
+
+    let (status, body) = exchange_code(&client, &code, CODE_VERIFIER).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let claims = decode_token(body["access_token"].as_str().unwrap(), None).unwrap();
+    assert_eq!(claims.auth_time, Some(issued.created_at.timestamp() as usize));
+
+    // Refreshing keeps the time the user signed in
+    let dir = mail_dir();
+    let app_state = common::app_state(&dir).await;
+    let (access_token, _) =
+        rotate_refresh_token(&app_state, body["refresh_token"].as_str().unwrap(), Some(&client.client_id))
+            .await
+            .unwrap();
+    remove_mail_dir(&dir);
+    assert_eq!(decode_token(&access_token, None).unwrap().auth_time, claims.auth_time);
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_keep_the_code_after_a_wrong_verifier() {
+    let (pool, user_id, client) = setup().await;
+    mark_email_verified(&pool, user_id).await.unwrap();
+    let (code, _) = issue_code(&pool, user_id, &client, chrono::Duration::zero()).await;
+
+    let wrong_verifier = CODE_VERIFIER.replace('d', "e");
+    let (status, body) = exchange_code(&client, &code, &wrong_verifier).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_grant");
+
+    // Whoever sent the wrong verifier could not spend the code of the real client
+    assert!(get_authorization_code(&pool, &code).await.unwrap().unwrap().consumed_at.is_none());
+    assert_eq!(exchange_code(&client, &code, CODE_VERIFIER).await.0, StatusCode::OK);
+
+    teardown(&pool, user_id, &client).await;
+}
+
+#[tokio::test]
+async fn should_refuse_a_code_once_the_account_cannot_sign_in() {
+    let (pool, user_id, client) = setup().await;
+
+    let (code, _) = issue_code(&pool, user_id, &client, chrono::Duration::zero()).await;
+    let (status, body) = exchange_code(&client, &code, CODE_VERIFIER).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_grant");
+
+    mark_email_verified(&pool, user_id).await.unwrap();
+    let user = get_user_account_by_id(&pool, user_id).await.unwrap().unwrap();
+    assert!(set_user_status(&pool, user_id, user.status, UserStatus::Suspended, None).await.unwrap());
+
+    let (code, _) = issue_code(&pool, user_id, &client, chrono::Duration::zero()).await;
+    let (status, body) = exchange_code(&client, &code, CODE_VERIFIER).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_grant");
+    assert!(get_user_sessions(&pool, user_id).await.unwrap().is_empty());
+
+    teardown(&pool, user_id, &client).await;
+}
+
+// A client_credentials request from `peer`, answered with its status
+async fn client_credentials(client_id: &str, client_secret: &str, peer: SocketAddr) -> StatusCode {
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/token", post(token_handler))
+        .with_state(common::app_state(&dir).await);
+
+    let form = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs([
+            ("grant_type", CLIENT_CREDENTIALS_GRANT),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+        ])
+        .finish();
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/token")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo(peer));
+
+    let status = app.oneshot(request).await.unwrap().status();
+    remove_mail_dir(&dir);
+
+    status
+}
+
+#[tokio::test]
+async fn should_limit_the_token_endpoint_per_client() {
+    let pool = common::pool();
+    let registered = OAuthClient::register(CreateOAuthClientInput {
+        name: "Reports Service".to_string(),
+        redirect_uris: vec![],
+        grant_types: Some(vec![CLIENT_CREDENTIALS_GRANT.to_string()]),
+        confidential: true,
+        scopes: vec![],
+        audiences: vec![],
+    })
+    .unwrap();
+    let client = registered.client;
+    let secret = registered.client_secret.unwrap();
+    create_oauth_client(&pool, &client).await.unwrap();
+
+    // Services behind one gateway share its address
+    let gateway = SocketAddr::from(([10, 9, rand_octet(), rand_octet()], 443));
+    for _ in 0..15 {
+        assert_eq!(client_credentials(&client.client_id, &secret, gateway).await, StatusCode::OK);
+    }
+
+    // Guessing secrets is still limited per IP
+    let attacker = SocketAddr::from(([10, 8, rand_octet(), rand_octet()], 443));
+    for _ in 0..10 {
+        let status = client_credentials(&client.client_id, "wrong", attacker).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let status = client_credentials(&client.client_id, "wrong", attacker).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    delete_oauth_client(&pool, &client.client_id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_refuse_requests_past_the_limit() {
+    let mut redis_conn = common::redis().await;
+    let key = format!("rate_limit:test:{}", Uuid::new_v4());
+
+    for _ in 0..3 {
+        check_rate_limit(&mut redis_conn, &key, 3).await.unwrap();
+    }
+
+    assert!(check_rate_limit(&mut redis_conn, &key, 3).await.is_err());
+}
+
+fn rand_octet() -> u8 {
+    Uuid::new_v4().as_bytes()[0]
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use common::TestClaims;
+use rust_auth_service::{
+    auth::{
+        auth::{decode_access_token, issue_client_token},
+        oidc::resource_audience,
+    },
+    models::{
+        auth::{Claims, TokenType},
+        oauth::{IntrospectionResponse, RevocationRequest},
+        oauth_client::{CreateOAuthClientInput, OAuthClient, TOKEN_EXCHANGE_GRANT},
+    },
+    services::pkce::{is_valid_code_challenge, s256_challenge, verify_code_challenge},
+};
+use uuid::Uuid;
+
+fn claims(token_type: TokenType) -> Claims {
+    Claims {
+        token_type,
+        sid: Some(Uuid::new_v4()),
+        ..Claims::for_test(Uuid::new_v4())
+    }
+}
+
+#[test]
+fn should_only_expose_active_flag_for_inactive_tokens() {
+    let json = serde_json::to_value(IntrospectionResponse::inactive()).unwrap();
+
+    assert_eq!(json, serde_json::json!({ "active": false }));
+}
+
+#[test]
+fn should_describe_active_access_token() {
+    let claims = claims(TokenType::Access);
+    let json = serde_json::to_value(IntrospectionResponse::active(claims.clone())).unwrap();
+
+    assert_eq!(json["active"], true);
+    assert_eq!(json["sub"], claims.sub.to_string());
+    assert_eq!(json["username"], claims.email);
+    assert_eq!(json["roles"], serde_json::json!(["User"]));
+    assert_eq!(json["token_type"], "Bearer");
+    assert_eq!(json["exp"], claims.exp);
+    assert_eq!(json["jti"], claims.jti);
+}
+
+#[test]
+fn should_describe_active_refresh_token() {
+    let response = IntrospectionResponse::active(claims(TokenType::Refresh));
+
+    assert!(response.active);
+    assert_eq!(response.token_type.as_deref(), Some("Bearer"));
+}
+
+#[test]
+fn should_accept_revocation_request_without_hint() {
+    let request: RevocationRequest =
+        serde_json::from_value(serde_json::json!({ "token": "some-token" })).unwrap();
+
+    assert_eq!(request.token, "some-token");
+    assert!(request.token_type_hint.is_none());
+}
+
+// RFC 7636 Appendix B
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+#[test]
+fn should_compute_s256_challenge() {
+    assert_eq!(s256_challenge(VERIFIER), CHALLENGE);
+    assert!(is_valid_code_challenge(CHALLENGE));
+    assert!(verify_code_challenge(VERIFIER, CHALLENGE));
+}
+
+#[test]
+fn should_reject_invalid_code_verifiers() {
+    assert!(!verify_code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXj", CHALLENGE));
+    assert!(!verify_code_challenge("too-short", &s256_challenge("too-short")));
+
+    let invalid_chars = format!("{}!", &VERIFIER[..43]);
+    assert!(!verify_code_challenge(&invalid_chars, &s256_challenge(&invalid_chars)));
+
+    // Only a base64url encoded SHA-256 digest is an S256 challenge
+    assert!(!is_valid_code_challenge("plain-challenge"));
+    assert!(!is_valid_code_challenge(&format!("{}=", CHALLENGE)));
+}
+
+fn client_input(redirect_uris: &[&str], grant_types: Option<&[&str]>) -> CreateOAuthClientInput {
+    CreateOAuthClientInput {
+        name: "Test App".to_string(),
+        redirect_uris: redirect_uris.iter().map(|uri| uri.to_string()).collect(),
+        grant_types: grant_types.map(|grants| grants.iter().map(|grant| grant.to_string()).collect()),
+        confidential: false,
+        scopes: vec![],
+        audiences: vec![],
+    }
+}
+
+fn service_client(scopes: &[&str]) -> CreateOAuthClientInput {
+    common::init();
+
+    CreateOAuthClientInput {
+        confidential: true,
+        scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        ..client_input(&[], Some(&["client_credentials"]))
+    }
+}
+
+#[test]
+fn should_register_client_with_default_grants() {
+    let created = OAuthClient::register(client_input(&["https://app.example.com/callback"], None)).unwrap();
+    let client = created.client;
+
+    assert!(created.client_secret.is_none());
+    assert!(!client.is_confidential());
+    assert!(client.allows_grant("authorization_code"));
+    assert!(client.allows_grant("refresh_token"));
+    assert!(!client.allows_grant("client_credentials"));
+    assert!(!client.allows_grant("password"));
+}
+
+#[test]
+fn should_match_redirect_uris_exactly() {
+    let client = OAuthClient::register(client_input(&["https://app.example.com/callback"], None))
+        .unwrap()
+        .client;
+
+    assert!(client.allows_redirect_uri("https://app.example.com/callback"));
+    assert!(!client.allows_redirect_uri("https://app.example.com/callback/"));
+    assert!(!client.allows_redirect_uri("https://app.example.com/callback?next=evil"));
+    assert!(!client.allows_redirect_uri("https://evil.example.com/callback"));
+}
+
+#[test]
+fn should_reject_invalid_client_registrations() {
+    assert!(OAuthClient::register(client_input(&["/relative/callback"], None)).is_err());
+    assert!(OAuthClient::register(client_input(&["https://app.example.com/cb#fragment"], None)).is_err());
+    assert!(OAuthClient::register(client_input(&["https://app.example.com/cb"], Some(&["password"]))).is_err());
+    assert!(OAuthClient::register(client_input(&[], None)).is_err());
+
+    // Public clients cannot use client_credentials or token exchange, and scopes must be valid tokens
+    assert!(OAuthClient::register(client_input(&[], Some(&["client_credentials"]))).is_err());
+    assert!(OAuthClient::register(client_input(&[], Some(&[TOKEN_EXCHANGE_GRANT]))).is_err());
+    assert!(OAuthClient::register(service_client(&["reports read"])).is_err());
+    assert!(OAuthClient::register(service_client(&["reports:\"read\""])).is_err());
+}
+
+#[test]
+fn should_issue_secret_only_to_confidential_clients() {
+    let created = OAuthClient::register(service_client(&["reports:read"])).unwrap();
+    let secret = created.client_secret.expect("Confidential clients get a secret");
+    let client = created.client;
+
+    assert!(client.is_confidential());
+    assert!(client.verify_secret(&secret));
+    assert!(!client.verify_secret("wrong-secret"));
+
+    // Only the hash is kept, and it is never serialized
+    assert_ne!(client.client_secret_hash.as_deref(), Some(secret.as_str()));
+    let json = serde_json::to_value(&client).unwrap();
+    assert!(json.get("client_secret_hash").is_none());
+}
+
+#[test]
+fn should_grant_only_assigned_scopes() {
+    let client = OAuthClient::register(service_client(&["reports:read", "reports:write"]))
+        .unwrap()
+        .client;
+
+    assert_eq!(client.grant_scopes(None).unwrap(), "reports:read reports:write");
+    assert_eq!(client.grant_scopes(Some("reports:read")).unwrap(), "reports:read");
+    assert!(client.grant_scopes(Some("reports:read users:delete")).is_err());
+}
+
+#[test]
+fn should_issue_machine_token_for_client() {
+    common::init();
+    let client = OAuthClient::register(service_client(&["reports:read"])).unwrap().client;
+
+    let token = issue_client_token(&client, "reports:read", resource_audience()).unwrap();
+    let claims = decode_access_token(&token).unwrap();
+
+    assert!(claims.machine);
+    assert_eq!(claims.token_type, TokenType::Access);
+    assert_eq!(claims.sub.simple().to_string(), client.client_id);
+    assert_eq!(claims.client_id.as_deref(), Some(client.client_id.as_str()));
+    assert_eq!(claims.scope.as_deref(), Some("reports:read"));
+    assert!(claims.roles.is_empty());
+
+    let introspection = serde_json::to_value(IntrospectionResponse::active(claims)).unwrap();
+    assert!(introspection.get("username").is_none());
+    assert_eq!(introspection["scope"], "reports:read");
+}
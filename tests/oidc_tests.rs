@@ -0,0 +1,222 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode},
+    routing::get,
+};
+use common::{JWT_SECRET, TestClaims, create_test_user, delete_test_user, mail_dir, remove_mail_dir};
+use jsonwebtoken::{Algorithm, Validation, decode};
+use rust_auth_service::{
+    auth::{
+        keys::{KeyRing, SigningKey, generate_key_material},
+        oidc::{IdTokenContext, issue_id_token, issuer, openid_configuration},
+    },
+    handlers::oauth::userinfo_handler,
+    models::{
+        auth::Claims,
+        oauth_client::{CreateOAuthClientInput, OAuthClient},
+        oidc::{IdTokenClaims, has_scope},
+        signing_key::SigningKeyRecord,
+        user::{User, UserStatus},
+    },
+};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn test_user() -> User {
+    User {
+        id: Uuid::new_v4(),
+        name: "Test User".to_string(),
+        email: "test@example.com".to_string(),
+        password: String::new(),
+        email_verified_at: None,
+        status: UserStatus::Active,
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+    }
+}
+
+fn es256_key() -> SigningKey {
+    let material = generate_key_material(Algorithm::ES256).unwrap();
+
+    SigningKey::from_record(&SigningKeyRecord::new("ES256".to_string(), material)).unwrap()
+}
+
+fn decode_id_token(key: &SigningKey, token: &str, audience: &str) -> IdTokenClaims {
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.set_audience(&[audience]);
+
+    decode::<IdTokenClaims>(token, key.decoding_key(), &validation).unwrap().claims
+}
+
+#[test]
+fn should_issue_id_token_for_client() {
+    common::init();
+    let key = es256_key();
+    let user = test_user();
+    let auth_time = chrono::Utc::now().timestamp() as usize - 30;
+
+    let context = IdTokenContext {
+        client_id: "wiki",
+        scope: "openid profile email",
+        auth_time,
+        nonce: Some("n-0S6_WzA2Mj"),
+        session_id: None,
+    };
+    let claims = decode_id_token(&key, &issue_id_token(&key, &user, &context).unwrap(), "wiki");
+
+    assert_eq!(claims.iss, issuer());
+    assert_eq!(claims.sub, user.id);
+    assert_eq!(claims.aud, "wiki");
+    assert_eq!(claims.auth_time, auth_time);
+    assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+    assert_eq!(claims.name.as_deref(), Some("Test User"));
+    assert_eq!(claims.email.as_deref(), Some("test@example.com"));
+}
+
+#[test]
+fn should_only_include_claims_of_granted_scopes() {
+    common::init();
+    let key = es256_key();
+    let user = test_user();
+
+    let context = IdTokenContext {
+        client_id: "dashboard",
+        scope: "openid",
+        auth_time: chrono::Utc::now().timestamp() as usize,
+        nonce: None,
+        session_id: None,
+    };
+    let token = issue_id_token(&key, &user, &context).unwrap();
+    let claims = decode_id_token(&key, &token, "dashboard");
+
+    assert!(claims.name.is_none());
+    assert!(claims.email.is_none());
+    assert!(claims.nonce.is_none());
+
+    // An ID token is only valid for the client it was issued to
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.set_audience(&["someone-else"]);
+    assert!(decode::<IdTokenClaims>(&token, key.decoding_key(), &validation).is_err());
+}
+
+#[test]
+fn should_refuse_id_tokens_signed_with_a_shared_secret() {
+    common::init();
+    let key = SigningKey::from_secret("primary", JWT_SECRET.as_bytes());
+
+    let context = IdTokenContext {
+        client_id: "wiki",
+        scope: "openid",
+        auth_time: chrono::Utc::now().timestamp() as usize,
+        nonce: None,
+        session_id: None,
+    };
+
+    // Relying parties never hold the secret, so they could not verify the token
+    assert!(issue_id_token(&key, &test_user(), &context).is_err());
+    assert!(openid_configuration(&KeyRing::new(vec![key]).unwrap()).is_none());
+}
+
+#[test]
+fn should_publish_discovery_document() {
+    common::init();
+    let configuration = openid_configuration(&KeyRing::new(vec![es256_key()]).unwrap()).unwrap();
+
+    assert_eq!(configuration.issuer, issuer());
+    assert_eq!(configuration.token_endpoint, format!("{}/api/oauth/token", issuer()));
+    assert_eq!(configuration.jwks_uri, format!("{}/.well-known/jwks.json", issuer()));
+    assert!(configuration.scopes_supported.contains(&"openid".to_string()));
+    assert_eq!(configuration.code_challenge_methods_supported, vec!["S256".to_string()]);
+    assert_eq!(configuration.id_token_signing_alg_values_supported, vec!["ES256".to_string()]);
+}
+
+#[test]
+fn should_allow_oidc_and_assigned_scopes_at_authorization() {
+    common::init();
+    let client = OAuthClient::register(CreateOAuthClientInput {
+        name: "Wiki".to_string(),
+        redirect_uris: vec!["https://wiki.example.com/callback".to_string()],
+        grant_types: None,
+        confidential: false,
+        scopes: vec!["pages:write".to_string()],
+        audiences: vec![],
+    })
+    .unwrap()
+    .client;
+
+    assert_eq!(client.authorize_scopes("openid  email").unwrap(), "openid email");
+    assert!(client.authorize_scopes("openid pages:write").is_ok());
+    assert!(client.authorize_scopes("openid admin").is_err());
+
+    assert!(has_scope("openid profile", "profile"));
+    assert!(!has_scope("openid profile", "email"));
+}
+
+async fn userinfo(claims: Claims) -> (StatusCode, serde_json::Value) {
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/", get(userinfo_handler))
+        .with_state(common::app_state(&dir).await);
+
+    let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+    request.extensions_mut().insert(claims);
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    remove_mail_dir(&dir);
+
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn should_return_every_claim_to_a_direct_login() {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    let (status, body) = userinfo(Claims::for_test(user.id)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], "Test User");
+    assert_eq!(body["email"], user.email);
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_return_only_granted_claims_to_a_client() {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    let claims = Claims {
+        client_id: Some("test-client".to_string()),
+        scope: Some("openid email".to_string()),
+        ..Claims::for_test(user.id)
+    };
+    let (status, body) = userinfo(claims).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["email"], user.email);
+    assert!(body.get("name").is_none_or(|name| name.is_null()));
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_reject_a_client_token_without_scope() {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    let claims = Claims {
+        client_id: Some("test-client".to_string()),
+        ..Claims::for_test(user.id)
+    };
+    let (status, _) = userinfo(claims).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    delete_test_user(&pool, user.id).await;
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{TEST_PASSWORD, create_test_user, delete_test_user, mail_dir, remove_mail_dir};
+use rust_auth_service::{
+    auth::lockout::lockout_policy,
+    db::user::get_user_by_email,
+    errors::my_error::MyError,
+    models::{app::AppState, user::UserOutput},
+    services::{password::verify_password, password_change::change_password},
+};
+use sqlx::PgPool;
+use std::path::PathBuf;
+
+async fn setup() -> (AppState, PgPool, UserOutput, PathBuf) {
+    let dir = mail_dir();
+    let app_state = common::app_state(&dir).await;
+    let pool = app_state.pool.clone();
+    let user = create_test_user(&pool, "Change User").await;
+
+    (app_state, pool, user, dir)
+}
+
+async fn stored_password(pool: &PgPool, user: &UserOutput) -> String {
+    get_user_by_email(pool, user.email.clone()).await.unwrap().unwrap().password
+}
+
+#[tokio::test]
+async fn should_change_password_given_the_current_one() {
+    let (app_state, pool, user, _) = setup().await;
+
+    change_password(&app_state, user.id, TEST_PASSWORD, "New-secret2", None).await.unwrap();
+
+    let hash = stored_password(&pool, &user).await;
+    assert!(verify_password(&hash, "New-secret2").unwrap());
+    assert!(!verify_password(&hash, TEST_PASSWORD).unwrap());
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_reject_wrong_current_password() {
+    let (app_state, pool, user, _) = setup().await;
+
+    let result = change_password(&app_state, user.id, "Wrong-secret3", "New-secret2", None).await;
+    assert!(matches!(result, Err(MyError::LoginError(_))));
+
+    assert!(verify_password(&stored_password(&pool, &user).await, TEST_PASSWORD).unwrap());
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_reject_weak_or_unchanged_password() {
+    let (app_state, pool, user, _) = setup().await;
+
+    assert!(matches!(
+        change_password(&app_state, user.id, TEST_PASSWORD, "   ", None).await,
+        Err(MyError::PasswordPolicy(_))
+    ));
+    // Contains the user's name
+    assert!(matches!(
+        change_password(&app_state, user.id, TEST_PASSWORD, "Change-User-2", None).await,
+        Err(MyError::PasswordPolicy(_))
+    ));
+    assert!(matches!(
+        change_password(&app_state, user.id, TEST_PASSWORD, TEST_PASSWORD, None).await,
+        Err(MyError::Validation(_))
+    ));
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_lock_out_after_repeated_wrong_current_passwords() {
+    let (app_state, pool, user, dir) = setup().await;
+
+    for _ in 0..lockout_policy().max_account_failures {
+        let result = change_password(&app_state, user.id, "Wrong-secret3", "New-secret2", None).await;
+        assert!(matches!(result, Err(MyError::LoginError(_))));
+    }
+
+    // Locked now, so not even the right password gets through
+    let result = change_password(&app_state, user.id, TEST_PASSWORD, "New-secret2", None).await;
+    assert!(matches!(result, Err(MyError::TooManyRequests)));
+    assert!(verify_password(&stored_password(&pool, &user).await, TEST_PASSWORD).unwrap());
+
+    delete_test_user(&pool, user.id).await;
+    remove_mail_dir(&dir);
+}
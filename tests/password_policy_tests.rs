@@ -0,0 +1,83 @@
+use rust_auth_service::{
+    errors::my_error::MyError,
+    services::password_policy::{PasswordPolicy, PasswordViolation},
+};
+use std::collections::HashMap;
+
+#[test]
+fn should_accept_password_meeting_the_default_policy() {
+    let policy = PasswordPolicy::default();
+
+    assert!(policy.violations("Correct-horse-7", &["Ana Silva", "ana.silva@example.com"]).is_empty());
+    // Spaces count like any other character
+    assert!(policy.violations(" Tr0ub4dor ", &[]).is_empty());
+}
+
+#[test]
+fn should_list_every_violation() {
+    let policy = PasswordPolicy::default();
+
+    assert_eq!(
+        policy.violations("abc", &[]),
+        vec![
+            PasswordViolation::TooShort { min: 8 },
+            PasswordViolation::MissingUppercase,
+            PasswordViolation::MissingDigit,
+        ]
+    );
+    assert_eq!(
+        policy.violations(&format!("Aa1{}", "x".repeat(126)), &[]),
+        vec![PasswordViolation::TooLong { max: 128 }]
+    );
+    assert_eq!(policy.violations("MyPassword1", &[]), vec![PasswordViolation::ContainsBannedWord]);
+}
+
+#[test]
+fn should_reject_personal_info_but_not_email_domains() {
+    let policy = PasswordPolicy::default();
+    let personal = ["Ana Silva", "ana.silva@example.com"];
+
+    assert_eq!(policy.violations("Silva-2024", &personal), vec![PasswordViolation::ContainsPersonalInfo]);
+    assert_eq!(policy.violations("ANA.SILVA-1x", &personal), vec![PasswordViolation::ContainsPersonalInfo]);
+    assert!(policy.violations("Example-2024", &personal).is_empty());
+}
+
+#[test]
+fn should_return_machine_readable_violations() {
+    let policy = PasswordPolicy { require_symbol: true, ..PasswordPolicy::default() };
+
+    let Err(MyError::PasswordPolicy(violations)) = policy.check("short", &[]) else {
+        panic!("expected a password policy error");
+    };
+
+    assert_eq!(
+        serde_json::to_value(&violations).unwrap(),
+        serde_json::json!([
+            {"code": "too_short", "min": 8},
+            {"code": "missing_uppercase"},
+            {"code": "missing_digit"},
+            {"code": "missing_symbol"},
+        ])
+    );
+}
+
+#[test]
+fn should_read_policy_from_variables() {
+    let vars = HashMap::from([
+        ("PASSWORD_MIN_LENGTH", "14"),
+        ("PASSWORD_MAX_LENGTH", "10"),
+        ("PASSWORD_REQUIRE_UPPERCASE", "false"),
+        ("PASSWORD_REQUIRE_SYMBOL", "true"),
+        ("PASSWORD_BANNED_WORDS", " Acme, , letmein "),
+    ]);
+
+    let policy = PasswordPolicy::from_vars(|name| vars.get(name).map(|value| value.to_string()));
+
+    assert_eq!(policy.min_length, 14);
+    // A maximum below the minimum would reject every password
+    assert_eq!(policy.max_length, 14);
+    assert!(!policy.require_uppercase);
+    assert!(policy.require_symbol);
+    assert!(policy.require_lowercase);
+    assert_eq!(policy.banned_words, vec!["acme".to_string(), "letmein".to_string()]);
+}
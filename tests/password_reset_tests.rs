@@ -0,0 +1,178 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    routing::post,
+};
+use common::{
+    TEST_PASSWORD, create_test_user, delete_test_user, latest_token, mail_dir, remove_mail_dir,
+    unique_email, wait_for_emails,
+};
+use rust_auth_service::{
+    db::user::{get_user_by_email, set_user_status},
+    errors::my_error::MyError,
+    handlers::password::forgot_password_handler,
+    models::{
+        app::AppState,
+        user::{UserOutput, UserStatus},
+    },
+    services::{
+        email_verification::verify_email,
+        mailer::FileMailer,
+        password::verify_password,
+        password_reset::{reset_password, send_password_reset_email},
+    },
+};
+use sqlx::PgPool;
+use std::path::{Path, PathBuf};
+use tower::ServiceExt;
+
+async fn setup() -> (PgPool, UserOutput, PathBuf) {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Reset User").await;
+
+    (pool, user, mail_dir())
+}
+
+async fn teardown(pool: &PgPool, user: &UserOutput, dir: &Path) {
+    delete_test_user(pool, user.id).await;
+    remove_mail_dir(dir);
+}
+
+async fn forgot_password(app_state: AppState, email: &str) -> (StatusCode, String) {
+    let app = Router::new()
+        .route("/", post(forgot_password_handler))
+        .with_state(app_state);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::json!({ "email": email }).to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn should_reset_password_once() {
+    let (pool, user, dir) = setup().await;
+    let mailer = FileMailer::new(&dir);
+
+    send_password_reset_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    let token = latest_token(&dir);
+
+    assert_eq!(reset_password(&pool, &token, "New-secret2").await.unwrap(), user.id);
+
+    let stored = get_user_by_email(&pool, user.email.clone()).await.unwrap().unwrap();
+    assert!(verify_password(&stored.password, "New-secret2").unwrap());
+    assert!(!verify_password(&stored.password, TEST_PASSWORD).unwrap());
+    assert!(stored.email_verified_at.is_some());
+
+    // The link is single-use
+    assert!(reset_password(&pool, &token, "Another-secret3").await.is_err());
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_invalidate_earlier_link_on_new_request() {
+    let (pool, user, dir) = setup().await;
+    let mailer = FileMailer::new(&dir);
+
+    send_password_reset_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    let first = latest_token(&dir);
+
+    send_password_reset_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    let second = latest_token(&dir);
+
+    assert!(reset_password(&pool, &first, "New-secret2").await.is_err());
+    assert!(reset_password(&pool, &second, "New-secret2").await.is_ok());
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_not_accept_tokens_meant_for_something_else() {
+    let (pool, user, dir) = setup().await;
+    let mailer = FileMailer::new(&dir);
+
+    send_password_reset_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    let token = latest_token(&dir);
+
+    // A reset link is no verification link, and a weak password is refused before
+    // the token is spent
+    assert!(verify_email(&pool, &token).await.is_err());
+    assert!(matches!(
+        reset_password(&pool, &token, "short").await,
+        Err(MyError::PasswordPolicy(_))
+    ));
+    assert!(reset_password(&pool, &token, "New-secret2").await.is_ok());
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_mail_the_reset_link_in_the_background() {
+    let (pool, user, dir) = setup().await;
+
+    let (status, _) = forgot_password(common::app_state(&dir).await, &user.email).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let sent = wait_for_emails(&dir, 1).await;
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].contains(&user.email));
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_answer_forgot_password_alike_for_unknown_emails() {
+    let (pool, user, dir) = setup().await;
+
+    // Even a mail server that is down must not set a registered address apart
+    let known = forgot_password(common::app_state_without_mail().await, &user.email).await;
+    let unknown = forgot_password(common::app_state_without_mail().await, &unique_email("nobody")).await;
+
+    assert_eq!(known.0, StatusCode::OK);
+    assert_eq!(known, unknown);
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_not_mail_a_reset_link_to_a_closed_account() {
+    let (pool, user, dir) = setup().await;
+    set_user_status(&pool, user.id, UserStatus::Pending, UserStatus::Suspended, None)
+        .await
+        .unwrap();
+
+    let (status, _) = forgot_password(common::app_state(&dir).await, &user.email).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert!(wait_for_emails(&dir, 1).await.is_empty());
+
+    teardown(&pool, &user, &dir).await;
+}
+
+#[tokio::test]
+async fn should_refuse_a_reset_link_once_the_account_is_closed() {
+    let (pool, user, dir) = setup().await;
+    let mailer = FileMailer::new(&dir);
+
+    send_password_reset_email(&pool, &mailer, user.id, &user.email).await.unwrap();
+    set_user_status(&pool, user.id, UserStatus::Pending, UserStatus::Deleted, None)
+        .await
+        .unwrap();
+
+    let result = reset_password(&pool, &latest_token(&dir), "New-secret42").await;
+    assert!(matches!(result, Err(MyError::Validation(_))));
+
+    teardown(&pool, &user, &dir).await;
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{create_test_account, create_test_user, delete_test_user, mail_dir};
+use rust_auth_service::{
+    auth::{
+        auth::REFRESH_TOKEN_TTL,
+        grants::{rotate_refresh_token, start_session},
+    },
+    db::auth::{
+        consume_refresh_token, create_refresh_token, create_session, get_refresh_token,
+        get_session, revoke_token_family,
+    },
+    models::{auth::RefreshToken, session::Session},
+    services::{client_info::ClientInfo, token_hash::hash_token},
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn setup() -> (PgPool, Uuid) {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    (pool, user.id)
+}
+
+async fn new_family(pool: &PgPool, user_id: Uuid) -> Uuid {
+    let session = Session::new(user_id, ClientInfo::default());
+    create_session(pool, &session).await.unwrap();
+
+    session.id
+}
+
+#[tokio::test]
+async fn should_consume_refresh_token_only_once() {
+    let (pool, user_id) = setup().await;
+    let token = format!("token-{}", Uuid::new_v4());
+
+    let family_id = new_family(&pool, user_id).await;
+
+    create_refresh_token(&pool, user_id, family_id, &token).await.unwrap();
+    let stored = get_refresh_token(&pool, &token).await.unwrap().unwrap();
+
+    assert!(consume_refresh_token(&pool, stored.id).await.unwrap());
+    assert!(!consume_refresh_token(&pool, stored.id).await.unwrap());
+
+    let stored = get_refresh_token(&pool, &token).await.unwrap().unwrap();
+    assert!(stored.consumed_at.is_some());
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_revoke_every_token_of_a_family() {
+    let (pool, user_id) = setup().await;
+    let family_id = new_family(&pool, user_id).await;
+    let other_family_id = new_family(&pool, user_id).await;
+    let first = format!("token-{}", Uuid::new_v4());
+    let second = format!("token-{}", Uuid::new_v4());
+    let other = format!("token-{}", Uuid::new_v4());
+
+    create_refresh_token(&pool, user_id, family_id, &first).await.unwrap();
+    create_refresh_token(&pool, user_id, family_id, &second).await.unwrap();
+    create_refresh_token(&pool, user_id, other_family_id, &other).await.unwrap();
+
+    revoke_token_family(&pool, family_id).await.unwrap();
+
+    let first = get_refresh_token(&pool, &first).await.unwrap().unwrap();
+    let second = get_refresh_token(&pool, &second).await.unwrap().unwrap();
+    let other = get_refresh_token(&pool, &other).await.unwrap().unwrap();
+
+    assert!(first.revoked_at.is_some());
+    assert!(second.revoked_at.is_some());
+    assert!(other.revoked_at.is_none());
+
+    // A revoked token can no longer be exchanged
+    assert!(!consume_refresh_token(&pool, second.id).await.unwrap());
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_store_only_the_token_hash() {
+    let (pool, user_id) = setup().await;
+    let family_id = new_family(&pool, user_id).await;
+    let token = format!("token-{}", Uuid::new_v4());
+
+    create_refresh_token(&pool, user_id, family_id, &token).await.unwrap();
+
+    let stored = get_refresh_token(&pool, &token).await.unwrap().unwrap();
+    assert_ne!(stored.token_hash, token);
+    assert_eq!(stored.token_hash, hash_token(&token));
+
+    let stored_tokens: Vec<String> =
+        sqlx::query_scalar("SELECT token_hash FROM refresh_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert!(stored_tokens.iter().all(|stored| !stored.contains(&token)));
+
+    assert!(get_refresh_token(&pool, "unknown-token").await.unwrap().is_none());
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_revoke_the_family_when_a_rotated_token_is_replayed() {
+    let app_state = common::app_state(&mail_dir()).await;
+    let pool = app_state.pool.clone();
+    let user = create_test_account(&pool, "Test User").await;
+
+    let session = Session::new(user.id, ClientInfo::default());
+    let (_, first) = start_session(&pool, &user, &session).await.unwrap();
+    let (_, second) = rotate_refresh_token(&app_state, &first, None).await.unwrap();
+
+    // The legitimate client already rotated; whoever presents the old token is an attacker
+    assert!(rotate_refresh_token(&app_state, &first, None).await.is_err());
+
+    let session = get_session(&pool, session.id).await.unwrap().unwrap();
+    assert!(session.revoked_at.is_some());
+    assert!(get_refresh_token(&pool, &second).await.unwrap().unwrap().revoked_at.is_some());
+    assert!(rotate_refresh_token(&app_state, &second, None).await.is_err());
+
+    let reuse_events: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM security_events WHERE user_id = $1 AND event_type = 'refresh_token_reuse'",
+    )
+    .bind(user.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(reuse_events, 1);
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[test]
+fn should_expire_stored_token_with_the_jwt() {
+    common::init();
+
+    let stored = RefreshToken::new("some-token", Uuid::new_v4(), Uuid::new_v4());
+    let lifetime = stored.expires_at - chrono::Utc::now();
+
+    assert!((lifetime.num_seconds() - REFRESH_TOKEN_TTL).abs() <= 1);
+}
+
+#[test]
+fn should_hash_tokens_deterministically() {
+    common::init();
+
+    let hash = hash_token("some-token");
+
+    assert_eq!(hash, hash_token("some-token"));
+    assert_ne!(hash, hash_token("other-token"));
+    assert_eq!(hash.len(), 64);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{
+    TEST_PASSWORD, create_test_user_with_email, delete_test_user, emails, mail_dir,
+    remove_mail_dir, unique_email,
+};
+use rust_auth_service::{
+    db::user::get_user_by_email,
+    errors::my_error::MyError,
+    models::user::{UserRegister, UserStatus},
+    services::{
+        mailer::FileMailer, password::verify_password, password_policy::PasswordViolation,
+        registration::register_user,
+    },
+};
+use sqlx::PgPool;
+use std::path::PathBuf;
+
+fn setup() -> (PgPool, String, PathBuf) {
+    (common::pool(), unique_email("register"), mail_dir())
+}
+
+fn registration(email: &str, password: &str) -> UserRegister {
+    UserRegister {
+        name: Some("Register User".to_string()),
+        email: Some(email.to_string()),
+        password: Some(password.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn should_register_pending_user_and_send_verification_link() {
+    let (pool, email, dir) = setup();
+    let mailer = FileMailer::new(&dir);
+
+    register_user(&pool, &mailer, registration(&email.to_uppercase(), "Sign-up-secret1")).await.unwrap();
+
+    let user = get_user_by_email(&pool, email.clone()).await.unwrap().unwrap();
+    assert_eq!(user.status, UserStatus::Pending);
+
+    let sent = emails(&dir);
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].contains("token="));
+
+    delete_test_user(&pool, user.id).await;
+    remove_mail_dir(&dir);
+}
+
+#[tokio::test]
+async fn should_notify_owner_instead_of_disclosing_existing_account() {
+    let (pool, email, dir) = setup();
+    let mailer = FileMailer::new(&dir);
+    let existing = create_test_user_with_email(&pool, "Register User", &email).await;
+
+    // Same outcome for the caller as a fresh sign-up
+    register_user(&pool, &mailer, registration(&email, "Attacker-secret2")).await.unwrap();
+
+    let user = get_user_by_email(&pool, email.clone()).await.unwrap().unwrap();
+    assert_eq!(user.id, existing.id);
+    assert!(verify_password(&user.password, TEST_PASSWORD).unwrap());
+
+    let sent = emails(&dir);
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].contains(&format!("To: {}", email)));
+    assert!(sent[0].contains("already has an account"));
+    assert!(!sent[0].contains("token="));
+
+    delete_test_user(&pool, existing.id).await;
+    remove_mail_dir(&dir);
+}
+
+#[tokio::test]
+async fn should_refuse_weak_password_before_looking_up_the_address() {
+    let (pool, email, dir) = setup();
+    let mailer = FileMailer::new(&dir);
+
+    let result = register_user(&pool, &mailer, registration(&email, "register1")).await;
+
+    match result {
+        Err(MyError::PasswordPolicy(violations)) => {
+            assert!(violations.contains(&PasswordViolation::MissingUppercase));
+            assert!(violations.contains(&PasswordViolation::ContainsPersonalInfo));
+        }
+        other => panic!("expected a password policy error, got {:?}", other.err()),
+    }
+
+    assert!(get_user_by_email(&pool, email).await.unwrap().is_none());
+    assert!(!dir.exists());
+}
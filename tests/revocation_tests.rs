@@ -0,0 +1,86 @@
+mod common;
+
+use common::{create_test_account, delete_test_user, mail_dir, remove_mail_dir};
+use redis::AsyncCommands;
+use rust_auth_service::{
+    auth::{
+        auth::{ACCESS_TOKEN_TTL, REFRESH_TOKEN_TTL, decode_token},
+        grants::{rotate_refresh_token, start_session},
+        revocation::{revocation_ttl, revoke_token},
+    },
+    errors::my_error::MyError,
+    models::session::Session,
+    services::client_info::ClientInfo,
+};
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[test]
+fn should_keep_revoked_access_token_until_expiry() {
+    let now = now();
+    let exp = (now + ACCESS_TOKEN_TTL) as usize;
+
+    assert_eq!(revocation_ttl(exp, now), Some(ACCESS_TOKEN_TTL as u64));
+}
+
+#[test]
+fn should_keep_revoked_refresh_token_until_expiry() {
+    let now = now();
+    let exp = (now + REFRESH_TOKEN_TTL) as usize;
+
+    let ttl = revocation_ttl(exp, now).unwrap();
+
+    // A fixed TTL would let a revoked refresh token be redeemed again within its lifetime
+    assert_eq!(ttl, REFRESH_TOKEN_TTL as u64);
+    assert!(ttl > 600);
+}
+
+#[test]
+fn should_shrink_ttl_as_token_ages() {
+    let issued_at = now();
+    let exp = (issued_at + REFRESH_TOKEN_TTL) as usize;
+    let a_day_later = issued_at + 60 * 60 * 24;
+
+    assert_eq!(
+        revocation_ttl(exp, a_day_later),
+        Some((REFRESH_TOKEN_TTL - 60 * 60 * 24) as u64)
+    );
+}
+
+#[test]
+fn should_not_store_expired_tokens() {
+    let now = now();
+
+    assert_eq!(revocation_ttl(now as usize, now), None);
+    assert_eq!(revocation_ttl((now - 60) as usize, now), None);
+}
+
+#[tokio::test]
+async fn should_refuse_a_revoked_refresh_token_for_its_whole_lifetime() {
+    let dir = mail_dir();
+    let app_state = common::app_state(&dir).await;
+    let user = create_test_account(&app_state.pool, "Test User").await;
+
+    let session = Session::new(user.id, ClientInfo::default());
+    let (_, refresh_token) = start_session(&app_state.pool, &user, &session).await.unwrap();
+    let claims = decode_token(&refresh_token, None).unwrap();
+
+    let mut redis_conn = app_state.redis.clone();
+    revoke_token(&mut redis_conn, &claims).await.unwrap();
+
+    // The denylist entry lives as long as the token, not a fixed 600 seconds
+    let ttl: i64 = redis_conn.ttl(format!("jti_revoked:{}", claims.jti)).await.unwrap();
+    let remaining = claims.exp as i64 - now();
+    assert!(ttl > 600);
+    assert!((remaining - 5..=remaining).contains(&ttl));
+
+    assert!(matches!(
+        rotate_refresh_token(&app_state, &refresh_token, None).await,
+        Err(MyError::Validation(_))
+    ));
+
+    delete_test_user(&app_state.pool, user.id).await;
+    remove_mail_dir(&dir);
+}
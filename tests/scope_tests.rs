@@ -0,0 +1,130 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    middleware::from_fn,
+    routing::get,
+};
+use common::TestClaims;
+use rust_auth_service::{
+    middleware::auth::{require_client, require_scope},
+    models::{
+        auth::Claims,
+        scope::{
+            ADMIN_SCOPE, INTROSPECT_SCOPE, USERS_READ_SCOPE, USERS_WRITE_SCOPE, restrict_to_roles,
+            role_scopes, user_scopes,
+        },
+    },
+};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn claims(client_id: Option<&str>, scope: Option<&str>) -> Claims {
+    Claims {
+        client_id: client_id.map(str::to_string),
+        scope: scope.map(str::to_string),
+        ..Claims::for_test(Uuid::new_v4())
+    }
+}
+
+async fn call(required_scope: &str, claims: Claims) -> StatusCode {
+    let app = Router::new()
+        .route("/", get(|| async { "OK" }))
+        .layer(from_fn(require_scope(required_scope)));
+
+    let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+    request.extensions_mut().insert(claims);
+
+    app.oneshot(request).await.unwrap().status()
+}
+
+// Layered like the introspection endpoint
+async fn introspect(claims: Claims) -> StatusCode {
+    let app = Router::new()
+        .route("/", get(|| async { "OK" }))
+        .layer(from_fn(require_scope(INTROSPECT_SCOPE)))
+        .layer(from_fn(require_client));
+
+    let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+    request.extensions_mut().insert(claims);
+
+    app.oneshot(request).await.unwrap().status()
+}
+
+fn roles(roles: &[&str]) -> Vec<String> {
+    roles.iter().map(|role| role.to_string()).collect()
+}
+
+#[test]
+fn should_give_admins_every_api_scope() {
+    let user = role_scopes(&roles(&["User"]));
+    let admin = role_scopes(&roles(&["User", "Admin"]));
+
+    assert!(user.contains(&USERS_READ_SCOPE));
+    assert!(!user.contains(&ADMIN_SCOPE));
+    assert!(admin.contains(&ADMIN_SCOPE));
+    assert_eq!(admin.len(), 5);
+}
+
+#[test]
+fn should_intersect_login_scopes_with_roles() {
+    let scope = user_scopes("openid users:read admin reports:read", &roles(&["User"]));
+
+    assert_eq!(scope, "openid users:read");
+    assert_eq!(user_scopes("admin", &roles(&["User"])), "");
+}
+
+#[test]
+fn should_restrict_only_api_scopes_to_roles() {
+    let scope = restrict_to_roles("openid users:write admin reports:read", &roles(&["User"]));
+
+    // Scopes of other resource servers were already checked against the client
+    assert_eq!(scope, "openid users:write reports:read");
+}
+
+#[tokio::test]
+async fn should_allow_token_with_required_scope() {
+    let status = call(USERS_READ_SCOPE, claims(Some("client"), Some("openid users:read"))).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn should_reject_token_without_required_scope() {
+    let status = call(USERS_WRITE_SCOPE, claims(Some("client"), Some("users:read"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // A login narrowed to some scopes is held to them as well
+    let status = call(USERS_WRITE_SCOPE, claims(None, Some("users:read"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn should_treat_unscoped_tokens_by_issuer() {
+    // Direct logins are unrestricted, a client without a granted scope gets nothing
+    assert_eq!(call(USERS_READ_SCOPE, claims(None, None)).await, StatusCode::OK);
+    assert_eq!(
+        call(USERS_READ_SCOPE, claims(Some("client"), None)).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn should_only_let_clients_with_the_scope_introspect() {
+    let machine = |scope: &str| Claims {
+        machine: true,
+        ..claims(Some("service"), Some(scope))
+    };
+
+    assert_eq!(introspect(machine("tokens:introspect")).await, StatusCode::OK);
+    assert_eq!(introspect(machine("reports:read")).await, StatusCode::UNAUTHORIZED);
+
+    // Users may not, even an unrestricted login or a client acting for them with the scope
+    assert_eq!(introspect(claims(None, None)).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        introspect(claims(Some("client"), Some("tokens:introspect"))).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
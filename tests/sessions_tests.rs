@@ -0,0 +1,282 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    extract::ConnectInfo,
+    http::{HeaderMap, HeaderValue, Request},
+    routing::get,
+};
+use common::{create_test_account, create_test_user, delete_test_user};
+use rust_auth_service::{
+    auth::{
+        auth::{decode_access_token, generate_tokens, validate_jwt},
+        grants::start_session,
+        revocation::revoke_session_tokens,
+    },
+    db::{
+        auth::{
+            create_refresh_token, create_session, get_refresh_token, get_user_sessions,
+            revoke_session, revoke_user_sessions,
+        },
+        role::{get_role_by_name, set_user_role},
+        user::{delete_user, get_token_epoch, get_user_account_by_id, increment_token_epoch},
+    },
+    models::session::Session,
+    services::client_info::{ClientInfo, ClientIp, TrustedProxy, client_ip},
+};
+use sqlx::PgPool;
+use std::net::{IpAddr, SocketAddr};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+async fn setup() -> (PgPool, Uuid) {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    (pool, user.id)
+}
+
+async fn login(pool: &PgPool, user_id: Uuid, device: &str) -> (Uuid, String) {
+    let session = Session::new(
+        user_id,
+        ClientInfo {
+            device_name: Some(device.to_string()),
+            ..Default::default()
+        },
+    );
+    create_session(pool, &session).await.unwrap();
+
+    let token = format!("token-{}", Uuid::new_v4());
+    create_refresh_token(pool, user_id, session.id, &token).await.unwrap();
+
+    (session.id, token)
+}
+
+#[test]
+fn should_read_client_info_from_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert("user-agent", HeaderValue::from_static("Mozilla/5.0"));
+    headers.insert("x-device-name", HeaderValue::from_static("  Work laptop "));
+
+    let client = ClientInfo::new(&headers, &ClientIp(Some("203.0.113.7".to_string())));
+
+    assert_eq!(client.ip_address.as_deref(), Some("203.0.113.7"));
+    assert_eq!(client.user_agent.as_deref(), Some("Mozilla/5.0"));
+    assert_eq!(client.device_name.as_deref(), Some("Work laptop"));
+}
+
+fn forwarded_for(value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", HeaderValue::from_static(value));
+
+    headers
+}
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn should_ignore_forwarded_for_from_untrusted_peers() {
+    let headers = forwarded_for("203.0.113.7");
+
+    // Sent straight to the service, the header is whatever the client wants it to be
+    assert_eq!(client_ip(Some(ip("198.51.100.1")), &headers, &[]), Some(ip("198.51.100.1")));
+
+    let proxies = [TrustedProxy::parse("10.0.0.1").unwrap()];
+    assert_eq!(client_ip(Some(ip("198.51.100.1")), &headers, &proxies), Some(ip("198.51.100.1")));
+}
+
+#[test]
+fn should_take_the_client_from_trusted_proxies() {
+    let proxies = [TrustedProxy::parse("10.0.0.0/8").unwrap()];
+
+    // The leftmost entry was made up by the client; the first hop no trusted proxy added is the client
+    let headers = forwarded_for("192.0.2.1, 203.0.113.7, 10.0.0.3");
+    assert_eq!(client_ip(Some(ip("10.0.0.2")), &headers, &proxies), Some(ip("203.0.113.7")));
+
+    // A garbled hop ends the chain at the proxy that passed it on
+    let headers = forwarded_for("203.0.113.7, not-an-ip");
+    assert_eq!(client_ip(Some(ip("10.0.0.2")), &headers, &proxies), Some(ip("10.0.0.2")));
+
+    assert_eq!(client_ip(None, &headers, &proxies), None);
+}
+
+#[tokio::test]
+async fn should_key_requests_on_the_connection_address() {
+    let app = Router::new().route(
+        "/",
+        get(|ClientIp(client_ip): ClientIp| async move { client_ip.unwrap_or_default() }),
+    );
+
+    let mut request = Request::builder()
+        .uri("/")
+        .header("x-forwarded-for", "203.0.113.7")
+        .body(Body::empty())
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([198, 51, 100, 1], 4711))));
+
+    let response = app.oneshot(request).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    assert_eq!(&body[..], b"198.51.100.1");
+}
+
+#[test]
+fn should_match_trusted_proxy_ranges() {
+    let range = TrustedProxy::parse("10.0.0.0/8").unwrap();
+
+    assert!(range.contains(ip("10.1.2.3")));
+    assert!(range.contains(ip("::ffff:10.1.2.3")));
+    assert!(!range.contains(ip("11.0.0.1")));
+    assert!(TrustedProxy::parse("::1").unwrap().contains(ip("::1")));
+    assert!(TrustedProxy::parse("10.0.0.0/33").is_none());
+    assert!(TrustedProxy::parse("proxy.internal").is_none());
+}
+
+#[test]
+fn should_mark_current_session() {
+    let session = Session::new(Uuid::new_v4(), ClientInfo::default());
+
+    assert!(session.to_output(Some(session.id)).current);
+    assert!(!session.to_output(Some(Uuid::new_v4())).current);
+    assert!(!session.to_output(None).current);
+}
+
+#[tokio::test]
+async fn should_keep_multiple_sessions_per_user() {
+    let (pool, user_id) = setup().await;
+
+    let (laptop, laptop_token) = login(&pool, user_id, "laptop").await;
+    let (phone, phone_token) = login(&pool, user_id, "phone").await;
+
+    let sessions = get_user_sessions(&pool, user_id).await.unwrap();
+    let ids: Vec<Uuid> = sessions.iter().map(|session| session.id).collect();
+
+    assert_eq!(sessions.len(), 2);
+    assert!(ids.contains(&laptop));
+    assert!(ids.contains(&phone));
+
+    assert!(get_refresh_token(&pool, &laptop_token).await.unwrap().unwrap().revoked_at.is_none());
+    assert!(get_refresh_token(&pool, &phone_token).await.unwrap().unwrap().revoked_at.is_none());
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_revoke_a_single_session() {
+    let (pool, user_id) = setup().await;
+
+    let (laptop, laptop_token) = login(&pool, user_id, "laptop").await;
+    let (phone, phone_token) = login(&pool, user_id, "phone").await;
+
+    assert!(revoke_session(&pool, user_id, laptop).await.unwrap());
+    assert!(!revoke_session(&pool, user_id, laptop).await.unwrap());
+
+    let sessions = get_user_sessions(&pool, user_id).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, phone);
+
+    assert!(get_refresh_token(&pool, &laptop_token).await.unwrap().unwrap().revoked_at.is_some());
+    assert!(get_refresh_token(&pool, &phone_token).await.unwrap().unwrap().revoked_at.is_none());
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_sessions_of_other_users() {
+    let (pool, owner_id) = setup().await;
+    let (_, other_id) = setup().await;
+
+    let (session, _) = login(&pool, owner_id, "laptop").await;
+
+    assert!(!revoke_session(&pool, other_id, session).await.unwrap());
+    assert_eq!(get_user_sessions(&pool, owner_id).await.unwrap().len(), 1);
+
+    delete_test_user(&pool, owner_id).await;
+    delete_test_user(&pool, other_id).await;
+}
+
+#[tokio::test]
+async fn should_sign_out_other_devices() {
+    let (pool, user_id) = setup().await;
+
+    let (current, _) = login(&pool, user_id, "laptop").await;
+    let (_, phone_token) = login(&pool, user_id, "phone").await;
+    let (_, tablet_token) = login(&pool, user_id, "tablet").await;
+
+    let revoked = revoke_user_sessions(&pool, user_id, None, Some(current)).await.unwrap();
+    assert_eq!(revoked.len(), 2);
+
+    let sessions = get_user_sessions(&pool, user_id).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, current);
+
+    assert!(get_refresh_token(&pool, &phone_token).await.unwrap().unwrap().revoked_at.is_some());
+    assert!(get_refresh_token(&pool, &tablet_token).await.unwrap().unwrap().revoked_at.is_some());
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_reject_access_tokens_of_a_revoked_session() {
+    let pool = common::pool();
+    let mut redis = common::redis().await;
+    let user = create_test_account(&pool, "Test User").await;
+
+    let session = Session::new(user.id, ClientInfo::default());
+    let (access_token, _) = start_session(&pool, &user, &session).await.unwrap();
+    assert!(validate_jwt(&pool, &mut redis, &access_token).await.is_ok());
+
+    assert!(revoke_session(&pool, user.id, session.id).await.unwrap());
+    revoke_session_tokens(&mut redis, &[session.id]).await.unwrap();
+
+    assert!(validate_jwt(&pool, &mut redis, &access_token).await.is_err());
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_embed_the_current_token_epoch() {
+    let (pool, user_id) = setup().await;
+
+    let epoch = get_token_epoch(&pool, user_id).await.unwrap().unwrap();
+    let user = get_user_account_by_id(&pool, user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let (access_token, refresh_token) = generate_tokens(&pool, &user).await.unwrap();
+
+    assert_eq!(decode_access_token(&access_token).unwrap().epoch, epoch);
+    assert_eq!(decode_access_token(&refresh_token).unwrap().epoch, epoch);
+
+    delete_test_user(&pool, user_id).await;
+}
+
+#[tokio::test]
+async fn should_bump_token_epoch() {
+    let (pool, user_id) = setup().await;
+
+    let before = get_token_epoch(&pool, user_id).await.unwrap().unwrap();
+    let after = increment_token_epoch(&pool, user_id).await.unwrap();
+
+    assert_eq!(after, before + 1);
+    assert_eq!(get_token_epoch(&pool, user_id).await.unwrap(), Some(after));
+
+    delete_user(&pool, user_id).await.unwrap();
+    assert_eq!(get_token_epoch(&pool, user_id).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn should_bump_token_epoch_on_role_change() {
+    let (pool, user_id) = setup().await;
+    let before = get_token_epoch(&pool, user_id).await.unwrap().unwrap();
+
+    let admin = get_role_by_name(&pool, "Admin".to_string()).await.unwrap();
+    set_user_role(&pool, user_id, admin.id).await.unwrap();
+
+    assert!(get_token_epoch(&pool, user_id).await.unwrap().unwrap() > before);
+
+    delete_test_user(&pool, user_id).await;
+}
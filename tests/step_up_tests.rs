@@ -0,0 +1,177 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    middleware::from_fn,
+    routing::{get, post},
+};
+use common::{TEST_PASSWORD, create_test_account, mail_dir, remove_mail_dir};
+use rust_auth_service::{
+    auth::{
+        auth::{
+            ELEVATED_TOKEN_TTL, STEP_UP_MAX_AGE, TokenOptions, decode_access_token,
+            issue_elevated_token, issue_tokens,
+        },
+        lockout::lockout_policy,
+    },
+    db::user::delete_user,
+    handlers::auth::reauthenticate_handler,
+    middleware::auth::require_recent_auth,
+    models::{
+        auth::{ACR_ELEVATED, ACR_SESSION, AMR_PASSWORD, Claims},
+        user::User,
+    },
+};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+async fn setup() -> (PgPool, User) {
+    let pool = common::pool();
+    let user = create_test_account(&pool, "Test User").await;
+
+    (pool, user)
+}
+
+async fn session_token(pool: &PgPool, user: &User, auth_time: usize) -> Claims {
+    let options = TokenOptions {
+        auth_time: Some(auth_time),
+        ..Default::default()
+    };
+    let (access_token, _) = issue_tokens(pool, user, &options).await.unwrap();
+
+    decode_access_token(&access_token).unwrap()
+}
+
+async fn call(claims: Claims) -> axum::response::Response {
+    let app = Router::new()
+        .route("/", get(|| async { "OK" }))
+        .layer(from_fn(require_recent_auth(STEP_UP_MAX_AGE)));
+
+    let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+    request.extensions_mut().insert(claims);
+
+    app.oneshot(request).await.unwrap()
+}
+
+async fn reauthenticate(app: Router, claims: Claims, password: &str) -> StatusCode {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::json!({ "password": password }).to_string()))
+        .unwrap();
+    request.extensions_mut().insert(claims);
+
+    app.oneshot(request).await.unwrap().status()
+}
+
+fn now() -> usize {
+    chrono::Utc::now().timestamp() as usize
+}
+
+#[tokio::test]
+async fn should_record_how_the_user_authenticated() {
+    let (pool, user) = setup().await;
+    let auth_time = now() - 60;
+
+    let claims = session_token(&pool, &user, auth_time).await;
+
+    assert_eq!(claims.auth_time, Some(auth_time));
+    assert_eq!(claims.amr, vec![AMR_PASSWORD.to_string()]);
+    assert_eq!(claims.acr.as_deref(), Some(ACR_SESSION));
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_allow_recent_authentication() {
+    let (pool, user) = setup().await;
+
+    let claims = session_token(&pool, &user, now() - 60).await;
+
+    assert_eq!(call(claims).await.status(), StatusCode::OK);
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_demand_step_up_for_old_authentication() {
+    let (pool, user) = setup().await;
+
+    // e.g. a token refreshed six days after the login
+    let claims = session_token(&pool, &user, now() - 60 * 60 * 24 * 6).await;
+    let response = call(claims).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let challenge = response.headers()[header::WWW_AUTHENTICATE].to_str().unwrap();
+    assert!(challenge.contains("insufficient_user_authentication"));
+    assert!(challenge.contains(&format!("max_age={}", STEP_UP_MAX_AGE)));
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_demand_step_up_without_auth_time() {
+    let (pool, user) = setup().await;
+
+    let (access_token, _) = issue_tokens(&pool, &user, &TokenOptions::default()).await.unwrap();
+    let claims = decode_access_token(&access_token).unwrap();
+
+    assert_eq!(call(claims).await.status(), StatusCode::UNAUTHORIZED);
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_issue_elevated_token_from_stale_one() {
+    let (pool, user) = setup().await;
+    let stale = session_token(&pool, &user, now() - 60 * 60 * 24 * 6).await;
+
+    let token = issue_elevated_token(&pool, &user, &stale).await.unwrap();
+    let claims = decode_access_token(&token).unwrap();
+
+    assert_eq!(claims.acr.as_deref(), Some(ACR_ELEVATED));
+    assert_eq!(claims.exp - claims.iat, ELEVATED_TOKEN_TTL as usize);
+    assert_eq!(claims.sid, stale.sid);
+    assert_eq!(call(claims).await.status(), StatusCode::OK);
+
+    delete_user(&pool, user.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn should_lock_out_reauthentication_after_repeated_wrong_passwords() {
+    let (pool, user) = setup().await;
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/", post(reauthenticate_handler))
+        .with_state(common::app_state(&dir).await);
+    let claims = session_token(&pool, &user, now() - 60 * 60 * 24 * 6).await;
+
+    for _ in 0..lockout_policy().max_account_failures {
+        let status = reauthenticate(app.clone(), claims.clone(), "Wrong-secret3").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    let status = reauthenticate(app, claims, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    delete_user(&pool, user.id).await.unwrap();
+    remove_mail_dir(&dir);
+}
+
+#[tokio::test]
+async fn should_reauthenticate_with_the_right_password() {
+    let (pool, user) = setup().await;
+    let dir = mail_dir();
+    let app = Router::new()
+        .route("/", post(reauthenticate_handler))
+        .with_state(common::app_state(&dir).await);
+    let claims = session_token(&pool, &user, now() - 60 * 60 * 24 * 6).await;
+
+    assert_eq!(reauthenticate(app, claims, TEST_PASSWORD).await, StatusCode::OK);
+
+    delete_user(&pool, user.id).await.unwrap();
+    remove_mail_dir(&dir);
+}
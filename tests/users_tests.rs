@@ -1,6 +1,71 @@
-use rust_auth_service::models::user::User;
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    middleware::from_fn,
+    routing::{get, patch},
+};
+use common::{
+    TEST_PASSWORD, TestClaims, create_test_account, create_test_user, delete_test_user, mail_dir,
+    remove_mail_dir, unique_email, wait_for_emails,
+};
+use rust_auth_service::{
+    auth::grants::start_session,
+    db::{
+        auth::get_session,
+        user::{get_token_epoch, get_user_account_by_id, mark_email_verified, set_user_status},
+    },
+    handlers::user::update_user_handler,
+    middleware::auth::{can_access_user, require_owner_or_role},
+    models::{
+        app::AppState,
+        auth::Claims,
+        session::Session,
+        user::{User, UserStatus},
+    },
+    services::{client_info::ClientInfo, password::verify_password},
+};
+use tower::ServiceExt;
 use uuid::Uuid;
 
+fn claims(sub: Uuid, roles: &[&str]) -> Claims {
+    Claims {
+        roles: roles.iter().map(|role| role.to_string()).collect(),
+        ..Claims::for_test(sub)
+    }
+}
+
+async fn call(claims: Claims, user_id: &str) -> StatusCode {
+    let app = Router::new()
+        .route("/users/{user_id}", get(|| async { "OK" }))
+        .layer(from_fn(require_owner_or_role(vec!["Admin".to_string()])));
+
+    let mut request = Request::builder()
+        .uri(format!("/users/{}", user_id))
+        .body(Body::empty())
+        .unwrap();
+    request.extensions_mut().insert(claims);
+
+    app.oneshot(request).await.unwrap().status()
+}
+
+async fn update(app_state: AppState, user_id: Uuid, payload: serde_json::Value) -> StatusCode {
+    let app = Router::new()
+        .route("/users/{user_id}", patch(update_user_handler))
+        .with_state(app_state);
+
+    let request = Request::builder()
+        .method("PATCH")
+        .uri(format!("/users/{}", user_id))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(payload.to_string()))
+        .unwrap();
+
+    app.oneshot(request).await.unwrap().status()
+}
+
 #[test]
 fn should_create_user_with_valid_data() {
     let user = User::new(
@@ -267,3 +332,221 @@ fn should_handle_name_with_spaces() {
 
     assert_eq!(user.name, "John   Doe"); // Should trim but preserve internal spaces
 }
+
+#[tokio::test]
+async fn should_allow_access_to_own_account() {
+    let user_id = Uuid::new_v4();
+
+    let status = call(claims(user_id, &["User"]), &user_id.to_string()).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn should_forbid_access_to_another_account() {
+    let status = call(claims(Uuid::new_v4(), &["User"]), &Uuid::new_v4().to_string()).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn should_allow_admin_access_to_another_account() {
+    let status = call(claims(Uuid::new_v4(), &["User", "Admin"]), &Uuid::new_v4().to_string()).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn should_forbid_roles_not_listed() {
+    let status = call(claims(Uuid::new_v4(), &["Moderator"]), &Uuid::new_v4().to_string()).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn should_reject_invalid_user_id() {
+    let status = call(claims(Uuid::new_v4(), &["Admin"]), "not-a-uuid").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn should_require_claims_for_user_routes() {
+    let app = Router::new()
+        .route("/users/{user_id}", get(|| async { "OK" }))
+        .layer(from_fn(require_owner_or_role(vec!["Admin".to_string()])));
+
+    let request = Request::builder()
+        .uri(format!("/users/{}", Uuid::new_v4()))
+        .body(Body::empty())
+        .unwrap();
+
+    assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn should_check_user_access() {
+    let user_id = Uuid::new_v4();
+    let admin = vec!["Admin".to_string()];
+
+    assert!(can_access_user(&claims(user_id, &[]), user_id, &admin));
+    assert!(!can_access_user(&claims(user_id, &["User"]), Uuid::new_v4(), &admin));
+    assert!(can_access_user(&claims(user_id, &["Admin"]), Uuid::new_v4(), &admin));
+}
+
+#[tokio::test]
+async fn should_update_only_the_password() {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    let status = update(common::app_state(&mail_dir()).await, user.id, serde_json::json!({ "password": "New-secret2" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let stored = get_user_account_by_id(&pool, user.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, user.email);
+    assert_eq!(stored.name, user.name);
+    assert!(verify_password(&stored.password, "New-secret2").unwrap());
+    assert!(!verify_password(&stored.password, TEST_PASSWORD).unwrap());
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_update_only_the_name() {
+    let pool = common::pool();
+    let user = create_test_user(&pool, "Test User").await;
+
+    let status = update(common::app_state(&mail_dir()).await, user.id, serde_json::json!({ "name": "Renamed User" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let stored = get_user_account_by_id(&pool, user.id).await.unwrap().unwrap();
+    assert_eq!(stored.name, "Renamed User");
+    assert_eq!(stored.email, user.email);
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_sign_out_everywhere_when_only_the_password_changes() {
+    let pool = common::pool();
+    let user = create_test_account(&pool, "Test User").await;
+
+    let session = Session::new(user.id, ClientInfo::default());
+    start_session(&pool, &user, &session).await.unwrap();
+    let epoch = get_token_epoch(&pool, user.id).await.unwrap().unwrap();
+
+    let status = update(common::app_state(&mail_dir()).await, user.id, serde_json::json!({ "password": "New-secret2" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert!(get_token_epoch(&pool, user.id).await.unwrap().unwrap() > epoch);
+    assert!(get_session(&pool, session.id).await.unwrap().unwrap().revoked_at.is_some());
+
+    delete_test_user(&pool, user.id).await;
+}
+
+#[tokio::test]
+async fn should_reverify_a_changed_email() {
+    let pool = common::pool();
+    let dir = mail_dir();
+    let user = create_test_user(&pool, "Test User").await;
+    mark_email_verified(&pool, user.id).await.unwrap();
+
+    let new_email = unique_email("moved");
+    let status = update(
+        common::app_state(&dir).await,
+        user.id,
+        serde_json::json!({ "email": new_email.to_uppercase() }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let stored = get_user_account_by_id(&pool, user.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, new_email);
+    assert!(stored.email_verified_at.is_none());
+
+    let sent = wait_for_emails(&dir, 1).await;
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].contains(&new_email));
+
+    delete_test_user(&pool, user.id).await;
+    remove_mail_dir(&dir);
+}
+
+#[tokio::test]
+async fn should_keep_verification_when_the_email_is_unchanged() {
+    let pool = common::pool();
+    let dir = mail_dir();
+    let user = create_test_user(&pool, "Test User").await;
+    mark_email_verified(&pool, user.id).await.unwrap();
+
+    let status = update(
+        common::app_state(&dir).await,
+        user.id,
+        serde_json::json!({ "name": "Renamed User", "email": user.email }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let stored = get_user_account_by_id(&pool, user.id).await.unwrap().unwrap();
+    assert!(stored.email_verified_at.is_some());
+    assert!(wait_for_emails(&dir, 1).await.is_empty());
+
+    delete_test_user(&pool, user.id).await;
+    remove_mail_dir(&dir);
+}
+
+#[tokio::test]
+async fn should_refuse_an_invalid_email() {
+    let pool = common::pool();
+    let dir = mail_dir();
+    let user = create_test_user(&pool, "Test User").await;
+
+    let payload = serde_json::json!({ "email": "not-an-email" });
+    let status = update(common::app_state(&dir).await, user.id, payload).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let stored = get_user_account_by_id(&pool, user.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, user.email);
+
+    delete_test_user(&pool, user.id).await;
+    remove_mail_dir(&dir);
+}
+
+#[tokio::test]
+async fn should_refuse_an_email_held_by_another_account() {
+    let pool = common::pool();
+    let dir = mail_dir();
+    let user = create_test_user(&pool, "Test User").await;
+    let other = create_test_user(&pool, "Other User").await;
+
+    let payload = serde_json::json!({ "email": other.email });
+    let status = update(common::app_state(&dir).await, user.id, payload).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let stored = get_user_account_by_id(&pool, user.id).await.unwrap().unwrap();
+    assert_eq!(stored.email, user.email);
+
+    delete_test_user(&pool, user.id).await;
+    delete_test_user(&pool, other.id).await;
+    remove_mail_dir(&dir);
+}
+
+#[tokio::test]
+async fn should_refuse_edits_to_a_deleted_account() {
+    let pool = common::pool();
+    let dir = mail_dir();
+    let user = create_test_user(&pool, "Test User").await;
+    set_user_status(&pool, user.id, UserStatus::Pending, UserStatus::Deleted, None)
+        .await
+        .unwrap();
+
+    let payload = serde_json::json!({ "name": "Revived" });
+    let status = update(common::app_state(&dir).await, user.id, payload).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let stored = get_user_account_by_id(&pool, user.id).await.unwrap().unwrap();
+    assert_eq!(stored.name, "Test User");
+
+    delete_test_user(&pool, user.id).await;
+    remove_mail_dir(&dir);
+}
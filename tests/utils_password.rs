@@ -1,4 +1,4 @@
-use rust_auth_service::services::password::{hash_password, verify_password};
+use rust_auth_service::services::password::{hash_password, verify_password, verify_password_or_dummy};
 
 #[test]
 fn test_hash_password() {
@@ -6,3 +6,13 @@ fn test_hash_password() {
     let hash = hash_password(password).unwrap();
     assert!(verify_password(&hash, password).unwrap());
 }
+
+#[test]
+fn test_verify_password_or_dummy() {
+    let hash = hash_password("password").unwrap();
+
+    assert!(verify_password_or_dummy(Some(&hash), "password"));
+    assert!(!verify_password_or_dummy(Some(&hash), "wrong"));
+    // No account: still an Argon2 verification, never a match
+    assert!(!verify_password_or_dummy(None, "password"));
+}